@@ -0,0 +1,254 @@
+use crate::artifacts::{owning_project, ArtifactKind, Registry};
+use crate::cleaner::{parallel_map, project_target_dirs};
+use crate::walker::DiscoveredPaths;
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How deep `--age-sample` looks inside an artifact
+pub(crate) const SAMPLE_DEPTH: usize = 2;
+
+/// Where an artifact's last-use time comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AgeSource {
+    /// Newest modification time inside the artifact
+    Artifact,
+    /// Newest modification time of the owning project's sources
+    Project,
+    /// Whichever of the two is more recent
+    Both,
+}
+
+/// Selects only artifacts that have not been used for at least `min_age`
+#[derive(Debug, Clone)]
+pub struct AgeFilter {
+    pub min_age: Duration,
+    pub source: AgeSource,
+    /// Only look at the top levels of each artifact instead of every file
+    pub sample: bool,
+    /// Directory names never counted as project sources (artifacts, `.git`)
+    skip_names: Vec<&'static str>,
+}
+
+/// The age computed for one discovered item and whether it stays selected
+#[derive(Debug, Clone)]
+pub struct AgeDecision {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    /// Time since last use, or `None` if no modification time could be read
+    pub age: Option<Duration>,
+    /// True if the item is old enough to be cleaned
+    pub selected: bool,
+}
+
+impl AgeFilter {
+    pub fn new(min_age: Duration, source: AgeSource, sample: bool, registry: &Registry) -> Self {
+        let mut skip_names = vec![".git", "target", "target-ra"];
+        for detector in registry.detectors() {
+            skip_names.extend_from_slice(detector.dir_names);
+        }
+
+        AgeFilter {
+            min_age,
+            source,
+            sample,
+            skip_names,
+        }
+    }
+
+    /// Works out when a discovered item was last used
+    ///
+    /// Rust projects are judged by their target directories and their own sources; a workspace
+    /// root by every target directory cleaned with it and by its members' sources too, wherever
+    /// they are. Every other kind is judged by its contents and the project that owns it, if any.
+    pub fn last_used(
+        &self,
+        kind: ArtifactKind,
+        path: &Path,
+        members: &[PathBuf],
+    ) -> Option<SystemTime> {
+        let (artifacts, projects): (Vec<PathBuf>, Vec<&Path>) =
+            if kind == ArtifactKind::RUST_PROJECT {
+                let outside = members
+                    .iter()
+                    .map(PathBuf::as_path)
+                    .filter(|member| !member.starts_with(path));
+                (
+                    project_target_dirs(path, members),
+                    std::iter::once(path).chain(outside).collect(),
+                )
+            } else {
+                (
+                    vec![path.to_path_buf()],
+                    owning_project(path).into_iter().collect(),
+                )
+            };
+
+        let from_artifacts = || {
+            let depth = self.sample.then_some(SAMPLE_DEPTH);
+            artifacts
+                .iter()
+                .filter_map(|artifact| newest_mtime(artifact, depth, &[]))
+                .max()
+        };
+        let from_project = || {
+            projects
+                .iter()
+                .filter_map(|dir| newest_mtime(dir, None, &self.skip_names))
+                .max()
+        };
+
+        match self.source {
+            AgeSource::Artifact => from_artifacts(),
+            AgeSource::Project => from_project().or_else(from_artifacts),
+            AgeSource::Both => from_artifacts().max(from_project()),
+        }
+    }
+
+    /// Decides whether one item (with its workspace members, if any) is old enough to clean
+    pub fn decide(
+        &self,
+        kind: ArtifactKind,
+        path: &Path,
+        members: &[PathBuf],
+        now: SystemTime,
+    ) -> AgeDecision {
+        // A clock running behind a file's mtime means it was used just now
+        let age = self
+            .last_used(kind, path, members)
+            .map(|last_used| now.duration_since(last_used).unwrap_or_default());
+
+        AgeDecision {
+            kind,
+            path: path.to_path_buf(),
+            age,
+            // Without a last-use time we can't prove the item is stale, so leave it alone
+            selected: age.is_some_and(|age| age >= self.min_age),
+        }
+    }
+
+    /// Drops every discovered item used more recently than `min_age`, returning a decision for each
+    pub fn apply(&self, discovered: &mut DiscoveredPaths) -> Vec<AgeDecision> {
+        let now = SystemTime::now();
+        let items: Vec<(ArtifactKind, PathBuf)> = discovered
+            .iter()
+            .map(|(kind, path)| (kind, path.to_path_buf()))
+            .collect();
+
+        let decisions = parallel_map(&items, |(kind, path)| {
+            self.decide(*kind, path, discovered.members(path), now)
+        });
+        discovered.retain(|kind, path| {
+            decisions
+                .iter()
+                .any(|d| d.selected && d.kind == kind && d.path == path)
+        });
+        decisions
+    }
+}
+
+/// Returns the newest modification time of `root` and everything below it
+///
+/// Symlinks are not followed and directories named in `skip_names` are not entered.
+pub(crate) fn newest_mtime(
+    root: &Path,
+    max_depth: Option<usize>,
+    skip_names: &[&str],
+) -> Option<SystemTime> {
+    let skip_names: Vec<String> = skip_names.iter().map(|name| name.to_string()).collect();
+    WalkBuilder::new(root)
+        .standard_filters(false)
+        .follow_links(false)
+        .max_depth(max_depth)
+        .filter_entry(move |entry| {
+            entry.depth() == 0
+                || !entry.file_type().is_some_and(|t| t.is_dir())
+                || !entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| skip_names.iter().any(|s| s == name))
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter_map(|metadata| metadata.modified().ok())
+        .max()
+}
+
+/// Parses an age such as `30d`, `12h`, `2w`, `90m` or `45s`
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid age '{}' (expected e.g. 30d, 12h, 2w)",
+                input
+            ))
+        }
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid age '{}' (expected e.g. 30d, 12h, 2w)", input))?;
+
+    Ok(Duration::from_secs(number.saturating_mul(seconds_per_unit)))
+}
+
+/// Formats an age with its largest whole unit, e.g. `45d`, `3h` or `12m`
+pub fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        s if s >= 24 * 60 * 60 => format!("{}d", s / (24 * 60 * 60)),
+        s if s >= 60 * 60 => format!("{}h", s / (60 * 60)),
+        s if s >= 60 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Describes a last-use time relative to now, e.g. "last used 45d ago"
+pub fn describe_last_use(last_used: Option<SystemTime>) -> String {
+    match last_used {
+        Some(time) => {
+            let age = SystemTime::now().duration_since(time).unwrap_or_default();
+            format!("last used {} ago", format_age(age))
+        }
+        None => "last use unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_duration() {
+        assert_eq!(
+            parse_duration("30d"),
+            Ok(Duration::from_secs(30 * 24 * 60 * 60))
+        );
+        assert_eq!(
+            parse_duration("2w"),
+            Ok(Duration::from_secs(14 * 24 * 60 * 60))
+        );
+        assert_eq!(parse_duration("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3 days").is_err());
+
+        assert_eq!(
+            format_age(Duration::from_secs(45 * 24 * 60 * 60 + 5)),
+            "45d"
+        );
+        assert_eq!(format_age(Duration::from_secs(3 * 60 * 60)), "3h");
+        assert_eq!(format_age(Duration::from_secs(59)), "59s");
+    }
+}
@@ -0,0 +1,341 @@
+use crate::cleaner::{
+    is_cargo_nix_dir, is_cargo_target_dir, is_next_dir, is_node_modules_dir,
+    is_orphaned_node_modules_dir, is_orphaned_target_dir, is_python_venv_dir, is_rustup_dir,
+    is_sccache_dir, is_stack_work_dir, suspicious_cargo_nix_dir,
+};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Identifies one kind of artifact across discovery, CLI selection, output and logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArtifactKind(pub &'static str);
+
+impl ArtifactKind {
+    /// Cargo projects found via their `Cargo.toml`; cleaned by `clean_project` rather than a detector
+    pub const RUST_PROJECT: ArtifactKind = ArtifactKind("rust-project");
+    pub const ORPHANED_TARGET: ArtifactKind = ArtifactKind("orphaned-target");
+    /// A target directory several projects build into (`CARGO_TARGET_DIR` or `build.target-dir`)
+    pub const SHARED_TARGET: ArtifactKind = ArtifactKind("shared-target");
+    pub const NODE_MODULES: ArtifactKind = ArtifactKind("node-modules");
+    /// A `node_modules` with no `package.json` or lockfile left beside it
+    pub const ORPHANED_NODE_MODULES: ArtifactKind = ArtifactKind("orphaned-node-modules");
+    pub const PYTHON_VENV: ArtifactKind = ArtifactKind("python-venv");
+    pub const SCCACHE: ArtifactKind = ArtifactKind("sccache");
+    pub const STACK_WORK: ArtifactKind = ArtifactKind("stack-work");
+    pub const RUSTUP: ArtifactKind = ArtifactKind("rustup");
+    pub const NEXT: ArtifactKind = ArtifactKind("next");
+    pub const CARGO_NIX: ArtifactKind = ArtifactKind("cargo-nix");
+
+    /// The identifier used on the command line and in logs
+    pub fn id(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Everything wd-40 needs to know to find, validate, report and delete one kind of artifact
+#[derive(Debug, Clone)]
+pub struct Detector {
+    pub kind: ArtifactKind,
+    /// Directory names worth validating; anything else is never considered
+    pub dir_names: &'static [&'static str],
+    /// Full validation, run during discovery and again right before deletion
+    pub validate: fn(&Path) -> bool,
+    /// Shown next to each cleaned path
+    pub icon: &'static str,
+    /// Tag used for this kind in the log file and dry-run output
+    pub log_tag: &'static str,
+    /// Files and directories validation looks for, relative to the artifact (`../` for its
+    /// parent); the ones present are reported alongside each artifact
+    pub markers: &'static [&'static str],
+    /// Singular and plural nouns, e.g. ("node_modules directory", "node_modules directories")
+    pub noun: (&'static str, &'static str),
+}
+
+impl Detector {
+    /// Returns true if a directory with this name should be validated by this detector
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.dir_names.contains(&name)
+    }
+
+    /// Returns why a directory with one of this detector's names, which failed validation, still
+    /// looks enough like the artifact to report rather than ignore
+    ///
+    /// Only detectors whose validation goes beyond the basics have anything to say here.
+    pub fn suspicion(&self, path: &Path) -> Option<String> {
+        match self.kind {
+            ArtifactKind::CARGO_NIX => suspicious_cargo_nix_dir(path),
+            _ => None,
+        }
+    }
+
+    /// Returns the markers present for the artifact at `path`
+    pub fn matched_markers(&self, path: &Path) -> Vec<&'static str> {
+        matched_markers(path, self.markers)
+    }
+
+    /// Picks the singular or plural noun for `count` items
+    pub fn noun(&self, count: usize) -> &'static str {
+        if count == 1 {
+            self.noun.0
+        } else {
+            self.noun.1
+        }
+    }
+}
+
+/// A candidate that failed its detector's validation in a way worth reporting; never cleaned
+#[derive(Debug, Clone)]
+pub struct SuspiciousPath {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Markers that identify a Cargo project and the target directories cleaned with it
+pub const PROJECT_MARKERS: &[&str] = &[
+    "Cargo.toml",
+    "target/CACHEDIR.TAG",
+    "target/.rustc_info.json",
+    "target-ra/CACHEDIR.TAG",
+    "target-ra/.rustc_info.json",
+];
+
+/// Returns the entries of `markers` that exist relative to `path` (`../` meaning its parent)
+pub fn matched_markers(path: &Path, markers: &[&'static str]) -> Vec<&'static str> {
+    markers
+        .iter()
+        .copied()
+        .filter(|marker| match marker.strip_prefix("../") {
+            Some(in_parent) => path
+                .parent()
+                .is_some_and(|parent| parent.join(in_parent).exists()),
+            None => path.join(marker).exists(),
+        })
+        .collect()
+}
+
+/// Files whose presence makes a directory the project that owns the artifacts inside it
+pub const PROJECT_MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "requirements.txt",
+    "stack.yaml",
+    "package.yaml",
+];
+
+/// Returns the directory containing an artifact if it looks like a project root
+pub fn owning_project(path: &Path) -> Option<&Path> {
+    let parent = path.parent()?;
+    PROJECT_MANIFESTS
+        .iter()
+        .any(|manifest| parent.join(manifest).exists())
+        .then_some(parent)
+}
+
+/// The ordered set of detectors the walker, cleaner and CLI iterate over
+#[derive(Debug, Clone)]
+pub struct Registry {
+    detectors: Vec<Detector>,
+}
+
+impl Registry {
+    /// A registry without any detectors
+    pub fn empty() -> Self {
+        Registry {
+            detectors: Vec::new(),
+        }
+    }
+
+    /// The detectors for every artifact type wd-40 ships with
+    pub fn builtin() -> Self {
+        let mut registry = Registry::empty();
+
+        registry.register(Detector {
+            kind: ArtifactKind::ORPHANED_TARGET,
+            // Support "target" and "target-ra" (rust-analyzer cache)
+            dir_names: &["target", "target-ra"],
+            validate: is_orphaned_target_dir,
+            icon: "⊗",
+            log_tag: "ORPHANED",
+            markers: &["CACHEDIR.TAG", ".rustc_info.json"],
+            noun: ("orphaned target directory", "orphaned target directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::SHARED_TARGET,
+            // Never matched by name: the walker finds these through the projects' Cargo configuration
+            dir_names: &[],
+            validate: is_cargo_target_dir,
+            icon: "⊕",
+            log_tag: "SHARED_TARGET",
+            markers: &["CACHEDIR.TAG", ".rustc_info.json"],
+            noun: ("shared target directory", "shared target directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::NODE_MODULES,
+            dir_names: &["node_modules"],
+            validate: is_node_modules_dir,
+            icon: "📦",
+            log_tag: "NODE_MODULES",
+            markers: &[
+                "../package.json",
+                "../package-lock.json",
+                "../yarn.lock",
+                "../pnpm-lock.yaml",
+                ".bin",
+                ".package-lock.json",
+            ],
+            noun: ("node_modules directory", "node_modules directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::ORPHANED_NODE_MODULES,
+            dir_names: &["node_modules"],
+            validate: is_orphaned_node_modules_dir,
+            icon: "⊗",
+            log_tag: "ORPHANED_NODE_MODULES",
+            markers: &[
+                ".package-lock.json",
+                ".modules.yaml",
+                ".yarn-integrity",
+                ".bin",
+                ".pnpm",
+            ],
+            noun: (
+                "orphaned node_modules directory",
+                "orphaned node_modules directories",
+            ),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::PYTHON_VENV,
+            dir_names: &["venv", ".venv", "env", "ENV", "virtualenv", ".virtualenv"],
+            validate: is_python_venv_dir,
+            icon: "🐍",
+            log_tag: "PYTHON_VENV",
+            markers: &[
+                "pyvenv.cfg",
+                "bin/activate",
+                "Scripts/activate.bat",
+                "lib",
+                "Lib",
+            ],
+            noun: ("Python virtual environment", "Python virtual environments"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::SCCACHE,
+            dir_names: &[".sccache"],
+            validate: is_sccache_dir,
+            icon: "🔧",
+            log_tag: "SCCACHE",
+            markers: &[],
+            noun: ("sccache directory", "sccache directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::STACK_WORK,
+            dir_names: &[".stack-work"],
+            validate: is_stack_work_dir,
+            icon: "λ",
+            log_tag: "STACK_WORK",
+            markers: &[
+                "stack.sqlite3",
+                "dist",
+                "install",
+                "../stack.yaml",
+                "../package.yaml",
+            ],
+            noun: ("Stack work directory", "Stack work directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::RUSTUP,
+            dir_names: &[".rustup"],
+            validate: is_rustup_dir,
+            icon: "🦀",
+            log_tag: "RUSTUP",
+            markers: &["settings.toml", "toolchains", "downloads", "update-hashes"],
+            noun: ("rustup directory", "rustup directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::NEXT,
+            dir_names: &[".next"],
+            validate: is_next_dir,
+            icon: "▲",
+            log_tag: "NEXT",
+            markers: &[
+                "BUILD_ID",
+                "cache",
+                "server",
+                "static",
+                "../next.config.js",
+                "../next.config.mjs",
+                "../next.config.ts",
+                "../package.json",
+            ],
+            noun: ("Next.js build directory", "Next.js build directories"),
+        });
+        registry.register(Detector {
+            kind: ArtifactKind::CARGO_NIX,
+            dir_names: &[".cargo-nix"],
+            validate: is_cargo_nix_dir,
+            icon: "❄",
+            log_tag: "CARGO_NIX",
+            markers: &[
+                "target",
+                "Cargo.nix",
+                "crate-hashes.json",
+                "result",
+                "../Cargo.toml",
+                "../Cargo.nix",
+                "../flake.nix",
+                "../default.nix",
+                "../shell.nix",
+            ],
+            noun: ("cargo-nix directory", "cargo-nix directories"),
+        });
+
+        registry
+    }
+
+    /// Adds a detector, replacing any existing detector for the same kind
+    pub fn register(&mut self, detector: Detector) {
+        if let Some(existing) = self.detectors.iter_mut().find(|d| d.kind == detector.kind) {
+            *existing = detector;
+        } else {
+            self.detectors.push(detector);
+        }
+    }
+
+    /// All detectors in registration order
+    pub fn detectors(&self) -> &[Detector] {
+        &self.detectors
+    }
+
+    /// Looks up the detector for a kind
+    pub fn get(&self, kind: ArtifactKind) -> Option<&Detector> {
+        self.detectors.iter().find(|d| d.kind == kind)
+    }
+
+    /// Looks up a detector by its command-line identifier
+    pub fn find_by_id(&self, id: &str) -> Option<&Detector> {
+        self.detectors.iter().find(|d| d.kind.id() == id)
+    }
+
+    /// Returns the first detector that claims and validates `path`
+    pub fn detect(&self, path: &Path) -> Option<&Detector> {
+        let name = path.file_name().and_then(|n| n.to_str())?;
+        self.detectors
+            .iter()
+            .find(|d| d.matches_name(name) && (d.validate)(path))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::builtin()
+    }
+}
@@ -0,0 +1,227 @@
+use crate::age::AgeFilter;
+use crate::artifacts::ArtifactKind;
+use crate::cleaner::parallel_map;
+use crate::walker::DiscoveredPaths;
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How much space a run should make available
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// Free at least this many bytes (`--free-at-least`)
+    FreeAtLeast(u64),
+    /// Clean until the filesystem has this many bytes available (`--target-free`)
+    TargetFree(u64),
+}
+
+/// A discovered item with what the ranking policy needs to know about it
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    pub last_used: Option<SystemTime>,
+    /// Estimated bytes cleaning it would free
+    pub size: u64,
+}
+
+/// Which candidates a budget run cleans, in policy order, and which it leaves alone
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub chosen: Vec<Candidate>,
+    pub left: Vec<Candidate>,
+    /// Bytes available on the filesystem when planning
+    pub available: u64,
+    /// Bytes that have to be freed to meet the goal
+    pub needed: u64,
+}
+
+impl Plan {
+    /// Estimated bytes the chosen candidates free
+    pub fn planned(&self) -> u64 {
+        self.chosen.iter().map(|c| c.size).sum()
+    }
+
+    /// Returns true if cleaning every chosen candidate is expected to meet the goal
+    pub fn meets_goal(&self) -> bool {
+        self.planned() >= self.needed
+    }
+}
+
+/// Sizes and dates every discovered item, in parallel
+///
+/// `size` estimates what cleaning an item would free, given its kind, path and workspace members,
+/// so that options removing only part of an item are planned for what they actually remove.
+pub fn candidates(
+    discovered: &DiscoveredPaths,
+    ages: &AgeFilter,
+    size: impl Fn(ArtifactKind, &Path, &[PathBuf]) -> u64 + Sync,
+) -> Vec<Candidate> {
+    let items: Vec<(ArtifactKind, PathBuf)> = discovered
+        .iter()
+        .map(|(kind, path)| (kind, path.to_path_buf()))
+        .collect();
+
+    parallel_map(&items, |(kind, path)| Candidate {
+        kind: *kind,
+        path: path.clone(),
+        last_used: ages.last_used(*kind, path, discovered.members(path)),
+        size: size(*kind, path, discovered.members(path)),
+    })
+}
+
+/// Orders candidates least recently used first, breaking ties by size (largest first)
+///
+/// Last-use times are compared by day, so artifacts touched on the same day are
+/// ordered by size. Candidates without a known last-use time go last, since they may be in use.
+pub fn rank(candidates: &mut [Candidate]) {
+    let day = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs() / SECONDS_PER_DAY)
+            .unwrap_or(0)
+    };
+
+    candidates.sort_by(|a, b| {
+        let by_age = match (a.last_used.map(day), b.last_used.map(day)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_age
+            .then_with(|| b.size.cmp(&a.size))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+}
+
+/// Ranks the candidates and chooses the shortest prefix that frees enough space
+///
+/// Empty candidates are never chosen, as cleaning them can't help.
+pub fn plan(mut candidates: Vec<Candidate>, goal: Goal, available: u64) -> Plan {
+    let needed = match goal {
+        Goal::FreeAtLeast(bytes) => bytes,
+        Goal::TargetFree(bytes) => bytes.saturating_sub(available),
+    };
+
+    rank(&mut candidates);
+
+    let mut chosen = Vec::new();
+    let mut left = Vec::new();
+    let mut planned = 0u64;
+    for candidate in candidates {
+        if planned < needed && candidate.size > 0 {
+            planned += candidate.size;
+            chosen.push(candidate);
+        } else {
+            left.push(candidate);
+        }
+    }
+
+    Plan {
+        chosen,
+        left,
+        available,
+        needed,
+    }
+}
+
+/// Keeps only the chosen candidates in `discovered`
+pub fn apply(plan: &Plan, discovered: &mut DiscoveredPaths) {
+    discovered.retain(|kind, path| plan.chosen.iter().any(|c| c.kind == kind && c.path == path));
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`
+pub fn available_space(path: &Path) -> Result<u64> {
+    fs2::available_space(path)
+        .with_context(|| format!("Failed to read free space for {}", path.display()))
+}
+
+/// Parses a size such as `50G`, `512M`, `1.5T` or `1048576` (binary units, optional `B`/`iB`)
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size '{}' (expected e.g. 50G, 512M, 1.5T)", input);
+
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn candidate(name: &str, days_ago: Option<u64>, size: u64) -> Candidate {
+        Candidate {
+            kind: ArtifactKind::NODE_MODULES,
+            path: PathBuf::from(name),
+            // A fixed clock keeps same-day candidates on the same day
+            last_used: days_ago
+                .map(|days| UNIX_EPOCH + Duration::from_secs((1000 - days) * SECONDS_PER_DAY)),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_plan_takes_oldest_then_largest_until_goal() {
+        let candidates = vec![
+            candidate("recent-huge", Some(1), 100),
+            candidate("old-small", Some(90), 10),
+            candidate("old-large", Some(90), 30),
+            candidate("unknown", None, 500),
+            candidate("middle", Some(40), 20),
+        ];
+
+        let oldest_first = plan(candidates, Goal::FreeAtLeast(50), 0);
+        let chosen: Vec<_> = oldest_first
+            .chosen
+            .iter()
+            .map(|c| c.path.to_str().unwrap())
+            .collect();
+        assert_eq!(chosen, ["old-large", "old-small", "middle"]);
+        assert_eq!(oldest_first.left.len(), 2);
+        assert!(oldest_first.meets_goal());
+
+        // Only the shortfall between the target and what's already free counts
+        let one = vec![candidate("a", Some(10), 100)];
+        assert!(plan(one.clone(), Goal::TargetFree(500), 600)
+            .chosen
+            .is_empty());
+        let short = plan(one, Goal::TargetFree(500), 300);
+        assert_eq!(short.needed, 200);
+        assert_eq!(short.chosen.len(), 1);
+        assert!(!short.meets_goal());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("50G"), Ok(50 << 30));
+        assert_eq!(parse_size("50GB"), Ok(50 << 30));
+        assert_eq!(parse_size("512mib"), Ok(512 << 20));
+        assert_eq!(parse_size("1.5T"), Ok(3 << 39));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("50X").is_err());
+        assert!(parse_size("G").is_err());
+    }
+}
@@ -1,8 +1,22 @@
 use anyhow::{Context, Result};
-use colored::Colorize;
+use crate::artifacts::{ArtifactKind, Detector, Registry};
+use crate::ignore_rules;
+use crate::quarantine::Quarantine;
+use crate::rustup;
+use crate::sccache;
+use crate::target_dir::{self, CustomTarget};
+use crate::target_parts::{output_dirs, TargetParts};
+use crate::tracked;
+use crate::validate::{ValidProject, Validation};
 use std::fs;
-use std::path::Path;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::SystemTime;
+
+/// How a project a `.wd40ignore` file excludes is reported as skipped, followed by that file's path
+pub const EXCLUDED_REASON: &str = "excluded by";
 
 #[derive(Debug)]
 pub enum CleanStatus {
@@ -10,14 +24,33 @@ pub enum CleanStatus {
     TargetOnly { space_freed: u64, reason: String },
     Failed(String),
     Skipped(String),
+    /// Left alone because something still needs it: it's in use, or holds files tracked by git
+    Refused(String),
 }
 
 pub struct CleanResult {
     pub project_path: String,
+    /// Workspace members cleaned along with the project (empty for a standalone package)
+    pub members: Vec<PathBuf>,
+    /// Target subdirectories cleaned with their sizes, when cleaning only parts of target directories
+    pub parts: Vec<(PathBuf, u64)>,
+    /// Final artifacts kept in the target directories with their sizes (see `TargetParts::keep_final_artifacts`)
+    pub preserved: Vec<(PathBuf, u64)>,
     pub status: CleanStatus,
 }
 
 impl CleanResult {
+    /// A result for `project_dir` with nothing cleaned in detail
+    pub fn new(project_dir: &Path, members: &[PathBuf], status: CleanStatus) -> Self {
+        CleanResult {
+            project_path: project_dir.display().to_string(),
+            members: members.to_vec(),
+            parts: Vec::new(),
+            preserved: Vec::new(),
+            status,
+        }
+    }
+
     pub fn is_success(&self) -> bool {
         matches!(self.status, CleanStatus::Success { .. })
     }
@@ -26,8 +59,13 @@ impl CleanResult {
         matches!(self.status, CleanStatus::TargetOnly { .. })
     }
 
+    /// Returns true if the project was skipped for any reason, including being refused
     pub fn is_skipped(&self) -> bool {
-        matches!(self.status, CleanStatus::Skipped(_))
+        matches!(self.status, CleanStatus::Skipped(_) | CleanStatus::Refused(_))
+    }
+
+    pub fn is_refused(&self) -> bool {
+        matches!(self.status, CleanStatus::Refused(_))
     }
 
     pub fn space_freed(&self) -> Option<u64> {
@@ -65,17 +103,55 @@ pub fn calculate_dir_size(path: &Path) -> Result<u64> {
     Ok(total_size)
 }
 
+/// Runs `f` over `items` on a small pool of threads, returning results in input order
+pub fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    parallel_map_with(workers, items, f)
+}
+
+/// Like `parallel_map`, on at most `workers` threads
+pub fn parallel_map_with<T: Sync, R: Send>(workers: usize, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let workers = workers.max(1).min(items.len());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                if let Ok(mut results) = results.lock() {
+                    results[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
 /// Validates if a directory is a Rust target directory by checking for Cargo-specific markers
 pub fn is_rust_target_dir(path: &Path) -> bool {
     // Must be named exactly "target" or "target-ra" (rust-analyzer cache)
-    let is_valid_name = path.file_name().and_then(|n| n.to_str()).map_or(false, |name| {
+    let is_valid_name = path.file_name().and_then(|n| n.to_str()).is_some_and(|name| {
         name == "target" || name == "target-ra"
     });
 
-    if !is_valid_name {
-        return false;
-    }
+    is_valid_name && is_cargo_target_dir(path)
+}
 
+/// Validates if a directory holds Cargo build output, whatever it is called
+///
+/// Used for target directories configured with `build.target-dir` or `CARGO_TARGET_DIR`.
+pub fn is_cargo_target_dir(path: &Path) -> bool {
     // Must NOT contain a Cargo.toml (safety check - could be a project named "target")
     if path.join("Cargo.toml").exists() {
         return false;
@@ -88,6 +164,19 @@ pub fn is_rust_target_dir(path: &Path) -> bool {
     has_cachedir_tag || has_rustc_info
 }
 
+/// Validates if a directory is a Rust target directory whose parent has no Cargo.toml
+pub fn is_orphaned_target_dir(path: &Path) -> bool {
+    if !is_rust_target_dir(path) {
+        return false;
+    }
+
+    // Orphaned means the project that produced it is gone
+    match path.parent() {
+        Some(parent) => !parent.join("Cargo.toml").exists(),
+        None => false,
+    }
+}
+
 /// Validates if a directory is a node_modules directory by checking multiple attributes
 pub fn is_node_modules_dir(path: &Path) -> bool {
     // Must be named "node_modules"
@@ -134,6 +223,62 @@ pub fn is_node_modules_dir(path: &Path) -> bool {
     has_bin || has_package_lock_json || has_subdirectories
 }
 
+/// Bookkeeping npm, pnpm and yarn leave inside a `node_modules` they installed
+const NODE_MODULES_METADATA: &[&str] = &[".package-lock.json", ".modules.yaml", ".yarn-integrity", ".bin"];
+
+/// Validates if a directory is a node_modules directory whose project is gone
+///
+/// With no `package.json` or lockfile next to it, only its contents vouch for it: it must hold
+/// a package manager's bookkeeping (`NODE_MODULES_METADATA`) and at least one installed
+/// package with a `package.json`. A global install (`<prefix>/lib/node_modules` next to
+/// `<prefix>/bin`) never counts.
+pub fn is_orphaned_node_modules_dir(path: &Path) -> bool {
+    if path.file_name().and_then(|n| n.to_str()) != Some("node_modules") {
+        return false;
+    }
+    if path.join("Cargo.toml").exists() || path.join("setup.py").exists() || path.join("package.json").exists() {
+        return false;
+    }
+
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    let has_project_marker = ["package.json", "package-lock.json", "yarn.lock", "pnpm-lock.yaml"]
+        .iter()
+        .any(|marker| parent.join(marker).exists());
+    if has_project_marker {
+        return false;
+    }
+    let is_global_prefix = parent.file_name().is_some_and(|name| name == "lib")
+        && parent.parent().is_some_and(|prefix| prefix.join("bin").is_dir());
+    if is_global_prefix {
+        return false;
+    }
+
+    NODE_MODULES_METADATA.iter().any(|entry| path.join(entry).exists()) && has_installed_package(path)
+}
+
+/// Returns true if `node_modules` holds a package (`name/` or `@scope/name/`) with a `package.json`
+fn has_installed_package(node_modules: &Path) -> bool {
+    let entries = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|name| !name.starts_with('.')))
+            .collect()
+    };
+    entries(node_modules).iter().any(|package| {
+        let scoped = package.file_name().and_then(|n| n.to_str()).is_some_and(|name| name.starts_with('@'));
+        if scoped {
+            entries(package).iter().any(|package| package.join("package.json").is_file())
+        } else {
+            package.join("package.json").is_file()
+        }
+    })
+}
+
 /// Validates if a directory is a Python virtual environment by checking multiple attributes
 pub fn is_python_venv_dir(path: &Path) -> bool {
     let dir_name = match path.file_name().and_then(|n| n.to_str()) {
@@ -182,8 +327,9 @@ pub fn is_python_venv_dir(path: &Path) -> bool {
 
 /// Validates if a directory is an sccache cache directory by checking multiple attributes
 pub fn is_sccache_dir(path: &Path) -> bool {
-    // Must be named ".sccache"
-    if path.file_name().and_then(|n| n.to_str()) != Some(".sccache") {
+    // Must be named ".sccache", or be the cache sccache is configured to use
+    if path.file_name().and_then(|n| n.to_str()) != Some(".sccache")
+        && sccache::configured_dir().as_deref() != Some(path) {
         return false;
     }
 
@@ -334,8 +480,35 @@ pub fn is_next_dir(path: &Path) -> bool {
     false
 }
 
+/// What cargo-nix and crate2nix tooling leaves in a `.cargo-nix` directory
+const CARGO_NIX_ENTRIES: &[&str] = &["target", "Cargo.nix", "crate-hashes.json", "result"];
+
+/// Files next to a `.cargo-nix` directory that make its parent a Rust project built with Nix
+const CARGO_NIX_PROJECT_MARKERS: &[&str] = &["Cargo.toml", "Cargo.nix", "flake.nix", "default.nix", "shell.nix"];
+
+/// How many entries, and how large a file, are looked through for a Nix store reference
+const NIX_STORE_SAMPLE: usize = 64;
+const NIX_STORE_SAMPLE_BYTES: u64 = 64 * 1024;
+
 /// Validates if a directory is a cargo-nix cache directory by checking multiple attributes
+///
+/// Besides the name, it must sit in a Rust or Nix project and hold something cargo-nix or
+/// crate2nix creates, or a reference into the Nix store; `suspicious_cargo_nix_dir` explains
+/// why a `.cargo-nix` directory that doesn't is left alone.
 pub fn is_cargo_nix_dir(path: &Path) -> bool {
+    is_cargo_nix_candidate(path) && cargo_nix_doubts(path).is_none()
+}
+
+/// Returns why a non-empty `.cargo-nix` directory fails `is_cargo_nix_dir`, if it does
+pub fn suspicious_cargo_nix_dir(path: &Path) -> Option<String> {
+    if !is_cargo_nix_candidate(path) {
+        return None;
+    }
+    cargo_nix_doubts(path)
+}
+
+/// Named `.cargo-nix`, not empty, and not a project itself
+fn is_cargo_nix_candidate(path: &Path) -> bool {
     // Must be named ".cargo-nix"
     if path.file_name().and_then(|n| n.to_str()) != Some(".cargo-nix") {
         return false;
@@ -349,333 +522,616 @@ pub fn is_cargo_nix_dir(path: &Path) -> bool {
     }
 
     // Must have content (files or subdirectories) - not empty
-    let has_content = fs::read_dir(path)
+    fs::read_dir(path)
         .ok()
         .and_then(|entries| entries.filter_map(|e| e.ok()).next())
-        .is_some();
+        .is_some()
+}
+
+fn cargo_nix_doubts(path: &Path) -> Option<String> {
+    let parent = path.parent()?;
+    if !CARGO_NIX_PROJECT_MARKERS.iter().any(|marker| parent.join(marker).exists()) {
+        return Some(format!(
+            "suspicious: none of {} next to it",
+            CARGO_NIX_PROJECT_MARKERS.join(", ")
+        ));
+    }
+    let has_entries = CARGO_NIX_ENTRIES.iter().any(|entry| path.join(entry).symlink_metadata().is_ok());
+    if !has_entries && !references_nix_store(path) {
+        return Some(format!(
+            "suspicious: holds none of {} and no Nix store references",
+            CARGO_NIX_ENTRIES.join(", ")
+        ));
+    }
+    None
+}
+
+/// Returns true if a symlink or small file in the top two levels of `dir` points into `/nix/store`
+fn references_nix_store(dir: &Path) -> bool {
+    let top: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    let nested = top
+        .iter()
+        .filter(|path| path.symlink_metadata().is_ok_and(|m| m.is_dir()))
+        .flat_map(|path| fs::read_dir(path).into_iter().flatten())
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path());
+
+    top.iter().cloned().chain(nested).take(NIX_STORE_SAMPLE).any(|path| {
+        let Ok(metadata) = path.symlink_metadata() else {
+            return false;
+        };
+        if metadata.is_symlink() {
+            fs::read_link(&path).is_ok_and(|target| target.starts_with("/nix/store"))
+        } else if metadata.is_file() && metadata.len() <= NIX_STORE_SAMPLE_BYTES {
+            fs::read(&path).is_ok_and(|contents| contents.windows(11).any(|w| w == b"/nix/store/"))
+        } else {
+            false
+        }
+    })
+}
+
+/// How a validated artifact is disposed of
+#[derive(Clone, Copy)]
+pub enum Removal<'a> {
+    /// Only report what would be freed
+    DryRun,
+    /// Delete it permanently
+    Delete,
+    /// Move it into quarantine so it can be restored
+    Quarantine(&'a Quarantine),
+}
 
-    has_content
+impl Removal<'_> {
+    pub fn from_dry_run(dry_run: bool) -> Self {
+        if dry_run {
+            Removal::DryRun
+        } else {
+            Removal::Delete
+        }
+    }
+
+    /// Deletes or quarantines `path`; `what` names it in errors
+    fn remove(self, path: &Path, what: &str) -> Result<()> {
+        match self {
+            Removal::DryRun => Ok(()),
+            Removal::Delete => {
+                let removed = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+                removed.with_context(|| format!("Failed to delete {}: {}", what, path.display()))
+            }
+            Removal::Quarantine(quarantine) => quarantine.quarantine(path).map(|_| ()),
+        }
+    }
 }
 
 /// Safely deletes a Rust target directory with multiple verification layers
 pub fn delete_target_dir(target_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    remove_target_dir(target_path, Removal::from_dry_run(dry_run))
+}
+
+/// Like `delete_target_dir`, disposing of the directory as `removal` says
+pub fn remove_target_dir(target_path: &Path, removal: Removal) -> Result<Option<u64>> {
     // Triple verification
     if !is_rust_target_dir(target_path) {
         return Ok(None);
     }
+    remove_validated_target(target_path, removal, false)
+}
 
-    // Verify it's a direct sibling of a Cargo.toml
-    if let Some(parent) = target_path.parent() {
-        if !parent.join("Cargo.toml").exists() {
-            return Ok(None);
-        }
-    } else {
+/// Like `remove_target_dir` for a configured target directory, which may have any name
+pub fn remove_custom_target_dir(target_path: &Path, removal: Removal) -> Result<Option<u64>> {
+    if !is_cargo_target_dir(target_path) {
         return Ok(None);
     }
+    remove_validated_target(target_path, removal, false)
+}
 
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+fn remove_validated_target(target_path: &Path, removal: Removal, allow_tracked: bool) -> Result<Option<u64>> {
+    // Never touch anything a .wd40ignore file protects
+    if ignore_rules::excluded_by(target_path).is_some() {
+        return Ok(None);
+    }
+    if !allow_tracked {
+        tracked::ensure_untracked(target_path)?;
     }
 
-    // Calculate size before deletion
+    // Calculate size before deletion (a dry run reports the same estimate)
     let size = calculate_dir_size(target_path).unwrap_or(0);
-
-    // Delete the directory
-    fs::remove_dir_all(target_path)
-        .with_context(|| format!("Failed to delete target directory: {}", target_path.display()))?;
+    removal.remove(target_path, "target directory")?;
 
     Ok(Some(size))
 }
 
-/// Safely deletes an orphaned Rust target directory (target without parent Cargo.toml)
-pub fn delete_orphaned_target_dir(target_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's a Rust target directory
-    if !is_rust_target_dir(target_path) {
+/// Like `remove_custom_target_dir`, removing only the subdirectories `parts` selects
+///
+/// Returns the subdirectories removed (or that would be) with their sizes, plus each profile
+/// directory a sweep pruned with what it freed there, or `Ok(None)` when the directory no
+/// longer validates or is protected by a `.wd40ignore` file. Unless `allow_tracked` is set,
+/// a target directory holding files tracked by git fails with `TrackedFiles`.
+pub fn remove_target_parts(
+    target_path: &Path,
+    parts: &TargetParts,
+    removal: Removal,
+    allow_tracked: bool,
+) -> Result<Option<Vec<(PathBuf, u64)>>> {
+    if !is_cargo_target_dir(target_path) || ignore_rules::excluded_by(target_path).is_some() {
         return Ok(None);
     }
+    if !allow_tracked {
+        tracked::ensure_untracked(target_path)?;
+    }
 
-    // Verify parent does NOT have Cargo.toml (confirming it's orphaned)
-    if let Some(parent) = target_path.parent() {
-        if parent.join("Cargo.toml").exists() {
-            // Not orphaned - has a parent Cargo.toml
-            return Ok(None);
+    let mut removed = Vec::new();
+    for part in parts.select(target_path) {
+        if ignore_rules::excluded_by(&part).is_some() {
+            continue;
         }
+        let size = calculate_dir_size(&part).unwrap_or(0);
+        removal.remove(&part, "target subdirectory")?;
+        removed.push((part, size));
     }
 
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+    if let Some(sweep) = &parts.sweep {
+        let now = SystemTime::now();
+        for profile in parts.sweep_dirs(target_path) {
+            let stale = sweep.stale_paths(target_path, &profile, now);
+            if stale.is_empty() || ignore_rules::excluded_by(&profile).is_some() {
+                continue;
+            }
+            let mut size = 0u64;
+            for path in stale {
+                size += calculate_dir_size(&path).unwrap_or(0);
+                removal.remove(&path, "stale build artifact")?;
+            }
+            removed.push((profile, size));
+        }
     }
-
-    // Calculate size before deletion
-    let size = calculate_dir_size(target_path).unwrap_or(0);
-
-    // Delete the directory
-    fs::remove_dir_all(target_path)
-        .with_context(|| format!("Failed to delete orphaned target directory: {}", target_path.display()))?;
-
-    Ok(Some(size))
+    Ok(Some(removed))
 }
 
-/// Safely deletes a node_modules directory with verification
-pub fn delete_node_modules_dir(node_modules_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually a node_modules directory
-    if !is_node_modules_dir(node_modules_path) {
+/// Like `remove_artifact`, cleaning only `parts` of a shared target directory
+///
+/// With `allow_tracked` (`--force`), artifacts holding files tracked by git are removed too.
+pub fn remove_artifact_parts(
+    detector: &Detector,
+    path: &Path,
+    parts: &TargetParts,
+    removal: Removal,
+    allow_tracked: bool,
+) -> Result<Option<u64>> {
+    if detector.kind != ArtifactKind::SHARED_TARGET || parts.is_whole() {
+        return remove_whole_artifact(detector, path, removal, allow_tracked);
+    }
+    // The executables in a shared target can't be traced back to one project's metadata,
+    // so there's no telling which to keep
+    if parts.keep_final_artifacts || !(detector.validate)(path) {
         return Ok(None);
     }
+    let removed = remove_target_parts(path, parts, removal, allow_tracked)?;
+    Ok(removed.map(|removed| removed.iter().map(|(_, size)| size).sum()))
+}
 
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+/// Like `remove_artifact` for a Next.js build directory, removing only what's in `.next/cache`
+///
+/// The webpack, turbopack, image and fetch caches go; the production build (`server/`,
+/// `static/`, `BUILD_ID` and the manifests) stays, see `next_build_output`. Returns each cache
+/// removed (or that would be) with its size; only the cache has to be free of git-tracked files.
+pub fn clean_next_cache(next_path: &Path, removal: Removal, allow_tracked: bool) -> Result<Option<Vec<(PathBuf, u64)>>> {
+    if !is_next_dir(next_path) || ignore_rules::excluded_by(next_path).is_some() {
+        return Ok(None);
+    }
+    let cache = next_path.join("cache");
+    if !allow_tracked {
+        tracked::ensure_untracked(&cache)?;
     }
 
-    // Calculate size before deletion
-    let size = calculate_dir_size(node_modules_path).unwrap_or(0);
+    let mut removed = Vec::new();
+    for entry in sorted_entries(&cache) {
+        let size = calculate_dir_size(&entry).unwrap_or(0);
+        removal.remove(&entry, "Next.js cache")?;
+        removed.push((entry, size));
+    }
+    Ok(Some(removed))
+}
 
-    // Delete the directory
-    fs::remove_dir_all(node_modules_path)
-        .with_context(|| format!("Failed to delete node_modules directory: {}", node_modules_path.display()))?;
+/// What `clean_next_cache` leaves in a Next.js build directory: everything but `cache`, with sizes
+pub fn next_build_output(next_path: &Path) -> Vec<(PathBuf, u64)> {
+    sorted_entries(next_path)
+        .into_iter()
+        .filter(|entry| entry.file_name().is_some_and(|name| name != "cache"))
+        .map(|entry| {
+            let size = calculate_dir_size(&entry).unwrap_or(0);
+            (entry, size)
+        })
+        .collect()
+}
 
-    Ok(Some(size))
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    entries
 }
 
-/// Safely deletes a Python virtual environment directory with verification
-pub fn delete_venv_dir(venv_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually a Python venv
-    if !is_python_venv_dir(venv_path) {
+/// Like `remove_artifact` for a rustup directory, removing only what nothing needs
+///
+/// That's the toolchains `rustup::prunable` finds unused (with their `update-hashes/` entries)
+/// and whatever is in `downloads/` and `tmp/`; `settings.toml` and the toolchains still in use
+/// stay. `toolchain_files` are the `rust-toolchain` files found while searching. Returns what
+/// was removed (or would be) with sizes, like `remove_target_parts`.
+pub fn prune_rustup_dir(
+    rustup_path: &Path,
+    toolchain_files: &[PathBuf],
+    removal: Removal,
+    allow_tracked: bool,
+) -> Result<Option<Vec<(PathBuf, u64)>>> {
+    if !is_rustup_dir(rustup_path) || ignore_rules::excluded_by(rustup_path).is_some() {
         return Ok(None);
     }
-
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+    if !allow_tracked {
+        tracked::ensure_untracked(rustup_path)?;
     }
 
-    // Calculate size before deletion
-    let size = calculate_dir_size(venv_path).unwrap_or(0);
-
-    // Delete the directory
-    fs::remove_dir_all(venv_path)
-        .with_context(|| format!("Failed to delete Python venv directory: {}", venv_path.display()))?;
-
-    Ok(Some(size))
+    let prunable = rustup::prunable(rustup_path, toolchain_files);
+    let mut removed = Vec::new();
+    for toolchain in prunable.toolchains {
+        let mut size = calculate_dir_size(&toolchain).unwrap_or(0);
+        removal.remove(&toolchain, "rustup toolchain")?;
+        if let Some(name) = toolchain.file_name() {
+            let update_hash = rustup_path.join("update-hashes").join(name);
+            if update_hash.is_file() {
+                size += calculate_dir_size(&update_hash).unwrap_or(0);
+                removal.remove(&update_hash, "rustup update hash")?;
+            }
+        }
+        removed.push((toolchain, size));
+    }
+    for path in prunable.scratch {
+        let size = calculate_dir_size(&path).unwrap_or(0);
+        removal.remove(&path, "rustup download")?;
+        removed.push((path, size));
+    }
+    Ok(Some(removed))
 }
 
-/// Safely deletes an sccache cache directory with verification
-pub fn delete_sccache_dir(sccache_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually an sccache directory
-    if !is_sccache_dir(sccache_path) {
+/// Like `remove_artifact` for an sccache cache, removing only its least recently used entries
+///
+/// Entries go, oldest first, until the cache holds at most `max_size` bytes (see `sccache::trim`).
+/// Returns the bytes freed (or that would be), which is zero when the cache already fits.
+pub fn trim_sccache_dir(sccache_path: &Path, max_size: u64, removal: Removal, allow_tracked: bool) -> Result<Option<u64>> {
+    if !is_sccache_dir(sccache_path) || ignore_rules::excluded_by(sccache_path).is_some() {
         return Ok(None);
     }
-
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+    if !allow_tracked {
+        tracked::ensure_untracked(sccache_path)?;
     }
 
-    // Calculate size before deletion
-    let size = calculate_dir_size(sccache_path).unwrap_or(0);
+    let mut freed = 0;
+    for (entry, size) in sccache::trim(sccache_path, max_size) {
+        removal.remove(&entry, "sccache entry")?;
+        freed += size;
+    }
+    Ok(Some(freed))
+}
 
-    // Delete the directory
-    fs::remove_dir_all(sccache_path)
-        .with_context(|| format!("Failed to delete sccache directory: {}", sccache_path.display()))?;
+/// Safely deletes a validated artifact, re-running the detector's checks first
+///
+/// Returns `Ok(None)` when the path no longer validates or is protected by a
+/// `.wd40ignore` file, and the number of bytes freed (or that would be freed) otherwise.
+/// An artifact holding files tracked by git is never deleted; that fails with `TrackedFiles`.
+pub fn delete_artifact(detector: &Detector, path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    remove_artifact(detector, path, Removal::from_dry_run(dry_run))
+}
 
-    Ok(Some(size))
+/// Like `delete_artifact`, disposing of the artifact as `removal` says
+pub fn remove_artifact(detector: &Detector, path: &Path, removal: Removal) -> Result<Option<u64>> {
+    remove_whole_artifact(detector, path, removal, false)
 }
 
-/// Safely deletes a Haskell Stack work directory with verification
-pub fn delete_stack_work_dir(stack_work_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually a Stack work directory
-    if !is_stack_work_dir(stack_work_path) {
+fn remove_whole_artifact(detector: &Detector, path: &Path, removal: Removal, allow_tracked: bool) -> Result<Option<u64>> {
+    // Verify it's still what the detector found
+    if !(detector.validate)(path) {
         return Ok(None);
     }
 
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+    // Never touch anything a .wd40ignore file protects
+    if ignore_rules::excluded_by(path).is_some() {
+        return Ok(None);
     }
 
-    // Calculate size before deletion
-    let size = calculate_dir_size(stack_work_path).unwrap_or(0);
+    // Nor anything someone committed, like a vendored subset of node_modules
+    if !allow_tracked {
+        tracked::ensure_untracked(path)?;
+    }
 
-    // Delete the directory
-    fs::remove_dir_all(stack_work_path)
-        .with_context(|| format!("Failed to delete Stack work directory: {}", stack_work_path.display()))?;
+    // Calculate size before deletion (a dry run reports the same estimate)
+    let size = calculate_dir_size(path).unwrap_or(0);
+    removal.remove(path, detector.noun(1))?;
 
     Ok(Some(size))
 }
 
-/// Safely deletes a rustup installation directory with verification
-pub fn delete_rustup_dir(rustup_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually a rustup directory
-    if !is_rustup_dir(rustup_path) {
-        return Ok(None);
-    }
-
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
+/// Deletes a path using the built-in detector for `kind`
+fn delete_builtin(kind: ArtifactKind, path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    match Registry::builtin().get(kind) {
+        Some(detector) => delete_artifact(detector, path, dry_run),
+        None => Ok(None),
     }
+}
 
-    // Calculate size before deletion
-    let size = calculate_dir_size(rustup_path).unwrap_or(0);
+/// Safely deletes a node_modules directory whose project's manifest and lockfiles are gone
+pub fn delete_orphaned_node_modules_dir(node_modules_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::ORPHANED_NODE_MODULES, node_modules_path, dry_run)
+}
 
-    // Delete the directory
-    fs::remove_dir_all(rustup_path)
-        .with_context(|| format!("Failed to delete rustup directory: {}", rustup_path.display()))?;
+/// Safely deletes an orphaned Rust target directory (target without parent Cargo.toml)
+pub fn delete_orphaned_target_dir(target_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::ORPHANED_TARGET, target_path, dry_run)
+}
 
-    Ok(Some(size))
+/// Safely deletes a node_modules directory with verification
+pub fn delete_node_modules_dir(node_modules_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::NODE_MODULES, node_modules_path, dry_run)
 }
 
-/// Safely deletes a Next.js build directory with verification
-pub fn delete_next_dir(next_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually a Next.js build directory
-    if !is_next_dir(next_path) {
-        return Ok(None);
-    }
+/// Safely deletes a Python virtual environment directory with verification
+pub fn delete_venv_dir(venv_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::PYTHON_VENV, venv_path, dry_run)
+}
 
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
-    }
+/// Safely deletes an sccache cache directory with verification
+pub fn delete_sccache_dir(sccache_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::SCCACHE, sccache_path, dry_run)
+}
 
-    // Calculate size before deletion
-    let size = calculate_dir_size(next_path).unwrap_or(0);
+/// Safely deletes a Haskell Stack work directory with verification
+pub fn delete_stack_work_dir(stack_work_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::STACK_WORK, stack_work_path, dry_run)
+}
 
-    // Delete the directory
-    fs::remove_dir_all(next_path)
-        .with_context(|| format!("Failed to delete .next directory: {}", next_path.display()))?;
+/// Safely deletes a rustup installation directory with verification
+pub fn delete_rustup_dir(rustup_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::RUSTUP, rustup_path, dry_run)
+}
 
-    Ok(Some(size))
+/// Safely deletes a Next.js build directory with verification
+pub fn delete_next_dir(next_path: &Path, dry_run: bool) -> Result<Option<u64>> {
+    delete_builtin(ArtifactKind::NEXT, next_path, dry_run)
 }
 
 /// Safely deletes a cargo-nix cache directory with verification
 pub fn delete_cargo_nix_dir(cargo_nix_path: &Path, dry_run: bool) -> Result<Option<u64>> {
-    // Verify it's actually a cargo-nix directory
-    if !is_cargo_nix_dir(cargo_nix_path) {
-        return Ok(None);
-    }
-
-    if dry_run {
-        return Ok(Some(0)); // In dry-run, don't calculate size
-    }
-
-    // Calculate size before deletion
-    let size = calculate_dir_size(cargo_nix_path).unwrap_or(0);
-
-    // Delete the directory
-    fs::remove_dir_all(cargo_nix_path)
-        .with_context(|| format!("Failed to delete .cargo-nix directory: {}", cargo_nix_path.display()))?;
+    delete_builtin(ArtifactKind::CARGO_NIX, cargo_nix_path, dry_run)
+}
 
-    Ok(Some(size))
+/// The binaries, cdylibs and staticlibs named `names` (see `ValidProject`), wherever they are in `targets`
+///
+/// Each profile directory (per target triple too) is checked for every name.
+fn final_artifacts(names: &[String], targets: &[PathBuf]) -> Vec<PathBuf> {
+    let mut kept: Vec<PathBuf> = targets
+        .iter()
+        .flat_map(|target| output_dirs(target))
+        .flat_map(|profile| names.iter().map(move |name| profile.join(name)))
+        .filter(|path| path.is_file())
+        .collect();
+    kept.sort();
+    kept.dedup();
+    kept
 }
 
-/// Validates a Cargo project by running `cargo metadata --no-deps`
-fn validate_project(project_dir: &Path) -> Result<(), String> {
-    let output = Command::new("cargo")
-        .arg("metadata")
-        .arg("--format-version=1")
-        .arg("--no-deps")
-        .current_dir(project_dir)
-        .output()
-        .map_err(|e| format!("Failed to execute cargo metadata: {}", e))?;
+/// The target directories cleaned along with a project
+///
+/// That's `target` and `target-ra` in the project and its workspace `members`, plus the
+/// directory set by `build.target-dir` in the project's own `.cargo/config.toml`. Shared
+/// target directories are cleaned on their own (see `ArtifactKind::SHARED_TARGET`).
+pub fn project_target_dirs(project_dir: &Path, members: &[PathBuf]) -> Vec<PathBuf> {
+    let mut targets: Vec<PathBuf> = std::iter::once(project_dir)
+        .chain(members.iter().map(PathBuf::as_path))
+        .flat_map(|dir| ["target", "target-ra"].map(|variant| dir.join(variant)))
+        .filter(|target| is_rust_target_dir(target))
+        .collect();
+
+    if let Some(CustomTarget::Project(custom)) = target_dir::resolve(project_dir) {
+        if !targets.contains(&custom) && is_cargo_target_dir(&custom) {
+            targets.push(custom);
+        }
+    }
+    targets
+}
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        let error_msg = String::from_utf8_lossy(&output.stderr);
-        // Extract just the first line of the error for cleaner output
-        let first_line = error_msg.lines().next().unwrap_or("Invalid project");
-        Err(first_line.to_string())
+/// Disposes of (`parts` of) one of `project_target_dirs`, re-validating it first
+///
+/// Files tracked by git are checked for up front, for all of a project's targets at once.
+fn remove_project_target(
+    target_path: &Path,
+    parts: &TargetParts,
+    removal: Removal,
+) -> Result<Option<Vec<(PathBuf, u64)>>> {
+    if !parts.is_whole() {
+        return remove_target_parts(target_path, parts, removal, true);
+    }
+    // Named `target` or configured, either way it carries Cargo's markers
+    if !is_cargo_target_dir(target_path) {
+        return Ok(None);
     }
+    let size = remove_validated_target(target_path, removal, true)?;
+    Ok(size.map(|size| vec![(target_path.to_path_buf(), size)]))
 }
 
 /// Cleans a Cargo project and optionally deletes its target directory
+///
+/// Prints nothing; the caller reports the returned `CleanResult`.
 pub fn clean_project(
     project_dir: &Path,
     dry_run: bool,
-    verbose: bool,
     force: bool,
     strict: bool,
 ) -> Result<CleanResult> {
-    let project_path = project_dir.display().to_string();
-    let target_path = project_dir.join("target");
+    clean_project_with(project_dir, Removal::from_dry_run(dry_run), force, strict)
+}
 
-    // Validate the project first unless --force is specified
-    if !force {
-        if let Err(reason) = validate_project(project_dir) {
-            // If validation fails but we're not in strict mode, try to clean target directory anyway
-            if !strict && target_path.exists() && is_rust_target_dir(&target_path) {
-                if verbose {
-                    println!("{} {} - {}", "⊙".yellow(), project_path, "cleaning target only (invalid project config)");
-                }
+/// Like `clean_project`, disposing of target directories as `removal` says
+pub fn clean_project_with(
+    project_dir: &Path,
+    removal: Removal,
+    force: bool,
+    strict: bool,
+) -> Result<CleanResult> {
+    clean_workspace_with(project_dir, &[], removal, force, strict)
+}
 
-                if dry_run {
-                    return Ok(CleanResult {
-                        project_path,
-                        status: CleanStatus::TargetOnly {
-                            space_freed: 0,
-                            reason: reason.clone(),
-                        },
-                    });
-                }
+/// Cleans a workspace as one unit: validated once at its root, whose target holds every member's builds
+///
+/// Target directories left inside `members` (from before they joined the workspace) are cleaned too.
+pub fn clean_workspace_with(
+    project_dir: &Path,
+    members: &[PathBuf],
+    removal: Removal,
+    force: bool,
+    strict: bool,
+) -> Result<CleanResult> {
+    clean_workspace_parts(project_dir, members, &TargetParts::default(), removal, force, strict)
+}
 
-                // Calculate and delete target
-                let space_freed = calculate_dir_size(&target_path).unwrap_or(0);
-                delete_target_dir(&target_path, false)?;
+/// Like `clean_workspace_with`, cleaning only the `parts` of each target directory
+pub fn clean_workspace_parts(
+    project_dir: &Path,
+    members: &[PathBuf],
+    parts: &TargetParts,
+    removal: Removal,
+    force: bool,
+    strict: bool,
+) -> Result<CleanResult> {
+    clean_validated(project_dir, members, None, parts, removal, force, strict)
+}
 
-                println!("{} {} (target only)", "⊙".cyan(), project_path);
-                return Ok(CleanResult {
-                    project_path,
-                    status: CleanStatus::TargetOnly { space_freed, reason },
-                });
-            }
+/// Like `clean_workspace_parts`, given the outcome of `Validation::validate` if the caller ran it
+///
+/// That lets callers validate many projects at once, with any validator. Without an outcome
+/// the default validation runs when needed.
+pub fn clean_validated(
+    project_dir: &Path,
+    members: &[PathBuf],
+    mut validated: Option<Result<ValidProject, String>>,
+    parts: &TargetParts,
+    removal: Removal,
+    force: bool,
+    strict: bool,
+) -> Result<CleanResult> {
+    let result = |status| CleanResult::new(project_dir, members, status);
+    // Whole target directories need no breakdown
+    let with_parts = |mut result: CleanResult, cleaned: Vec<(PathBuf, u64)>| {
+        if !parts.is_whole() {
+            result.parts = cleaned;
+        }
+        result
+    };
 
-            // In strict mode or no valid target, skip the project
-            if verbose {
-                println!("{} {} - {}", "⊘".yellow(), project_path, reason);
+    // A .wd40ignore match wins over every other option, including --force
+    if let Some(ignore_file) = ignore_rules::excluded_by(project_dir) {
+        return Ok(result(CleanStatus::Skipped(format!("{} {}", EXCLUDED_REASON, ignore_file.display()))));
+    }
+
+    // Refuse the whole project rather than clean some of its targets, unless --force is specified
+    let targets = project_target_dirs(project_dir, members);
+    if !force {
+        for target in &targets {
+            if let Err(tracked) = tracked::ensure_untracked(target) {
+                return Ok(result(CleanStatus::Refused(format!("{} {}", target.display(), tracked))));
             }
-            return Ok(CleanResult {
-                project_path,
-                status: CleanStatus::Skipped(reason),
-            });
         }
     }
 
-    if dry_run {
-        println!("{} {}", "[DRY RUN]".yellow(), project_path);
-        return Ok(CleanResult {
-            project_path,
-            status: CleanStatus::Success { space_freed: None },
-        });
-    }
+    // Validate the project first unless --force is specified
+    if !force {
+        if let Err(reason) = validated.get_or_insert_with(|| Validation::default().validate(project_dir, members)) {
+            let reason = reason.clone();
+            // If validation fails but we're not in strict mode, try to clean target directories anyway
+            // (unless final artifacts are to be kept, which takes a valid manifest)
+            if !strict && !parts.keep_final_artifacts {
+                let (cleaned, cleaned_any, errors) = remove_project_targets(&targets, parts, removal);
+                if !errors.is_empty() {
+                    return Ok(with_parts(result(CleanStatus::Failed(errors.join("; "))), cleaned));
+                }
+                if cleaned_any {
+                    let space_freed = cleaned.iter().map(|(_, size)| size).sum();
+                    return Ok(with_parts(result(CleanStatus::TargetOnly { space_freed, reason }), cleaned));
+                }
+            }
 
-    if verbose {
-        println!("{} {}", "Cleaning".cyan(), project_path);
+            // In strict mode or no valid target, skip the project
+            return Ok(result(CleanStatus::Skipped(reason)));
+        }
     }
 
-    // Calculate total space freed from all target variants
-    let mut total_space_freed = 0u64;
-    let mut found_any_target = false;
+    let mut preserved = Vec::new();
+    let mut parts = parts.clone();
+    if parts.keep_final_artifacts {
+        let valid = match validated.unwrap_or_else(|| Validation::default().validate(project_dir, members)) {
+            Ok(valid) => valid,
+            Err(reason) => {
+                return Ok(result(CleanStatus::Skipped(format!("can't tell which executables to keep: {}", reason))))
+            }
+        };
+        parts.keep.extend(final_artifacts(&valid.final_artifacts, &targets));
+        preserved = parts
+            .keep
+            .iter()
+            .map(|path| (path.clone(), path.metadata().map(|m| m.len()).unwrap_or(0)))
+            .collect();
+    }
 
-    // List of target directory variants to clean
-    let target_variants = ["target", "target-ra"];
+    // Calculate total space freed from all target variants (or what would be freed in a dry run)
+    let (cleaned, found_any_target, errors) = remove_project_targets(&targets, &parts, removal);
 
-    for variant in &target_variants {
-        let target_path = project_dir.join(variant);
-        if target_path.exists() && is_rust_target_dir(&target_path) {
-            found_any_target = true;
-            if let Ok(size) = calculate_dir_size(&target_path) {
-                total_space_freed += size;
-            }
-            delete_target_dir(&target_path, dry_run).ok();
-        }
+    // A target that couldn't be deleted or quarantined fails the project, even if others went
+    if !errors.is_empty() {
+        return Ok(with_parts(result(CleanStatus::Failed(errors.join("; "))), cleaned));
     }
 
     let space_freed = if found_any_target {
-        Some(total_space_freed)
+        Some(cleaned.iter().map(|(_, size)| size).sum())
     } else {
         None
     };
 
-    println!("{} {}", "✓".green(), project_path);
-    Ok(CleanResult {
-        project_path,
-        status: CleanStatus::Success { space_freed },
-    })
+    let mut cleaned = with_parts(result(CleanStatus::Success { space_freed }), cleaned);
+    cleaned.preserved = preserved;
+    Ok(cleaned)
+}
+
+/// Disposes of (`parts` of) every one of a project's `targets`, carrying on past failures
+///
+/// Returns what was removed, whether any target was there to remove, and why each failure failed.
+fn remove_project_targets(
+    targets: &[PathBuf],
+    parts: &TargetParts,
+    removal: Removal,
+) -> (Vec<(PathBuf, u64)>, bool, Vec<String>) {
+    let mut cleaned = Vec::new();
+    let mut found_any_target = false;
+    let mut errors = Vec::new();
+
+    for target_path in targets {
+        // Removal re-validates (including .wd40ignore) and reports the size it (would have) freed
+        match remove_project_target(target_path, parts, removal) {
+            Ok(Some(removed)) => {
+                found_any_target = true;
+                cleaned.extend(removed);
+            }
+            Ok(None) => {}
+            Err(e) => errors.push(format!("{:#}", e)),
+        }
+    }
+    (cleaned, found_any_target, errors)
 }
 
 #[cfg(test)]
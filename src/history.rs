@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the history record schema
+///
+/// Adding fields keeps the version; removing or changing the meaning of one bumps it.
+pub const HISTORY_SCHEMA_VERSION: u32 = 1;
+
+/// One project or artifact a run cleaned (or, in a dry run, would have cleaned)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CleanedItem {
+    pub kind: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// Everything `wd-40 history` needs to know about one past run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub schema_version: u32,
+    pub run_id: String,
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub root: PathBuf,
+    pub dry_run: bool,
+    pub quarantine: bool,
+    pub log_file: PathBuf,
+    pub cleaned: Vec<CleanedItem>,
+}
+
+impl RunRecord {
+    pub fn size_bytes(&self) -> u64 {
+        self.cleaned.iter().map(|item| item.size_bytes).sum()
+    }
+
+    /// Items and bytes cleaned per kind
+    pub fn by_kind(&self) -> BTreeMap<&str, (usize, u64)> {
+        let mut by_kind: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+        for item in &self.cleaned {
+            let totals = by_kind.entry(item.kind.as_str()).or_default();
+            totals.0 += 1;
+            totals.1 += item.size_bytes;
+        }
+        by_kind
+    }
+}
+
+/// A path cleaned by more than one run, with how quickly it comes back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regrowth {
+    pub kind: String,
+    pub path: PathBuf,
+    pub times_cleaned: usize,
+    pub last_cleaned: DateTime<Local>,
+    /// Bytes that grew back between the first and the last cleaning, per day
+    pub bytes_per_day: u64,
+}
+
+/// The append-only record of past runs, one JSON object per line
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> Self {
+        History { path }
+    }
+
+    /// The default history: ~/.cache/wd-40/history.jsonl
+    pub fn default_location() -> Result<Self> {
+        let path = dirs::cache_dir()
+            .context("Failed to determine cache directory")?
+            .join("wd-40")
+            .join("history.jsonl");
+        Ok(History::new(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds one run to the end of the history
+    pub fn append(&self, record: &RunRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create history directory: {}", parent.display())
+            })?;
+        }
+
+        // A single write per record keeps concurrent runs from interleaving lines
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| {
+                // A line cut short by a crash is ended first, so it can't swallow this record
+                if ends_mid_line(&mut file)? {
+                    line.insert(0, b'\n');
+                }
+                file.write_all(&line)
+            })
+            .with_context(|| format!("Failed to write history: {}", self.path.display()))
+    }
+
+    /// All recorded runs, oldest first
+    ///
+    /// Lines that can't be parsed (say, cut short by a crash) are skipped.
+    pub fn load(&self) -> Result<Vec<RunRecord>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read history: {}", self.path.display()))
+            }
+        };
+
+        let mut records: Vec<RunRecord> = contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        records.sort_by_key(|record| record.started);
+        Ok(records)
+    }
+}
+
+/// Returns true if `file` is neither empty nor ends with a newline
+fn ends_mid_line(file: &mut File) -> std::io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0u8];
+    file.read_exact(&mut last)?;
+    Ok(last[0] != b'\n')
+}
+
+/// Paths cleaned by more than one real run, fastest-regrowing first
+///
+/// Dry runs are ignored, since they leave everything in place.
+pub fn regrowth(records: &[RunRecord]) -> Vec<Regrowth> {
+    let mut cleanings: HashMap<&Path, Vec<(&RunRecord, &CleanedItem)>> = HashMap::new();
+    for record in records.iter().filter(|record| !record.dry_run) {
+        for item in &record.cleaned {
+            cleanings
+                .entry(item.path.as_path())
+                .or_default()
+                .push((record, item));
+        }
+    }
+
+    let mut regrowth: Vec<Regrowth> = cleanings
+        .into_values()
+        .filter(|cleanings| cleanings.len() > 1)
+        .map(|mut cleanings| {
+            cleanings.sort_by_key(|(record, _)| record.finished);
+            let (first, _) = cleanings[0];
+            let (last, item) = cleanings[cleanings.len() - 1];
+
+            // Whatever a later run cleaned grew back after the run before it
+            let regrown: u64 = cleanings
+                .iter()
+                .skip(1)
+                .map(|(_, item)| item.size_bytes)
+                .sum();
+            let seconds = (last.finished - first.finished).num_seconds().max(1) as u64;
+
+            Regrowth {
+                kind: item.kind.clone(),
+                path: item.path.clone(),
+                times_cleaned: cleanings.len(),
+                last_cleaned: last.finished,
+                bytes_per_day: (regrown as u128 * 24 * 60 * 60 / seconds as u128) as u64,
+            }
+        })
+        .collect();
+
+    regrowth.sort_by(|a, b| {
+        b.bytes_per_day
+            .cmp(&a.bytes_per_day)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    regrowth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn record(run_id: &str, days_ago: i64, dry_run: bool, cleaned: &[(&str, u64)]) -> RunRecord {
+        let finished = Local::now() - Duration::days(days_ago);
+        RunRecord {
+            schema_version: HISTORY_SCHEMA_VERSION,
+            run_id: run_id.to_string(),
+            started: finished,
+            finished,
+            root: PathBuf::from("/src"),
+            dry_run,
+            quarantine: false,
+            log_file: PathBuf::from(format!("/logs/clean-{}.log", run_id)),
+            cleaned: cleaned
+                .iter()
+                .map(|(path, size_bytes)| CleanedItem {
+                    kind: "node-modules".to_string(),
+                    path: PathBuf::from(path),
+                    size_bytes: *size_bytes,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_history_round_trip_and_regrowth() {
+        let temp_dir = TempDir::new().unwrap();
+        let history = History::new(temp_dir.path().join("history.jsonl"));
+        assert!(history.load().unwrap().is_empty());
+
+        history
+            .append(&record(
+                "b",
+                10,
+                false,
+                &[
+                    ("/src/web/node_modules", 100),
+                    ("/src/api/node_modules", 50),
+                ],
+            ))
+            .unwrap();
+        history
+            .append(&record(
+                "a",
+                20,
+                false,
+                &[
+                    ("/src/web/node_modules", 100),
+                    ("/src/api/node_modules", 50),
+                ],
+            ))
+            .unwrap();
+        history
+            .append(&record("c", 1, true, &[("/src/web/node_modules", 999)]))
+            .unwrap();
+        // A line cut short by a crash doesn't hide the rest of the history
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(history.path())
+            .unwrap();
+        file.write_all(b"{\"schema_version\":1,\"run_").unwrap();
+        // ...nor the next run appended after it
+        history.append(&record("d", 0, true, &[])).unwrap();
+
+        let records = history.load().unwrap();
+        let ids: Vec<&str> = records.iter().map(|r| r.run_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+        assert_eq!(records[0].size_bytes(), 150);
+        assert_eq!(records[0].by_kind()["node-modules"], (2, 150));
+
+        // Dry runs don't count, and web regrows 100 bytes over 10 days
+        let regrowth = regrowth(&records);
+        assert_eq!(regrowth.len(), 2);
+        assert_eq!(regrowth[0].path, PathBuf::from("/src/web/node_modules"));
+        assert_eq!(regrowth[0].times_cleaned, 2);
+        assert_eq!(regrowth[0].bytes_per_day, 10);
+        assert_eq!(regrowth[1].bytes_per_day, 5);
+    }
+}
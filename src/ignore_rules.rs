@@ -0,0 +1,117 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Name of the per-directory file listing paths that must never be cleaned
+pub const IGNORE_FILE_NAME: &str = ".wd40ignore";
+
+/// A discovered artifact that was left alone because a `.wd40ignore` file matched it
+#[derive(Debug, Clone)]
+pub struct ExcludedPath {
+    pub path: PathBuf,
+    pub ignore_file: PathBuf,
+}
+
+/// Loads `.wd40ignore` files on demand and caches them per directory.
+///
+/// Patterns use gitignore syntax and are relative to the directory containing the
+/// file. Files closer to the candidate take precedence over files further up, and
+/// within a file the last matching pattern wins, so `!pattern` re-includes a path.
+#[derive(Default)]
+pub struct IgnoreRules {
+    cache: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl IgnoreRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the ignore file that excludes `path`, or `None` if it may be cleaned
+    pub fn excluded_by(&self, path: &Path) -> Option<PathBuf> {
+        let is_dir = path.is_dir();
+
+        for dir in path.ancestors().skip(1) {
+            let gitignore = match self.load(dir) {
+                Some(gitignore) => gitignore,
+                None => continue,
+            };
+
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(glob) => {
+                    let ignore_file = glob
+                        .from()
+                        .map(Path::to_path_buf)
+                        .unwrap_or_else(|| dir.join(IGNORE_FILE_NAME));
+                    return Some(ignore_file);
+                }
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+
+        None
+    }
+
+    /// Parses the ignore file in `dir`, if there is one
+    fn load(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Ok(cache) = self.cache.lock() {
+            if let Some(cached) = cache.get(dir) {
+                return cached.clone();
+            }
+        }
+
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        let loaded = if ignore_file.is_file() {
+            let mut builder = GitignoreBuilder::new(dir);
+            // Unreadable lines are skipped rather than failing the whole scan
+            let _ = builder.add(&ignore_file);
+            builder.build().ok().filter(|g| !g.is_empty()).map(Arc::new)
+        } else {
+            None
+        };
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(dir.to_path_buf(), loaded.clone());
+        }
+
+        loaded
+    }
+}
+
+/// Checks a single path against the `.wd40ignore` files of all its ancestors
+pub fn excluded_by(path: &Path) -> Option<PathBuf> {
+    IgnoreRules::new().excluded_by(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_nested_ignore_files_and_negation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("vendor/lib/node_modules")).unwrap();
+        fs::create_dir_all(root.join("vendor/keep/node_modules")).unwrap();
+        fs::create_dir_all(root.join("app/node_modules")).unwrap();
+
+        fs::write(root.join(IGNORE_FILE_NAME), "vendor/\n").unwrap();
+        fs::write(root.join("vendor").join(IGNORE_FILE_NAME), "!keep/\n").unwrap();
+
+        let rules = IgnoreRules::new();
+        assert_eq!(
+            rules.excluded_by(&root.join("vendor/lib/node_modules")),
+            Some(root.join(IGNORE_FILE_NAME))
+        );
+        assert_eq!(
+            rules.excluded_by(&root.join("vendor/keep/node_modules")),
+            None
+        );
+        assert_eq!(rules.excluded_by(&root.join("app/node_modules")), None);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::age::{format_age, newest_mtime, SAMPLE_DEPTH};
+use crate::cleaner::is_cargo_target_dir;
+use crate::target_parts::output_dirs;
+use fs2::FileExt;
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Why an artifact looks like something is using it right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Usage {
+    /// A Cargo build holds this lock file inside the target directory
+    BuildLock(PathBuf),
+    /// A process has its working directory, executable or an open or mapped file inside
+    Process { pid: u32, name: String },
+    /// Something inside was written this long ago
+    RecentWrite(Duration),
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Usage::BuildLock(lock) => write!(f, "cargo build lock held on {}", lock.display()),
+            Usage::Process { pid, name } => write!(f, "used by {} (pid {})", name, pid),
+            Usage::RecentWrite(age) => write!(f, "written {} ago", format_age(*age)),
+        }
+    }
+}
+
+impl Usage {
+    /// The reason an artifact in use is skipped with
+    pub fn reason(&self) -> String {
+        format!("in use: {}", self)
+    }
+}
+
+/// What a running process has open, as far as `/proc` lets us see
+struct ProcessPaths {
+    pid: u32,
+    name: String,
+    paths: Vec<PathBuf>,
+}
+
+/// Tells whether artifacts are in use, from a snapshot of running processes
+///
+/// Processes are only inspected on Linux, through `/proc`, and only those we may look into
+/// (our own user's, or every process as root); elsewhere lock files and writes still count.
+pub struct InUse {
+    /// Writes this recent mean something is still building or installing; zero turns it off
+    pub recent: Duration,
+    processes: Vec<ProcessPaths>,
+}
+
+impl InUse {
+    /// Takes a snapshot of the running processes
+    pub fn scan(recent: Duration) -> Self {
+        InUse {
+            recent,
+            processes: processes(),
+        }
+    }
+
+    /// Returns how `path` is in use, if it is
+    pub fn check(&self, path: &Path) -> Option<Usage> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if is_cargo_target_dir(&path) {
+            let locks = std::iter::once(path.clone())
+                .chain(output_dirs(&path))
+                .map(|dir| dir.join(".cargo-lock"));
+            if let Some(lock) = locks
+                .filter(|lock| lock.is_file())
+                .find(|lock| is_locked(lock))
+            {
+                return Some(Usage::BuildLock(lock));
+            }
+        }
+
+        if let Some(process) = self
+            .processes
+            .iter()
+            .find(|process| process.paths.iter().any(|used| used.starts_with(&path)))
+        {
+            return Some(Usage::Process {
+                pid: process.pid,
+                name: process.name.clone(),
+            });
+        }
+
+        if self.recent > Duration::ZERO {
+            let age = newest_mtime(&path, Some(SAMPLE_DEPTH), &[])
+                .and_then(|written| SystemTime::now().duration_since(written).ok());
+            if let Some(age) = age.filter(|age| *age < self.recent) {
+                return Some(Usage::RecentWrite(age));
+            }
+        }
+        None
+    }
+
+    /// Like `check`, waiting up to `timeout` for `path` to fall out of use
+    ///
+    /// Processes are scanned again every second, so this snapshot is refreshed when it waits.
+    pub fn wait(&mut self, path: &Path, timeout: Duration) -> Option<Usage> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let usage = self.check(path)?;
+            if Instant::now() >= deadline {
+                return Some(usage);
+            }
+            thread::sleep(Duration::from_secs(1).min(deadline - Instant::now()));
+            self.processes = processes();
+        }
+    }
+}
+
+/// Returns true if another process holds a lock on `lock`, as Cargo does for the whole build
+fn is_locked(lock: &Path) -> bool {
+    let Ok(file) = File::open(lock) else {
+        return false;
+    };
+    match file.try_lock_exclusive() {
+        Ok(()) => {
+            let _ = file.unlock();
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn processes() -> Vec<ProcessPaths> {
+    let own = std::process::id();
+    fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            if pid == own {
+                return None;
+            }
+            let dir = entry.path();
+            let mut paths: Vec<PathBuf> = ["cwd", "exe"]
+                .iter()
+                .filter_map(|link| fs::read_link(dir.join(link)).ok())
+                .collect();
+            paths.extend(
+                fs::read_dir(dir.join("fd"))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|fd| fs::read_link(fd.ok()?.path()).ok()),
+            );
+            // Interpreters often live outside the artifact (a venv's python links to the system
+            // one), but the libraries they load from it are mapped
+            let maps = fs::read_to_string(dir.join("maps")).unwrap_or_default();
+            paths.extend(
+                maps.lines()
+                    .filter_map(|line| line.split_whitespace().nth(5))
+                    .filter(|file| file.starts_with('/'))
+                    .map(PathBuf::from),
+            );
+            if paths.is_empty() {
+                return None;
+            }
+            paths.sort();
+            paths.dedup();
+            let name = fs::read_to_string(dir.join("comm"))
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            Some(ProcessPaths { pid, name, paths })
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn processes() -> Vec<ProcessPaths> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_build_lock_and_recent_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        fs::create_dir_all(target.join("debug/deps")).unwrap();
+        fs::write(
+            target.join("CACHEDIR.TAG"),
+            "Signature: 8a477f597d28d172789f06886806bc55",
+        )
+        .unwrap();
+        let lock = target.join("debug/.cargo-lock");
+        fs::write(&lock, "").unwrap();
+
+        let quiet = InUse {
+            recent: Duration::ZERO,
+            processes: Vec::new(),
+        };
+        assert_eq!(quiet.check(&target), None);
+
+        let build = File::open(&lock).unwrap();
+        build.lock_exclusive().unwrap();
+        let lock = lock.canonicalize().unwrap();
+        assert_eq!(quiet.check(&target), Some(Usage::BuildLock(lock)));
+        build.unlock().unwrap();
+
+        let watchful = InUse {
+            recent: Duration::from_secs(60),
+            processes: Vec::new(),
+        };
+        assert!(matches!(
+            watchful.check(&target),
+            Some(Usage::RecentWrite(_))
+        ));
+    }
+}
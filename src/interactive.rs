@@ -0,0 +1,519 @@
+use crate::age::format_age;
+use crate::artifacts::{owning_project, ArtifactKind};
+use crate::budget::Candidate;
+use crate::logging::human_bytes;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// How candidates are grouped in the list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Kind,
+    Project,
+}
+
+/// How groups, and the items inside them, are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// Largest first
+    Size,
+    /// Least recently used first
+    Age,
+    Path,
+}
+
+/// One line of the list: a group header or an item (by index into the items)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Row {
+    Group(String),
+    Item(usize),
+}
+
+/// The candidates and what the user has selected, sorted and filtered so far
+///
+/// Everything starts selected, matching what batch mode would clean, but only the selected items
+/// the filter shows are chosen: what's filtered out is never cleaned.
+pub struct Selection {
+    items: Vec<Candidate>,
+    selected: Vec<bool>,
+    pub group_by: GroupBy,
+    pub sort_by: SortBy,
+    /// Case-insensitive substring the paths must contain
+    pub filter: String,
+}
+
+impl Selection {
+    pub fn new(items: Vec<Candidate>) -> Self {
+        let selected = vec![true; items.len()];
+        Selection {
+            items,
+            selected,
+            group_by: GroupBy::Kind,
+            sort_by: SortBy::Size,
+            filter: String::new(),
+        }
+    }
+
+    /// The selected items the filter shows, in their original order
+    pub fn chosen(&self) -> Vec<(ArtifactKind, PathBuf)> {
+        self.visible()
+            .into_iter()
+            .filter(|index| self.selected[*index])
+            .map(|index| (self.items[index].kind, self.items[index].path.clone()))
+            .collect()
+    }
+
+    pub fn is_selected(&self, index: usize) -> bool {
+        self.selected[index]
+    }
+
+    /// Number and total size of the items `chosen` returns
+    pub fn selected_totals(&self) -> (usize, u64) {
+        self.visible()
+            .into_iter()
+            .filter(|index| self.selected[*index])
+            .fold((0, 0), |(count, bytes), index| {
+                (count + 1, bytes + self.items[index].size)
+            })
+    }
+
+    /// Toggles one item, or every visible item of a group (selecting all unless all already are)
+    pub fn toggle(&mut self, row: &Row) {
+        match row {
+            Row::Item(index) => self.selected[*index] = !self.selected[*index],
+            Row::Group(label) => {
+                let members: Vec<usize> = self
+                    .visible()
+                    .into_iter()
+                    .filter(|index| &self.group_label(*index) == label)
+                    .collect();
+                let select = !members.iter().all(|index| self.selected[*index]);
+                for index in members {
+                    self.selected[index] = select;
+                }
+            }
+        }
+    }
+
+    /// Selects every visible item, or clears them if they are all selected already
+    pub fn toggle_all(&mut self) {
+        let visible = self.visible();
+        let select = !visible.iter().all(|index| self.selected[*index]);
+        for index in visible {
+            self.selected[index] = select;
+        }
+    }
+
+    /// Group headers and items to show, in display order
+    pub fn rows(&self) -> Vec<Row> {
+        let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for index in self.visible() {
+            groups
+                .entry(self.group_label(index))
+                .or_default()
+                .push(index);
+        }
+
+        let mut groups: Vec<(String, Vec<usize>)> = groups.into_iter().collect();
+        for (_, members) in groups.iter_mut() {
+            members.sort_by(|a, b| self.compare(&[*a], &[*b]));
+        }
+        groups.sort_by(|(a_label, a), (b_label, b)| {
+            self.compare(a, b).then_with(|| a_label.cmp(b_label))
+        });
+
+        let mut rows = Vec::new();
+        for (label, members) in groups {
+            rows.push(Row::Group(label));
+            rows.extend(members.into_iter().map(Row::Item));
+        }
+        rows
+    }
+
+    /// Orders two sets of items (single items or whole groups) by the current sort
+    fn compare(&self, a: &[usize], b: &[usize]) -> std::cmp::Ordering {
+        match self.sort_by {
+            SortBy::Size => self.total_size(b).cmp(&self.total_size(a)),
+            // Unknown last use sorts last, as the most recently used
+            SortBy::Age => {
+                let (a, b) = (self.last_used(a), self.last_used(b));
+                (a.is_none(), a).cmp(&(b.is_none(), b))
+            }
+            SortBy::Path => self.items[a[0]].path.cmp(&self.items[b[0]].path),
+        }
+    }
+
+    fn total_size(&self, indices: &[usize]) -> u64 {
+        indices.iter().map(|index| self.items[*index].size).sum()
+    }
+
+    /// The oldest last use among `indices`
+    fn last_used(&self, indices: &[usize]) -> Option<SystemTime> {
+        indices
+            .iter()
+            .filter_map(|index| self.items[*index].last_used)
+            .min()
+    }
+
+    fn visible(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        (0..self.items.len())
+            .filter(|index| {
+                filter.is_empty()
+                    || self.items[*index]
+                        .path
+                        .to_string_lossy()
+                        .to_lowercase()
+                        .contains(&filter)
+            })
+            .collect()
+    }
+
+    fn group_label(&self, index: usize) -> String {
+        let item = &self.items[index];
+        match self.group_by {
+            GroupBy::Kind => item.kind.id().to_string(),
+            GroupBy::Project if item.kind == ArtifactKind::RUST_PROJECT => {
+                item.path.display().to_string()
+            }
+            GroupBy::Project => owning_project(&item.path)
+                .map(|project| project.display().to_string())
+                .unwrap_or_else(|| "(no project)".to_string()),
+        }
+    }
+
+    /// One line describing a row, without any styling
+    fn describe(&self, row: &Row) -> String {
+        match row {
+            Row::Group(label) => {
+                let members: Vec<usize> = self
+                    .visible()
+                    .into_iter()
+                    .filter(|index| &self.group_label(*index) == label)
+                    .collect();
+                let selected = members
+                    .iter()
+                    .filter(|index| self.selected[**index])
+                    .count();
+                format!(
+                    "{}  ({}/{} selected, {})",
+                    label,
+                    selected,
+                    members.len(),
+                    human_bytes(self.total_size(&members))
+                )
+            }
+            Row::Item(index) => {
+                let item = &self.items[*index];
+                let age = item
+                    .last_used
+                    .map(|time| {
+                        format_age(SystemTime::now().duration_since(time).unwrap_or_default())
+                    })
+                    .unwrap_or_else(|| "?".to_string());
+                format!(
+                    "  [{}] {:>10}  {:>5}  {}",
+                    if self.selected[*index] { "x" } else { " " },
+                    human_bytes(item.size),
+                    age,
+                    item.path.display()
+                )
+            }
+        }
+    }
+}
+
+/// Restores the terminal however the interactive session ends
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(
+            std::io::stdout(),
+            terminal::EnterAlternateScreen,
+            cursor::Hide
+        )?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(
+            std::io::stdout(),
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+const HELP: &str =
+    "↑/↓ move  space toggle  a all  s sort  g group  / filter  enter clean shown  q quit";
+
+/// Lets the user pick what to clean; returns `None` if they quit without confirming
+pub fn select(items: Vec<Candidate>) -> Result<Option<Vec<(ArtifactKind, PathBuf)>>> {
+    let mut selection = Selection::new(items);
+    let mut cursor_row = 0usize;
+    let mut scroll = 0usize;
+    let mut editing_filter = false;
+
+    let _guard = TerminalGuard::enter()?;
+    let mut out = std::io::stdout();
+
+    loop {
+        let rows = selection.rows();
+        cursor_row = cursor_row.min(rows.len().saturating_sub(1));
+
+        // Keep the cursor on screen below the two header lines and above the footer
+        let (width, height) = terminal::size()?;
+        let list_height = (height as usize).saturating_sub(4).max(1);
+        if cursor_row < scroll {
+            scroll = cursor_row;
+        } else if cursor_row >= scroll + list_height {
+            scroll = cursor_row + 1 - list_height;
+        }
+
+        queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        let sort = match selection.sort_by {
+            SortBy::Size => "size",
+            SortBy::Age => "age",
+            SortBy::Path => "path",
+        };
+        let group = match selection.group_by {
+            GroupBy::Kind => "kind",
+            GroupBy::Project => "project",
+        };
+        let filter = if editing_filter || !selection.filter.is_empty() {
+            format!(
+                "  filter: {}{}",
+                selection.filter,
+                if editing_filter { "_" } else { "" }
+            )
+        } else {
+            String::new()
+        };
+        queue!(
+            out,
+            SetAttribute(Attribute::Bold),
+            Print(truncate(
+                &format!(
+                    "wd-40: select what to clean (sort: {}, group: {}){}",
+                    sort, group, filter
+                ),
+                width
+            )),
+            SetAttribute(Attribute::Reset),
+            cursor::MoveTo(0, 1),
+            Print(truncate(HELP, width)),
+        )?;
+
+        for (line, row) in rows.iter().enumerate().skip(scroll).take(list_height) {
+            queue!(out, cursor::MoveTo(0, (line - scroll + 2) as u16))?;
+            if line == cursor_row {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            if matches!(row, Row::Group(_)) {
+                queue!(out, SetAttribute(Attribute::Bold))?;
+            }
+            queue!(
+                out,
+                Print(truncate(&selection.describe(row), width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+
+        let (count, bytes) = selection.selected_totals();
+        queue!(
+            out,
+            cursor::MoveTo(0, height.saturating_sub(1)),
+            Print(truncate(
+                &format!(
+                    "Selected: {} {}, {}",
+                    count,
+                    if count == 1 { "item" } else { "items" },
+                    human_bytes(bytes)
+                ),
+                width
+            )),
+        )?;
+        out.flush()?;
+
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        }) = event::read()?
+        else {
+            continue;
+        };
+        if kind != KeyEventKind::Press {
+            continue;
+        }
+
+        if editing_filter {
+            match code {
+                KeyCode::Enter | KeyCode::Esc => editing_filter = false,
+                KeyCode::Backspace => {
+                    selection.filter.pop();
+                }
+                KeyCode::Char(c) => selection.filter.push(c),
+                _ => {}
+            }
+            continue;
+        }
+
+        match code {
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            KeyCode::Enter => return Ok(Some(selection.chosen())),
+            KeyCode::Up | KeyCode::Char('k') => cursor_row = cursor_row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => cursor_row += 1,
+            KeyCode::PageUp => cursor_row = cursor_row.saturating_sub(list_height),
+            KeyCode::PageDown => cursor_row += list_height,
+            KeyCode::Char(' ') => {
+                if let Some(row) = rows.get(cursor_row) {
+                    selection.toggle(row);
+                }
+            }
+            KeyCode::Char('a') => selection.toggle_all(),
+            KeyCode::Char('s') => {
+                selection.sort_by = match selection.sort_by {
+                    SortBy::Size => SortBy::Age,
+                    SortBy::Age => SortBy::Path,
+                    SortBy::Path => SortBy::Size,
+                };
+            }
+            KeyCode::Char('g') => {
+                selection.group_by = match selection.group_by {
+                    GroupBy::Kind => GroupBy::Project,
+                    GroupBy::Project => GroupBy::Kind,
+                };
+                cursor_row = 0;
+            }
+            KeyCode::Char('/') => editing_filter = true,
+            _ => {}
+        }
+    }
+}
+
+/// Cuts a line to the terminal width
+fn truncate(line: &str, width: u16) -> String {
+    line.chars().take(width as usize).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::time::Duration;
+
+    fn candidate(kind: ArtifactKind, path: &str, size: u64, days_ago: u64) -> Candidate {
+        Candidate {
+            kind,
+            path: PathBuf::from(path),
+            last_used: Some(SystemTime::now() - Duration::from_secs(days_ago * 24 * 60 * 60)),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_selection_groups_sorts_filters_and_toggles() {
+        let mut selection = Selection::new(vec![
+            candidate(ArtifactKind::NODE_MODULES, "/src/web/node_modules", 10, 5),
+            candidate(ArtifactKind::NODE_MODULES, "/src/api/node_modules", 30, 50),
+            candidate(ArtifactKind::PYTHON_VENV, "/src/ml/.venv", 100, 1),
+        ]);
+
+        // Largest group first, largest item first within it
+        assert_eq!(
+            selection.rows(),
+            [
+                Row::Group("python-venv".to_string()),
+                Row::Item(2),
+                Row::Group("node-modules".to_string()),
+                Row::Item(1),
+                Row::Item(0),
+            ]
+        );
+
+        selection.sort_by = SortBy::Age;
+        assert_eq!(selection.rows()[0], Row::Group("node-modules".to_string()));
+        assert_eq!(selection.rows()[1], Row::Item(1));
+
+        // Items never used (as far as we can tell) go last, in a stable order
+        let mut unknown = Selection::new(vec![
+            Candidate {
+                last_used: None,
+                ..candidate(ArtifactKind::NEXT, "/src/site/.next", 1, 0)
+            },
+            candidate(ArtifactKind::NEXT, "/src/blog/.next", 1, 3),
+            Candidate {
+                last_used: None,
+                ..candidate(ArtifactKind::NEXT, "/src/docs/.next", 1, 0)
+            },
+        ]);
+        unknown.sort_by = SortBy::Age;
+        assert_eq!(
+            unknown.rows(),
+            [
+                Row::Group("next".to_string()),
+                Row::Item(1),
+                Row::Item(0),
+                Row::Item(2)
+            ]
+        );
+
+        // Toggling a group only affects its visible members
+        selection.filter = "API".to_string();
+        assert_eq!(
+            selection.rows(),
+            [Row::Group("node-modules".to_string()), Row::Item(1)]
+        );
+        selection.toggle(&Row::Group("node-modules".to_string()));
+        selection.filter.clear();
+        assert!(!selection.is_selected(1));
+        assert!(selection.is_selected(0));
+
+        selection.toggle(&Row::Item(2));
+        assert_eq!(
+            selection.chosen(),
+            [(
+                ArtifactKind::NODE_MODULES,
+                Path::new("/src/web/node_modules").to_path_buf()
+            )]
+        );
+        assert_eq!(selection.selected_totals(), (1, 10));
+    }
+
+    #[test]
+    fn test_chosen_excludes_filtered_out_items() {
+        let mut selection = Selection::new(vec![
+            candidate(ArtifactKind::NODE_MODULES, "/src/foo/node_modules", 10, 5),
+            candidate(ArtifactKind::NODE_MODULES, "/src/bar/node_modules", 30, 50),
+        ]);
+
+        // Everything starts selected, but only what the filter shows is chosen
+        selection.filter = "foo".to_string();
+        assert_eq!(
+            selection.chosen(),
+            [(
+                ArtifactKind::NODE_MODULES,
+                Path::new("/src/foo/node_modules").to_path_buf()
+            )]
+        );
+        assert_eq!(selection.selected_totals(), (1, 10));
+
+        selection.filter.clear();
+        assert_eq!(selection.chosen().len(), 2);
+    }
+}
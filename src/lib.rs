@@ -1,5 +1,24 @@
 // Library interface for WD-40
 // This allows integration tests to access the cleaner and walker modules
 
+pub mod age;
+pub mod artifacts;
+pub mod budget;
 pub mod cleaner;
+pub mod history;
+pub mod ignore_rules;
+pub mod in_use;
+pub mod interactive;
+pub mod logging;
+pub mod quarantine;
+pub mod report;
+pub mod retention;
+pub mod rustup;
+pub mod sccache;
+pub mod sweep;
+pub mod target_dir;
+pub mod target_parts;
+pub mod tracked;
+pub mod validate;
 pub mod walker;
+pub mod workspace;
@@ -1,91 +1,269 @@
+use crate::age::{describe_last_use, format_age, AgeDecision};
+use crate::artifacts::{ArtifactKind, Detector};
+use crate::budget::Plan;
+use crate::cleaner::CleanResult;
+use crate::history::{CleanedItem, History, RunRecord, HISTORY_SCHEMA_VERSION};
+use crate::ignore_rules::ExcludedPath;
+use crate::report::{KindTotals, Outcome};
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Version of the JSON-lines log schema, written on every line.
+///
+/// Adding fields or events keeps the version; removing or changing the meaning of one bumps it.
+/// Version 2 logs items refused for being in use or tracked by git as `refused`.
+pub const LOG_SCHEMA_VERSION: u32 = 2;
+
+/// How the log file is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Free-form text for people
+    Text,
+    /// One JSON object per event, for auditing tools
+    #[value(name = "jsonl")]
+    JsonLines,
+}
+
+impl LogFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Text => "log",
+            LogFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+/// One line of a JSON-lines log; `run_id` ties together every event of one invocation
+#[derive(Serialize)]
+struct Line<'a> {
+    schema_version: u32,
+    run_id: &'a str,
+    time: DateTime<Local>,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    RunStart {
+        root: String,
+        dry_run: bool,
+        /// Artifact kinds the run looks for
+        kinds: &'a [&'static str],
+        /// Command-line arguments, without the program name
+        args: Vec<String>,
+    },
+    /// Artifacts are moved into quarantine under the run id instead of being deleted
+    Quarantine,
+    Found {
+        kind: &'static str,
+        path: String,
+        /// Members of a workspace root, cleaned along with it
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        members: Vec<String>,
+    },
+    Excluded {
+        path: String,
+        ignore_file: String,
+    },
+    AgeDecision {
+        kind: &'static str,
+        path: String,
+        /// Seconds since last use, or null if unknown
+        age_secs: Option<u64>,
+        min_age_secs: u64,
+        selected: bool,
+    },
+    BudgetPlan {
+        needed_bytes: u64,
+        available_bytes: u64,
+        planned_bytes: u64,
+    },
+    BudgetCandidate {
+        kind: &'static str,
+        path: String,
+        size_bytes: u64,
+        last_used: Option<DateTime<Local>>,
+        chosen: bool,
+    },
+    CleaningStart,
+    /// Free space after cleaning towards a space goal; `met` is false if the goal was missed
+    BudgetResult {
+        goal_bytes: u64,
+        available_bytes: u64,
+        met: bool,
+    },
+    /// A project or artifact was processed; `size_bytes` is exact (or the estimate in a dry run)
+    Result {
+        kind: &'static str,
+        path: &'a str,
+        size_bytes: Option<u64>,
+        #[serde(flatten)]
+        outcome: Outcome,
+    },
+    Summary {
+        projects: usize,
+        cleaned: usize,
+        target_only: usize,
+        skipped: usize,
+        refused: usize,
+        failed: usize,
+        size_bytes: u64,
+        by_kind: BTreeMap<&'static str, KindTotals>,
+    },
+}
 
 pub struct Logger {
-    file: File,
-    log_path: PathBuf,
+    /// The log file, or a sink when the run is not logged
+    file: Box<dyn Write>,
+    log_path: Option<PathBuf>,
+    format: LogFormat,
+    run_id: String,
+    /// In a dry run nothing is deleted, so sizes are logged as estimates
+    dry_run: bool,
+    /// Artifacts are moved into quarantine rather than deleted
+    quarantine: bool,
+    started: DateTime<Local>,
+    root: PathBuf,
+    cleaned: Vec<CleanedItem>,
+    /// Where the summary of the run is recorded for `wd-40 history`
+    history: Option<History>,
 }
 
 impl Logger {
-    /// Creates a new logger, either at the specified path or in the default cache directory
-    pub fn new(custom_path: Option<PathBuf>) -> Result<Self> {
+    /// Creates a new logger for a fresh run, either at the specified path or in the default cache directory
+    pub fn new(custom_path: Option<PathBuf>, dry_run: bool, format: LogFormat) -> Result<Self> {
+        let run_id = new_run_id();
         let log_path = if let Some(path) = custom_path {
             path
         } else {
-            // Default: ~/.cache/wd-40/clean-<timestamp>.log
-            let cache_dir = dirs::cache_dir()
-                .context("Failed to determine cache directory")?
-                .join("wd-40");
+            // Default: ~/.cache/wd-40/clean-<run-id>.log (or .jsonl)
+            let cache_dir = default_log_dir()?;
 
             fs::create_dir_all(&cache_dir)
                 .context("Failed to create cache directory")?;
 
-            let timestamp = Local::now().format("%Y%m%d-%H%M%S");
-            cache_dir.join(format!("clean-{}.log", timestamp))
+            cache_dir.join(format!("clean-{}.{}", run_id, format.extension()))
         };
 
         let file = File::create(&log_path)
             .with_context(|| format!("Failed to create log file: {}", log_path.display()))?;
 
-        let mut logger = Logger { file, log_path };
-        logger.write_header()?;
-        Ok(logger)
+        Ok(Logger {
+            file: Box::new(file),
+            log_path: Some(log_path),
+            format,
+            run_id,
+            dry_run,
+            quarantine: false,
+            started: Local::now(),
+            root: PathBuf::new(),
+            cleaned: Vec::new(),
+            history: History::default_location().ok(),
+        })
+    }
+
+    /// Creates a logger that writes nothing, not even to the history
+    pub fn disabled(dry_run: bool) -> Self {
+        Logger {
+            file: Box::new(std::io::sink()),
+            log_path: None,
+            format: LogFormat::Text,
+            run_id: new_run_id(),
+            dry_run,
+            quarantine: false,
+            started: Local::now(),
+            root: PathBuf::new(),
+            cleaned: Vec::new(),
+            history: None,
+        }
+    }
+
+    /// Returns the path to the log file, if the run is logged
+    pub fn path(&self) -> Option<&Path> {
+        self.log_path.as_deref()
     }
 
-    /// Returns the path to the log file
-    pub fn path(&self) -> &Path {
-        &self.log_path
+    /// Identifies this run in every log event and in the quarantine
+    pub fn run_id(&self) -> &str {
+        &self.run_id
     }
 
-    fn write_header(&mut self) -> Result<()> {
+    /// Writes the header: when and where the run started and which kinds it looks for
+    pub fn log_run_start(&mut self, root: &Path, kinds: &[ArtifactKind]) -> Result<()> {
+        self.root = root.to_path_buf();
+        if self.format == LogFormat::JsonLines {
+            let kinds: Vec<&'static str> = kinds.iter().map(|kind| kind.id()).collect();
+            return self.emit(Event::RunStart {
+                root: root.display().to_string(),
+                dry_run: self.dry_run,
+                kinds: &kinds,
+                args: std::env::args().skip(1).collect(),
+            });
+        }
+
         writeln!(self.file, "WD-40 Rust Project Cleaner")?;
         writeln!(self.file, "==========================")?;
         writeln!(self.file, "Started: {}", Local::now().format("%Y-%m-%d %H:%M:%S"))?;
+        writeln!(self.file, "Run: {}", self.run_id)?;
+        writeln!(self.file, "Root: {}", root.display())?;
+        if self.dry_run {
+            writeln!(self.file, "Mode: dry run (nothing is deleted)")?;
+        }
         writeln!(self.file)?;
         Ok(())
     }
 
-    pub fn log_found_projects(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} projects:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    /// Records that this run moves artifacts into quarantine under its run id
+    pub fn log_quarantine_run(&mut self) -> Result<()> {
+        self.quarantine = true;
+        if self.format == LogFormat::JsonLines {
+            return self.emit(Event::Quarantine);
         }
+
+        writeln!(self.file, "Quarantine run: {} (undo with `wd-40 restore {}`)", self.run_id, self.run_id)?;
         writeln!(self.file)?;
         Ok(())
     }
 
-    pub fn log_found_orphaned(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} orphaned target directories:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    /// Logs the projects found, with each workspace root's members listed under it
+    pub fn log_found_projects(&mut self, paths: &[PathBuf], workspaces: &BTreeMap<PathBuf, Vec<PathBuf>>) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            for path in paths {
+                let members = workspaces.get(path).map(Vec::as_slice).unwrap_or(&[]);
+                self.emit(Event::Found {
+                    kind: ArtifactKind::RUST_PROJECT.id(),
+                    path: path.display().to_string(),
+                    members: members.iter().map(|member| member.display().to_string()).collect(),
+                })?;
+            }
+            return Ok(());
         }
-        writeln!(self.file)?;
-        Ok(())
-    }
 
-    pub fn log_found_node_modules(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} node_modules directories:", count)?;
+        writeln!(self.file, "Found {} projects:", paths.len())?;
         for path in paths {
             writeln!(self.file, "  - {}", path.display())?;
+            for member in workspaces.get(path).into_iter().flatten() {
+                writeln!(self.file, "      member {}", member.display())?;
+            }
         }
         writeln!(self.file)?;
         Ok(())
     }
 
-    pub fn log_found_venvs(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} Python virtual environments:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    pub fn log_found(&mut self, detector: &Detector, paths: &[PathBuf]) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            return self.emit_found(detector.kind, paths);
         }
-        writeln!(self.file)?;
-        Ok(())
-    }
 
-    pub fn log_found_sccache(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} sccache directories:", count)?;
+        writeln!(self.file, "Found {} {}:", paths.len(), detector.noun(paths.len()))?;
         for path in paths {
             writeln!(self.file, "  - {}", path.display())?;
         }
@@ -93,56 +271,162 @@ impl Logger {
         Ok(())
     }
 
-    pub fn log_found_stack_work(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} Stack work directories:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    pub fn log_excluded(&mut self, excluded: &[ExcludedPath]) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            for entry in excluded {
+                self.emit(Event::Excluded {
+                    path: entry.path.display().to_string(),
+                    ignore_file: entry.ignore_file.display().to_string(),
+                })?;
+            }
+            return Ok(());
+        }
+
+        if excluded.is_empty() {
+            return Ok(());
+        }
+        writeln!(self.file, "Excluded {} candidates via .wd40ignore:", excluded.len())?;
+        for entry in excluded {
+            writeln!(
+                self.file,
+                "  - {} (by {})",
+                entry.path.display(),
+                entry.ignore_file.display()
+            )?;
         }
         writeln!(self.file)?;
         Ok(())
     }
 
-    pub fn log_found_rustup(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} rustup directories:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    pub fn log_age_decisions(&mut self, decisions: &[AgeDecision], min_age: Duration) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            for decision in decisions {
+                self.emit(Event::AgeDecision {
+                    kind: decision.kind.id(),
+                    path: decision.path.display().to_string(),
+                    age_secs: decision.age.map(|age| age.as_secs()),
+                    min_age_secs: min_age.as_secs(),
+                    selected: decision.selected,
+                })?;
+            }
+            return Ok(());
+        }
+
+        writeln!(self.file, "Age filter: older than {}", format_age(min_age))?;
+        for decision in decisions {
+            let verdict = if decision.selected { "selected" } else { "skipped" };
+            match decision.age {
+                Some(age) => writeln!(
+                    self.file,
+                    "  - {}: last used {} ago ({})",
+                    decision.path.display(),
+                    format_age(age),
+                    verdict
+                )?,
+                None => writeln!(self.file, "  - {}: last use unknown ({})", decision.path.display(), verdict)?,
+            }
         }
         writeln!(self.file)?;
         Ok(())
     }
 
-    pub fn log_found_next(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} Next.js build directories:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    pub fn log_budget_plan(&mut self, plan: &Plan) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            self.emit(Event::BudgetPlan {
+                needed_bytes: plan.needed,
+                available_bytes: plan.available,
+                planned_bytes: plan.planned(),
+            })?;
+            let chosen = plan.chosen.iter().map(|c| (c, true));
+            let left = plan.left.iter().map(|c| (c, false));
+            for (candidate, chosen) in chosen.chain(left) {
+                self.emit(Event::BudgetCandidate {
+                    kind: candidate.kind.id(),
+                    path: candidate.path.display().to_string(),
+                    size_bytes: candidate.size,
+                    last_used: candidate.last_used.map(DateTime::from),
+                    chosen,
+                })?;
+            }
+            return Ok(());
+        }
+
+        writeln!(
+            self.file,
+            "Space goal: {} to free ({} available at start), {} chosen:",
+            human_bytes(plan.needed),
+            human_bytes(plan.available),
+            human_bytes(plan.planned())
+        )?;
+        for candidate in &plan.chosen {
+            writeln!(
+                self.file,
+                "  - {} ({}, {}) (chosen)",
+                candidate.path.display(),
+                human_bytes(candidate.size),
+                describe_last_use(candidate.last_used)
+            )?;
+        }
+        for candidate in &plan.left {
+            writeln!(
+                self.file,
+                "  - {} ({}, {}) (left alone)",
+                candidate.path.display(),
+                human_bytes(candidate.size),
+                describe_last_use(candidate.last_used)
+            )?;
         }
         writeln!(self.file)?;
         Ok(())
     }
 
-    pub fn log_found_cargo_nix(&mut self, count: usize, paths: &[PathBuf]) -> Result<()> {
-        writeln!(self.file, "Found {} cargo-nix directories:", count)?;
-        for path in paths {
-            writeln!(self.file, "  - {}", path.display())?;
+    pub fn log_budget_result(&mut self, goal: u64, available: u64) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            return self.emit(Event::BudgetResult {
+                goal_bytes: goal,
+                available_bytes: available,
+                met: available >= goal,
+            });
+        }
+
+        if available >= goal {
+            writeln!(self.file, "Space goal met: {} available (goal {})", human_bytes(available), human_bytes(goal))?;
+        } else {
+            writeln!(
+                self.file,
+                "Space goal missed by {}: {} available (goal {})",
+                human_bytes(goal - available),
+                human_bytes(available),
+                human_bytes(goal)
+            )?;
         }
-        writeln!(self.file)?;
         Ok(())
     }
 
     pub fn log_cleaning_start(&mut self) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            return self.emit(Event::CleaningStart);
+        }
+
         writeln!(self.file, "Starting cleanup...")?;
         writeln!(self.file)?;
         Ok(())
     }
 
     pub fn log_success(&mut self, project: &str, space_freed: Option<u64>) -> Result<()> {
+        self.record_cleaned(ArtifactKind::RUST_PROJECT, project, space_freed.unwrap_or(0));
+        if self.format == LogFormat::JsonLines {
+            return self.emit_result(ArtifactKind::RUST_PROJECT, project, space_freed, Outcome::Success);
+        }
+
         let timestamp = Local::now().format("%H:%M:%S");
         if let Some(bytes) = space_freed {
             writeln!(
                 self.file,
-                "[{}] SUCCESS: {} (freed {})",
+                "[{}] SUCCESS: {} ({} {})",
                 timestamp,
                 project,
+                self.freed_verb(),
                 human_bytes(bytes)
             )?;
         } else {
@@ -152,169 +436,253 @@ impl Logger {
     }
 
     pub fn log_skipped(&mut self, project: &str, reason: &str) -> Result<()> {
-        let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(self.file, "[{}] SKIPPED: {} - {}", timestamp, project, reason)?;
-        Ok(())
+        self.log_artifact_skipped(ArtifactKind::RUST_PROJECT, project, reason)
+    }
+
+    pub fn log_refused(&mut self, project: &str, reason: &str) -> Result<()> {
+        self.log_artifact_refused(ArtifactKind::RUST_PROJECT, project, reason)
     }
 
     pub fn log_failed(&mut self, project: &str, error: &str) -> Result<()> {
-        let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(self.file, "[{}] FAILED: {} - {}", timestamp, project, error)?;
-        Ok(())
+        self.log_artifact_failed(ArtifactKind::RUST_PROJECT, project, error)
     }
 
     pub fn log_target_only(&mut self, project: &str, space_freed: u64, reason: &str) -> Result<()> {
+        self.record_cleaned(ArtifactKind::RUST_PROJECT, project, space_freed);
+        if self.format == LogFormat::JsonLines {
+            let outcome = Outcome::TargetOnly {
+                reason: reason.to_string(),
+            };
+            return self.emit_result(ArtifactKind::RUST_PROJECT, project, Some(space_freed), outcome);
+        }
+
         let timestamp = Local::now().format("%H:%M:%S");
         writeln!(
             self.file,
-            "[{}] TARGET ONLY: {} (freed {}) - {}",
+            "[{}] TARGET ONLY: {} ({} {}) - {}",
             timestamp,
             project,
+            self.freed_verb(),
             human_bytes(space_freed),
             reason
         )?;
         Ok(())
     }
 
-    pub fn log_orphaned_cleaned(&mut self, target_path: &str, space_freed: u64) -> Result<()> {
-        let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] ORPHANED: {} (freed {})",
-            timestamp,
-            target_path,
-            human_bytes(space_freed)
-        )?;
-        Ok(())
-    }
+    pub fn log_artifact_cleaned(&mut self, detector: &Detector, path: &str, space_freed: u64) -> Result<()> {
+        self.record_cleaned(detector.kind, path, space_freed);
+        if self.format == LogFormat::JsonLines {
+            return self.emit_result(detector.kind, path, Some(space_freed), Outcome::Success);
+        }
 
-    pub fn log_node_modules_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
         let timestamp = Local::now().format("%H:%M:%S");
         writeln!(
             self.file,
-            "[{}] NODE_MODULES: {} (freed {})",
+            "[{}] {}: {} ({} {})",
             timestamp,
+            detector.log_tag,
             path,
+            self.freed_verb(),
             human_bytes(space_freed)
         )?;
         Ok(())
     }
 
-    pub fn log_venv_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
-        let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] PYTHON_VENV: {} (freed {})",
-            timestamp,
-            path,
-            human_bytes(space_freed)
-        )?;
-        Ok(())
-    }
+    pub fn log_artifact_skipped(&mut self, kind: ArtifactKind, path: &str, reason: &str) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            let outcome = Outcome::Skipped {
+                reason: reason.to_string(),
+            };
+            return self.emit_result(kind, path, None, outcome);
+        }
 
-    pub fn log_sccache_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
         let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] SCCACHE: {} (freed {})",
-            timestamp,
-            path,
-            human_bytes(space_freed)
-        )?;
+        writeln!(self.file, "[{}] SKIPPED: {} - {}", timestamp, path, reason)?;
         Ok(())
     }
 
-    pub fn log_stack_work_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
-        let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] STACK_WORK: {} (freed {})",
-            timestamp,
-            path,
-            human_bytes(space_freed)
-        )?;
-        Ok(())
-    }
+    pub fn log_artifact_refused(&mut self, kind: ArtifactKind, path: &str, reason: &str) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            let outcome = Outcome::Refused {
+                reason: reason.to_string(),
+            };
+            return self.emit_result(kind, path, None, outcome);
+        }
 
-    pub fn log_rustup_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
         let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] RUSTUP: {} (freed {})",
-            timestamp,
-            path,
-            human_bytes(space_freed)
-        )?;
+        writeln!(self.file, "[{}] REFUSED: {} - {}", timestamp, path, reason)?;
         Ok(())
     }
 
-    pub fn log_next_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
+    pub fn log_artifact_failed(&mut self, kind: ArtifactKind, path: &str, error: &str) -> Result<()> {
+        if self.format == LogFormat::JsonLines {
+            let outcome = Outcome::Failed {
+                error: error.to_string(),
+            };
+            return self.emit_result(kind, path, None, outcome);
+        }
+
         let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] NEXT: {} (freed {})",
-            timestamp,
-            path,
-            human_bytes(space_freed)
-        )?;
+        writeln!(self.file, "[{}] FAILED: {} - {}", timestamp, path, error)?;
         Ok(())
     }
 
-    pub fn log_cargo_nix_cleaned(&mut self, path: &str, space_freed: u64) -> Result<()> {
-        let timestamp = Local::now().format("%H:%M:%S");
-        writeln!(
-            self.file,
-            "[{}] CARGO_NIX: {} (freed {})",
-            timestamp,
-            path,
-            human_bytes(space_freed)
-        )?;
-        Ok(())
+    fn freed_verb(&self) -> &'static str {
+        if self.dry_run {
+            "would free"
+        } else if self.quarantine {
+            "quarantined"
+        } else {
+            "freed"
+        }
     }
 
+    /// Writes the summary and records the run in the history
     pub fn log_summary(
         &mut self,
-        total: usize,
-        successful: usize,
-        target_only: usize,
-        skipped: usize,
-        failed: usize,
-        orphaned_cleaned: usize,
-        node_modules_cleaned: usize,
-        venvs_cleaned: usize,
-        sccache_cleaned: usize,
-        stack_work_cleaned: usize,
-        rustup_cleaned: usize,
-        next_cleaned: usize,
-        cargo_nix_cleaned: usize,
+        results: &[CleanResult],
+        cleaned: &[(&Detector, usize, u64)],
         total_space_freed: u64,
     ) -> Result<()> {
+        let successful = results.iter().filter(|r| r.is_success()).count();
+        let target_only = results.iter().filter(|r| r.is_target_only()).count();
+        let refused = results.iter().filter(|r| r.is_refused()).count();
+        let skipped = results.iter().filter(|r| r.is_skipped()).count() - refused;
+        let failed = results.len() - successful - target_only - skipped - refused;
+
+        if self.format == LogFormat::JsonLines {
+            let mut by_kind = BTreeMap::new();
+            let project_space: u64 = results.iter().filter_map(|r| r.space_freed()).sum();
+            if successful + target_only > 0 {
+                by_kind.insert(
+                    ArtifactKind::RUST_PROJECT.id(),
+                    KindTotals {
+                        cleaned: successful + target_only,
+                        size_bytes: project_space,
+                    },
+                );
+            }
+            for (detector, count, bytes) in cleaned.iter().filter(|(_, count, _)| *count > 0) {
+                by_kind.insert(
+                    detector.kind.id(),
+                    KindTotals {
+                        cleaned: *count,
+                        size_bytes: *bytes,
+                    },
+                );
+            }
+            self.emit(Event::Summary {
+                projects: results.len(),
+                cleaned: by_kind.values().map(|totals| totals.cleaned).sum(),
+                target_only,
+                skipped,
+                refused,
+                failed,
+                size_bytes: total_space_freed,
+                by_kind,
+            })?;
+            return self.record_history();
+        }
+
         writeln!(self.file)?;
         writeln!(self.file, "==========================")?;
         writeln!(self.file, "Summary")?;
         writeln!(self.file, "==========================")?;
-        writeln!(self.file, "Total projects found: {}", total)?;
+        writeln!(self.file, "Total projects found: {}", results.len())?;
         writeln!(self.file, "Successfully cleaned: {}", successful)?;
         writeln!(self.file, "Target-only cleaned: {}", target_only)?;
         writeln!(self.file, "Skipped: {}", skipped)?;
+        writeln!(self.file, "Refused: {}", refused)?;
         writeln!(self.file, "Failed: {}", failed)?;
-        writeln!(self.file, "Orphaned targets cleaned: {}", orphaned_cleaned)?;
-        writeln!(self.file, "Node modules cleaned: {}", node_modules_cleaned)?;
-        writeln!(self.file, "Python venvs cleaned: {}", venvs_cleaned)?;
-        writeln!(self.file, "Sccache dirs cleaned: {}", sccache_cleaned)?;
-        writeln!(self.file, "Stack work dirs cleaned: {}", stack_work_cleaned)?;
-        writeln!(self.file, "Rustup dirs cleaned: {}", rustup_cleaned)?;
-        writeln!(self.file, "Next.js builds cleaned: {}", next_cleaned)?;
-        writeln!(self.file, "Cargo-nix dirs cleaned: {}", cargo_nix_cleaned)?;
-        writeln!(self.file, "Total space freed: {}", human_bytes(total_space_freed))?;
+        for (detector, count, bytes) in cleaned {
+            writeln!(self.file, "{} cleaned: {} ({})", detector.noun(2), count, human_bytes(*bytes))?;
+        }
+        if self.dry_run {
+            writeln!(self.file, "Estimated space to free: {}", human_bytes(total_space_freed))?;
+        } else if self.quarantine {
+            writeln!(self.file, "Total moved to quarantine: {}", human_bytes(total_space_freed))?;
+        } else {
+            writeln!(self.file, "Total space freed: {}", human_bytes(total_space_freed))?;
+        }
         writeln!(self.file)?;
         writeln!(self.file, "Completed: {}", Local::now().format("%Y-%m-%d %H:%M:%S"))?;
+        self.record_history()
+    }
+
+    fn record_cleaned(&mut self, kind: ArtifactKind, path: &str, size_bytes: u64) {
+        self.cleaned.push(CleanedItem {
+            kind: kind.id().to_string(),
+            path: PathBuf::from(path),
+            size_bytes,
+        });
+    }
+
+    fn record_history(&mut self) -> Result<()> {
+        let Some(history) = &self.history else {
+            return Ok(());
+        };
+        history.append(&RunRecord {
+            schema_version: HISTORY_SCHEMA_VERSION,
+            run_id: self.run_id.clone(),
+            started: self.started,
+            finished: Local::now(),
+            root: self.root.clone(),
+            dry_run: self.dry_run,
+            quarantine: self.quarantine,
+            log_file: self.log_path.clone().unwrap_or_default(),
+            cleaned: std::mem::take(&mut self.cleaned),
+        })
+    }
+
+    fn emit_found(&mut self, kind: ArtifactKind, paths: &[PathBuf]) -> Result<()> {
+        for path in paths {
+            self.emit(Event::Found {
+                kind: kind.id(),
+                path: path.display().to_string(),
+                members: Vec::new(),
+            })?;
+        }
         Ok(())
     }
+
+    fn emit_result(&mut self, kind: ArtifactKind, path: &str, size_bytes: Option<u64>, outcome: Outcome) -> Result<()> {
+        self.emit(Event::Result {
+            kind: kind.id(),
+            path,
+            size_bytes,
+            outcome,
+        })
+    }
+
+    /// Writes one JSON-lines event, flushed so the log survives a crash mid-run
+    fn emit(&mut self, event: Event) -> Result<()> {
+        let line = Line {
+            schema_version: LOG_SCHEMA_VERSION,
+            run_id: &self.run_id,
+            time: Local::now(),
+            event,
+        };
+        serde_json::to_writer(&mut self.file, &line)?;
+        writeln!(self.file)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Where run logs go unless `--log-file` says otherwise: ~/.cache/wd-40
+pub fn default_log_dir() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .context("Failed to determine cache directory")?
+        .join("wd-40"))
+}
+
+/// Identifies one run of wd-40, e.g. `20250112-143055-4242`
+pub fn new_run_id() -> String {
+    format!("{}-{}", Local::now().format("%Y%m%d-%H%M%S"), std::process::id())
 }
 
 /// Converts bytes to human-readable format
-fn human_bytes(bytes: u64) -> String {
+pub fn human_bytes(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
 
     if bytes == 0 {
@@ -349,4 +717,45 @@ mod tests {
         assert_eq!(human_bytes(1048576), "1.00 MB");
         assert_eq!(human_bytes(1073741824), "1.00 GB");
     }
+
+    #[test]
+    fn test_json_lines_log_events_share_run_id() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("clean.jsonl");
+        let registry = crate::artifacts::Registry::builtin();
+        let detector = registry.get(ArtifactKind::NODE_MODULES).unwrap();
+
+        let mut logger = Logger::new(Some(log_path.clone()), false, LogFormat::JsonLines).unwrap();
+        logger.history = Some(History::new(temp_dir.path().join("history.jsonl")));
+        logger.log_run_start(temp_dir.path(), &[ArtifactKind::NODE_MODULES]).unwrap();
+        logger.log_found(detector, &[PathBuf::from("/src/web/node_modules")]).unwrap();
+        logger.log_artifact_cleaned(detector, "/src/web/node_modules", 1_234_567).unwrap();
+        logger.log_artifact_failed(ArtifactKind::NODE_MODULES, "/src/api/node_modules", "permission denied").unwrap();
+        logger.log_summary(&[], &[(detector, 1, 1_234_567)], 1_234_567).unwrap();
+        let run_id = logger.run_id().to_string();
+        drop(logger);
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&log_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let events: Vec<&str> = lines.iter().map(|line| line["event"].as_str().unwrap()).collect();
+        assert_eq!(events, ["run_start", "found", "result", "result", "summary"]);
+        assert!(lines.iter().all(|line| line["run_id"] == run_id.as_str()));
+        assert!(lines.iter().all(|line| line["schema_version"] == LOG_SCHEMA_VERSION));
+
+        assert_eq!(lines[0]["kinds"], serde_json::json!(["node-modules"]));
+        assert_eq!(lines[2]["size_bytes"], 1_234_567);
+        assert_eq!(lines[2]["status"], "success");
+        assert_eq!(lines[3]["status"], "failed");
+        assert_eq!(lines[3]["error"], "permission denied");
+        assert_eq!(lines[4]["by_kind"]["node-modules"]["size_bytes"], 1_234_567);
+
+        // The run is recorded in the history too
+        let runs = History::new(temp_dir.path().join("history.jsonl")).load().unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].run_id, run_id);
+        assert_eq!(runs[0].size_bytes(), 1_234_567);
+    }
 }
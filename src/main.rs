@@ -1,20 +1,70 @@
+pub mod age;
+pub mod artifacts;
+pub mod budget;
 pub mod cleaner;
+pub mod history;
+pub mod ignore_rules;
+pub mod in_use;
+pub mod interactive;
 mod logging;
+pub mod quarantine;
+pub mod report;
+pub mod retention;
+pub mod rustup;
+pub mod sccache;
+pub mod sweep;
+pub mod target_dir;
+pub mod target_parts;
+pub mod tracked;
+pub mod validate;
 pub mod walker;
+pub mod workspace;
 
-use anyhow::Result;
-use clap::Parser;
+use age::{AgeDecision, AgeFilter, AgeSource};
+use anyhow::{bail, Result};
+use artifacts::{ArtifactKind, Detector, Registry, SuspiciousPath};
+use budget::{Goal, Plan};
+use clap::{Parser, Subcommand};
 use colored::Colorize;
-use logging::Logger;
-use std::path::PathBuf;
+use history::{History, RunRecord};
+use ignore_rules::ExcludedPath;
+use in_use::InUse;
+use logging::{human_bytes, LogFormat, Logger};
+use quarantine::QuarantineStore;
+use report::{ArtifactRecord, OutputFormat, PreservedFile, Reporter, SKIPPED_ARTIFACT_REASON};
+use retention::Retention;
+use sweep::Sweep;
+use target_parts::{TargetPart, TargetParts};
+use tracked::TrackedFiles;
+use validate::{Validation, Validator};
+use std::collections::{BTreeMap, HashMap};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How many of the biggest items a dry run lists
+const LARGEST_ITEMS_SHOWN: usize = 10;
+
+/// The paths cleaning an artifact removed (or would), with their sizes; `None` if it was skipped
+type Removed = Result<Option<Vec<(PathBuf, u64)>>>;
+
+/// Why items a space goal chose are left alone once enough space is free
+const GOAL_REACHED_REASON: &str = "space goal already reached";
+
+/// Why a configured sccache cache outside the searched directory isn't deleted
+const OUTSIDE_SCCACHE_REASON: &str = "outside the searched directory; only trimmed with --sccache-max-size";
 
 #[derive(Parser)]
 #[command(
     name = "wd-40",
     about = "A CLI tool to recursively find and clean Rust, Node.js, and Python project artifacts",
-    version
+    version,
+    args_conflicts_with_subcommands = true
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Directory to search for project artifacts (default: current directory)
     #[arg(default_value = ".")]
     path: PathBuf,
@@ -31,7 +81,7 @@ struct Cli {
     #[arg(short = 'y', long)]
     no_confirm: bool,
 
-    /// Force cleaning even if project validation fails
+    /// Force cleaning even if project validation fails or artifacts hold files tracked by git
     #[arg(short, long)]
     force: bool,
 
@@ -39,6 +89,26 @@ struct Cli {
     #[arg(short, long)]
     strict: bool,
 
+    /// How to validate Cargo projects: check Cargo.toml in-process, or run `cargo metadata`
+    #[arg(long, value_enum, default_value = "manifest")]
+    validator: Validator,
+
+    /// Give up validating a project after this long (it then counts as invalid), e.g. `30s`
+    #[arg(long, value_name = "AGE", value_parser = age::parse_duration, default_value = "30s")]
+    validate_timeout: Duration,
+
+    /// Validate this many projects at once (defaults to the number of CPUs)
+    #[arg(long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Count artifacts written to within this long as in use and skip them, e.g. `30s` (`0s` to turn off)
+    #[arg(long, value_name = "AGE", value_parser = age::parse_duration, default_value = "30s")]
+    in_use_window: Duration,
+
+    /// Wait up to this long for artifacts in use to be released instead of skipping them, e.g. `5m` (not in dry runs)
+    #[arg(long, value_name = "AGE", value_parser = age::parse_duration)]
+    wait_in_use: Option<Duration>,
+
     /// Clean only orphaned target directories (no parent Cargo.toml)
     #[arg(long)]
     orphaned_only: bool,
@@ -47,10 +117,14 @@ struct Cli {
     #[arg(long)]
     rust_only: bool,
 
-    /// Clean only Node.js node_modules directories
+    /// Clean only Node.js node_modules directories, orphaned ones included
     #[arg(long)]
     node_only: bool,
 
+    /// Clean only orphaned node_modules directories (no package.json or lockfile beside them)
+    #[arg(long)]
+    orphaned_node_modules_only: bool,
+
     /// Clean only Python virtual environments
     #[arg(long)]
     python_only: bool,
@@ -63,6 +137,21 @@ struct Cli {
     #[arg(long)]
     rustup_only: bool,
 
+    /// Delete whole .rustup directories, settings and default toolchain included, instead of pruning
+    /// the toolchains nothing uses
+    #[arg(long)]
+    whole_rustup: bool,
+
+    /// Trim sccache caches to at most SIZE, least recently used entries first, instead of deleting them;
+    /// the cache SCCACHE_DIR or sccache's config file points at is trimmed even outside PATH
+    #[arg(long, value_name = "SIZE", value_parser = budget::parse_size)]
+    sccache_max_size: Option<u64>,
+
+    /// Clean only the caches in .next directories (webpack, turbopack, images), keeping the
+    /// production build; how much is build output is reported alongside
+    #[arg(long)]
+    next_cache_only: bool,
+
     /// Clean only Next.js build (.next) directories
     #[arg(long)]
     next_only: bool,
@@ -71,19 +160,197 @@ struct Cli {
     #[arg(long)]
     cargo_nix_only: bool,
 
-    /// Custom log file path (default: ~/.cache/wd-40/clean-<timestamp>.log)
+    /// Clean only the given artifact kind (repeatable), e.g. `--only node-modules --only python-venv`
+    #[arg(long, value_name = "KIND")]
+    only: Vec<String>,
+
+    /// Only clean artifacts that have not been used for at least this long, e.g. `30d`, `12h`, `2w`
+    #[arg(long, value_name = "AGE", value_parser = age::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// What counts as use for --older-than: files inside the artifact, the owning project's sources, or both
+    #[arg(long, value_enum, default_value = "both")]
+    age_source: AgeSource,
+
+    /// Estimate last use for --older-than from the top levels of each artifact instead of every file
+    #[arg(long)]
+    age_sample: bool,
+
+    /// Clean only until the filesystem has this much free space, e.g. `50G`
+    #[arg(long, value_name = "SIZE", value_parser = budget::parse_size, conflicts_with = "free_at_least")]
+    target_free: Option<u64>,
+
+    /// Clean only until at least this much space has been freed, e.g. `50G`
+    #[arg(long, value_name = "SIZE", value_parser = budget::parse_size)]
+    free_at_least: Option<u64>,
+
+    /// Clean only this build profile in target directories (repeatable), e.g. `dev`, `release`
+    #[arg(long, value_name = "PROFILE", value_parser = target_parts::parse_dir_name)]
+    profile: Vec<String>,
+
+    /// Clean only this target triple's builds in target directories (repeatable)
+    #[arg(long, value_name = "TRIPLE", value_parser = target_parts::parse_dir_name)]
+    triple: Vec<String>,
+
+    /// Clean only this part of target directories (repeatable)
+    #[arg(long, value_enum, value_name = "PART")]
+    target_part: Vec<TargetPart>,
+
+    /// Never clean this top-level entry of target directories (repeatable), e.g. `criterion`
+    #[arg(long, value_name = "NAME")]
+    preserve: Vec<String>,
+
+    /// Keep each project's built executables, cdylibs and staticlibs, cleaning the rest of its target
+    #[arg(long)]
+    keep_executables: bool,
+
+    /// Remove only build artifacts in target directories that haven't been rebuilt for this long, e.g. `30d`
+    #[arg(long, value_name = "AGE", value_parser = age::parse_duration)]
+    sweep_older_than: Option<Duration>,
+
+    /// Remove only build artifacts in target directories made by toolchains that are no longer installed
+    #[arg(long)]
+    sweep_toolchains: bool,
+
+    /// Pick what to clean from a list before anything is removed (replaces the y/N prompt)
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// Move artifacts into a quarantine area instead of deleting them (see `restore` and `purge`)
+    #[arg(long)]
+    quarantine: bool,
+
+    /// Output format: colored text, a JSON document, or JSON lines streamed as items are processed
+    #[arg(long, value_enum, default_value = "human")]
+    format: OutputFormat,
+
+    /// Custom log file path (default: ~/.cache/wd-40/clean-<run-id>.log, or .jsonl)
     #[arg(long)]
     log_file: Option<PathBuf>,
+
+    /// Log file format: free-form text, or one JSON object per event for auditing
+    #[arg(long, value_enum, default_value = "text", env = "WD40_LOG_FORMAT")]
+    log_format: LogFormat,
+
+    /// Don't write a log (or a history entry) for dry runs
+    #[arg(long, env = "WD40_NO_DRY_RUN_LOG")]
+    no_dry_run_log: bool,
+
+    /// Keep only this many logs in ~/.cache/wd-40, removing older ones at startup
+    #[arg(long, value_name = "N", env = "WD40_LOG_KEEP")]
+    log_keep: Option<usize>,
+
+    /// Remove logs in ~/.cache/wd-40 older than this at startup, e.g. `90d`
+    #[arg(long, value_name = "AGE", value_parser = age::parse_duration, env = "WD40_LOG_MAX_AGE")]
+    log_max_age: Option<Duration>,
+
+    /// Keep only the newest logs in ~/.cache/wd-40 that together fit in this size, e.g. `100M`
+    #[arg(long, value_name = "SIZE", value_parser = budget::parse_size, env = "WD40_LOG_MAX_SIZE")]
+    log_max_size: Option<u64>,
+
+    /// Gzip earlier logs in ~/.cache/wd-40 at startup
+    #[arg(long, env = "WD40_LOG_COMPRESS")]
+    log_compress: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Move quarantined artifacts back; lists quarantine runs when no target is given
+    Restore {
+        /// A quarantine run id, or the original path of one quarantined artifact
+        target: Option<String>,
+    },
+    /// Permanently delete quarantined artifacts
+    Purge {
+        /// Only purge runs quarantined at least this long ago, e.g. `7d`
+        #[arg(long, value_name = "AGE", value_parser = age::parse_duration)]
+        older_than: Option<Duration>,
+
+        /// Show what would be purged without deleting anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// List past runs with totals and the artifacts that grow back fastest
+    History {
+        /// Only include runs from this long ago or later, e.g. `30d`
+        #[arg(long, value_name = "AGE", value_parser = age::parse_duration)]
+        since: Option<Duration>,
+
+        /// How many of the fastest-regrowing paths to show
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        top: usize,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
+    match args.command {
+        Some(Command::Restore { ref target }) => restore(target.as_deref()),
+        Some(Command::Purge { older_than, dry_run }) => purge(older_than, dry_run),
+        Some(Command::History { since, top }) => history(since, top),
+        None => clean(args),
+    }
+}
+
+/// Finds and cleans artifacts under `args.path`
+fn clean(args: Cli) -> Result<()> {
+    let registry = Registry::builtin();
+    let selected = selected_kinds(&args, &registry)?;
+    let mut target_parts = TargetParts {
+        profiles: args.profile.clone(),
+        triples: args.triple.clone(),
+        parts: args.target_part.iter().copied().collect(),
+        preserve: args.preserve.clone(),
+        sweep: None,
+        keep_final_artifacts: args.keep_executables,
+        keep: Vec::new(),
+    };
+    let human = args.format == OutputFormat::Human;
+
+    // Machine-readable output must not be interleaved with a prompt
+    if !human && !args.dry_run && !args.no_confirm {
+        bail!("Machine-readable output cannot prompt for confirmation; pass --no-confirm or --dry-run");
+    }
+    if args.interactive {
+        if !human {
+            bail!("--interactive only works with --format human");
+        }
+        if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+            bail!("--interactive needs a terminal");
+        }
+    }
+
+    // Keep earlier logs within the retention policy before adding another
+    let retention = Retention {
+        keep_last: args.log_keep,
+        max_age: args.log_max_age,
+        max_total_size: args.log_max_size,
+        compress: args.log_compress,
+    };
+    if !retention.is_empty() {
+        let pruned = retention.enforce(&logging::default_log_dir()?, SystemTime::now())?;
+        if args.verbose && human && (!pruned.removed.is_empty() || !pruned.compressed.is_empty()) {
+            println!(
+                "{} removed {} and compressed {} old {}",
+                "Log retention:".dimmed(),
+                pruned.removed.len(),
+                pruned.compressed.len(),
+                if pruned.removed.len() + pruned.compressed.len() == 1 { "log" } else { "logs" }
+            );
+        }
+    }
 
     // Initialize logger
-    let mut logger = Logger::new(args.log_file)?;
+    let mut logger = if args.dry_run && args.no_dry_run_log {
+        Logger::disabled(args.dry_run)
+    } else {
+        Logger::new(args.log_file.clone(), args.dry_run, args.log_format)?
+    };
 
-    println!("{}", "🛢️  WD-40 - Project Artifact Cleaner".bold().cyan());
-    println!();
+    if human {
+        println!("{}", "🛢️  WD-40 - Project Artifact Cleaner".bold().cyan());
+        println!();
+    }
 
     // Canonicalize the path
     let root_path = args
@@ -91,7 +358,36 @@ fn main() -> Result<()> {
         .canonicalize()
         .unwrap_or_else(|_| args.path.clone());
 
-    if args.verbose {
+    logger.log_run_start(&root_path, &selected)?;
+
+    // Quarantined artifacts are grouped by the id of the run that moved them
+    let quarantine = if args.quarantine && !args.dry_run {
+        let quarantine = QuarantineStore::default_location()?.begin(logger.run_id())?;
+        logger.log_quarantine_run()?;
+        Some(quarantine)
+    } else {
+        None
+    };
+    let removal = match &quarantine {
+        _ if args.dry_run => cleaner::Removal::DryRun,
+        Some(quarantine) => cleaner::Removal::Quarantine(quarantine),
+        None => cleaner::Removal::Delete,
+    };
+
+    // Telling toolchains apart takes a build with each one, so it's done once up front
+    if args.sweep_older_than.is_some() || args.sweep_toolchains {
+        let installed = if args.sweep_toolchains {
+            Some(sweep::installed_rustc_hashes()?)
+        } else {
+            None
+        };
+        target_parts.sweep = Some(Sweep {
+            older_than: args.sweep_older_than,
+            installed,
+        });
+    }
+
+    if args.verbose && human {
         println!(
             "{} {}",
             "Searching for project artifacts in:".cyan(),
@@ -99,247 +395,173 @@ fn main() -> Result<()> {
         );
     }
 
-    // Find all artifacts (Rust, Node.js, Python)
-    let discovered = walker::find_all_rust_artifacts(&root_path)?;
+    let mut reporter = Reporter::new(args.format, std::io::stdout(), &root_path, args.dry_run)?;
+
+    // Find all artifacts known to the registry
+    let mut discovered = walker::find_artifacts(&root_path, &registry)?;
+    let excluded = std::mem::take(&mut discovered.excluded);
+    reporter.excluded(&excluded)?;
 
     // Decide what to process based on flags
-    let (projects_to_clean, orphaned_to_clean, node_modules_to_clean, venvs_to_clean, sccache_to_clean, stack_work_to_clean, rustup_to_clean, next_to_clean, cargo_nix_to_clean) =
-        if args.orphaned_only {
-            // Only clean orphaned Rust targets
-            (Vec::new(), discovered.orphaned_targets, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
-        } else if args.rust_only {
-            // Only clean Rust artifacts
-            (discovered.projects, discovered.orphaned_targets, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
-        } else if args.node_only {
-            // Only clean Node.js artifacts
-            (Vec::new(), Vec::new(), discovered.node_modules, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
-        } else if args.python_only {
-            // Only clean Python artifacts
-            (Vec::new(), Vec::new(), Vec::new(), discovered.python_venvs, Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new())
-        } else if args.haskell_only {
-            // Only clean Haskell Stack artifacts
-            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), discovered.stack_work_dirs, Vec::new(), Vec::new(), Vec::new())
-        } else if args.rustup_only {
-            // Only clean rustup directories
-            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), discovered.rustup_dirs, Vec::new(), Vec::new())
-        } else if args.next_only {
-            // Only clean Next.js build directories
-            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), discovered.next_dirs, Vec::new())
-        } else if args.cargo_nix_only {
-            // Only clean cargo-nix directories
-            (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), discovered.cargo_nix_dirs)
-        } else {
-            // Clean everything by default
-            (discovered.projects, discovered.orphaned_targets, discovered.node_modules, discovered.python_venvs, discovered.sccache_dirs, discovered.stack_work_dirs, discovered.rustup_dirs, discovered.next_dirs, discovered.cargo_nix_dirs)
+    discovered.select(&selected);
+
+    // Candidates that only half pass validation are reported as skipped, never cleaned
+    let suspicious: Vec<SuspiciousPath> = std::mem::take(&mut discovered.suspicious)
+        .into_iter()
+        .filter(|entry| selected.contains(&entry.kind))
+        .collect();
+    for entry in &suspicious {
+        let Some(detector) = registry.get(entry.kind) else {
+            continue;
         };
-
-    if projects_to_clean.is_empty()
-        && orphaned_to_clean.is_empty()
-        && node_modules_to_clean.is_empty()
-        && venvs_to_clean.is_empty()
-        && sccache_to_clean.is_empty()
-        && stack_work_to_clean.is_empty()
-        && rustup_to_clean.is_empty()
-        && next_to_clean.is_empty()
-        && cargo_nix_to_clean.is_empty()
-    {
-        println!("{}", "No artifacts found.".yellow());
-        logger.log_found_projects(0, &[])?;
-        return Ok(());
+        logger.log_artifact_skipped(entry.kind, &entry.path.display().to_string(), &entry.reason)?;
+        let markers = detector.matched_markers(&entry.path);
+        reporter.record(ArtifactRecord::skipped_artifact(detector, &entry.path, markers, entry.reason.clone()))?;
     }
 
-    // Show what was found
-    if !projects_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            projects_to_clean.len(),
-            if projects_to_clean.len() == 1 {
-                "Rust project"
-            } else {
-                "Rust projects"
-            }
-        );
-        if args.verbose {
-            for project in &projects_to_clean {
-                println!("  {}", project.display());
+    // The configured sccache cache is found wherever it is, but one outside the searched
+    // directory is only ever trimmed to --sccache-max-size, never deleted whole
+    if args.sccache_max_size.is_none() {
+        if let Some(detector) = registry.get(ArtifactKind::SCCACHE) {
+            let outside: Vec<PathBuf> = discovered
+                .iter()
+                .filter(|(kind, path)| *kind == ArtifactKind::SCCACHE && !path.starts_with(&root_path))
+                .map(|(_, path)| path.to_path_buf())
+                .collect();
+            for path in &outside {
+                if human {
+                    println!("{} {} - {}", "⊘".yellow(), path.display(), OUTSIDE_SCCACHE_REASON);
+                }
+                logger.log_artifact_skipped(detector.kind, &path.display().to_string(), OUTSIDE_SCCACHE_REASON)?;
+                let markers = detector.matched_markers(path);
+                reporter.record(ArtifactRecord::skipped_artifact(detector, path, markers, OUTSIDE_SCCACHE_REASON.to_string()))?;
             }
+            discovered.retain(|kind, path| kind != ArtifactKind::SCCACHE || !outside.iter().any(|p| p == path));
         }
     }
 
-    if !orphaned_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".yellow(),
-            orphaned_to_clean.len(),
-            if orphaned_to_clean.len() == 1 {
-                "orphaned target directory"
-            } else {
-                "orphaned target directories"
-            }
-        );
-        if args.verbose || args.orphaned_only {
-            for orphaned in &orphaned_to_clean {
-                println!("  {}", orphaned.display());
-            }
+    // Leave anything used recently alone
+    if let Some(min_age) = args.older_than {
+        let filter = AgeFilter::new(min_age, args.age_source, args.age_sample, &registry);
+        let decisions = filter.apply(&mut discovered);
+        if human {
+            print_age_decisions(&decisions, min_age, args.verbose);
         }
+        logger.log_age_decisions(&decisions, min_age)?;
     }
 
-    if !node_modules_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            node_modules_to_clean.len(),
-            if node_modules_to_clean.len() == 1 {
-                "node_modules directory"
-            } else {
-                "node_modules directories"
-            }
-        );
-        if args.verbose {
-            for nm in &node_modules_to_clean {
-                println!("  {}", nm.display());
-            }
+    // Sizing items up front and cleaning them later validate and remove them the same way
+    let validation = Validation {
+        validator: args.validator,
+        timeout: args.validate_timeout,
+    };
+    let needs_validation = !args.force || target_parts.keep_final_artifacts;
+    let toolchain_files = std::mem::take(&mut discovered.toolchain_files);
+
+    // Rustup directories are only pruned of unused toolchains and downloads unless --whole-rustup,
+    // and what goes from inside one is listed with -v; --sccache-max-size trims sccache caches
+    // and --next-cache-only empties .next/cache
+    let clean_artifact = |detector: &Detector, path: &Path, removal: cleaner::Removal| -> Removed {
+        if detector.kind == ArtifactKind::RUSTUP && !args.whole_rustup {
+            return cleaner::prune_rustup_dir(path, &toolchain_files, removal, args.force);
         }
-    }
-
-    if !venvs_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            venvs_to_clean.len(),
-            if venvs_to_clean.len() == 1 {
-                "Python virtual environment"
-            } else {
-                "Python virtual environments"
-            }
-        );
-        if args.verbose {
-            for venv in &venvs_to_clean {
-                println!("  {}", venv.display());
-            }
+        if detector.kind == ArtifactKind::NEXT && args.next_cache_only {
+            return cleaner::clean_next_cache(path, removal, args.force);
         }
-    }
-
-    if !sccache_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            sccache_to_clean.len(),
-            if sccache_to_clean.len() == 1 {
-                "sccache directory"
-            } else {
-                "sccache directories"
-            }
-        );
-        if args.verbose {
-            for sccache in &sccache_to_clean {
-                println!("  {}", sccache.display());
+        let size = match args.sccache_max_size {
+            Some(max_size) if detector.kind == ArtifactKind::SCCACHE => {
+                cleaner::trim_sccache_dir(path, max_size, removal, args.force)?
             }
+            _ => cleaner::remove_artifact_parts(detector, path, &target_parts, removal, args.force)?,
+        };
+        Ok(size.map(|size| vec![(path.to_path_buf(), size)]))
+    };
+
+    // Space goals and the picker size each item by what cleaning it would actually remove
+    // (just the selected profiles, say), which is what a dry run of the same clean reports
+    let estimate = |kind: ArtifactKind, path: &Path, members: &[PathBuf]| -> u64 {
+        if kind == ArtifactKind::RUST_PROJECT {
+            let validated = needs_validation.then(|| validation.validate(path, members));
+            let dry_run = cleaner::clean_validated(path, members, validated, &target_parts, cleaner::Removal::DryRun, args.force, args.strict);
+            return dry_run.ok().and_then(|result| result.space_freed()).unwrap_or(0);
         }
-    }
-
-    if !stack_work_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            stack_work_to_clean.len(),
-            if stack_work_to_clean.len() == 1 {
-                "Stack work directory"
-            } else {
-                "Stack work directories"
-            }
-        );
-        if args.verbose {
-            for stack_work in &stack_work_to_clean {
-                println!("  {}", stack_work.display());
-            }
+        registry
+            .get(kind)
+            .and_then(|detector| clean_artifact(detector, path, cleaner::Removal::DryRun).ok().flatten())
+            .map(|removed| removed.iter().map(|(_, size)| size).sum())
+            .unwrap_or(0)
+    };
+
+    // With a space goal, clean only as much as needed, least recently used and largest first
+    let goal = args
+        .target_free
+        .map(Goal::TargetFree)
+        .or(args.free_at_least.map(Goal::FreeAtLeast));
+    let mut space_goal = None;
+    if let Some(goal) = goal {
+        let ages = AgeFilter::new(Duration::ZERO, args.age_source, args.age_sample, &registry);
+        let available = budget::available_space(&root_path)?;
+        let plan = budget::plan(budget::candidates(&discovered, &ages, estimate), goal, available);
+        if human {
+            print_budget_plan(&plan, goal, args.verbose);
         }
+        logger.log_budget_plan(&plan)?;
+        budget::apply(&plan, &mut discovered);
+        space_goal = Some(available + plan.needed);
     }
 
-    if !rustup_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            rustup_to_clean.len(),
-            if rustup_to_clean.len() == 1 {
-                "rustup directory"
-            } else {
-                "rustup directories"
-            }
-        );
-        if args.verbose {
-            for rustup in &rustup_to_clean {
-                println!("  {}", rustup.display());
-            }
+    // Let the user narrow down what is left; the cleaner still re-validates everything picked
+    if args.interactive && discovered.iter().next().is_some() {
+        let ages = AgeFilter::new(Duration::ZERO, args.age_source, args.age_sample, &registry);
+        let Some(chosen) = interactive::select(budget::candidates(&discovered, &ages, estimate))? else {
+            println!("{}", "Aborted.".red());
+            return Ok(());
+        };
+        if chosen.is_empty() {
+            println!("{}", "Nothing selected.".yellow());
+            return Ok(());
         }
+        discovered.retain(|kind, path| chosen.iter().any(|(k, p)| *k == kind && p == path));
     }
 
-    if !next_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            next_to_clean.len(),
-            if next_to_clean.len() == 1 {
-                "Next.js build directory"
-            } else {
-                "Next.js build directories"
-            }
-        );
-        if args.verbose {
-            for next in &next_to_clean {
-                println!("  {}", next.display());
-            }
+    let projects_to_clean = std::mem::take(&mut discovered.projects);
+    let workspaces = std::mem::take(&mut discovered.workspaces);
+    let artifacts_to_clean: Vec<(&Detector, Vec<PathBuf>)> = registry
+        .detectors()
+        .iter()
+        .map(|detector| (detector, discovered.take(detector.kind)))
+        .filter(|(_, paths)| !paths.is_empty())
+        .collect();
+
+    if projects_to_clean.is_empty() && artifacts_to_clean.is_empty() {
+        if human {
+            println!("{}", "No artifacts found.".yellow());
+            print_exclusions(&excluded, args.verbose);
+            print_suspicious(&suspicious);
         }
+        logger.log_found_projects(&[], &workspaces)?;
+        logger.log_excluded(&excluded)?;
+        // Runs that found nothing are listed in the history too
+        logger.log_summary(&[], &[], 0)?;
+        return reporter.finish();
     }
 
-    if !cargo_nix_to_clean.is_empty() {
-        println!(
-            "{} {} {}",
-            "Found".green(),
-            cargo_nix_to_clean.len(),
-            if cargo_nix_to_clean.len() == 1 {
-                "cargo-nix directory"
-            } else {
-                "cargo-nix directories"
-            }
-        );
-        if args.verbose {
-            for cargo_nix in &cargo_nix_to_clean {
-                println!("  {}", cargo_nix.display());
-            }
-        }
+    // Show what was found
+    if human {
+        print_found(&projects_to_clean, &workspaces, &artifacts_to_clean, args.verbose || args.orphaned_only || args.orphaned_node_modules_only);
+        print_exclusions(&excluded, args.verbose);
+        print_suspicious(&suspicious);
     }
 
     // Log found artifacts
-    logger.log_found_projects(projects_to_clean.len(), &projects_to_clean)?;
-    if !orphaned_to_clean.is_empty() {
-        logger.log_found_orphaned(orphaned_to_clean.len(), &orphaned_to_clean)?;
-    }
-    if !node_modules_to_clean.is_empty() {
-        logger.log_found_node_modules(node_modules_to_clean.len(), &node_modules_to_clean)?;
-    }
-    if !venvs_to_clean.is_empty() {
-        logger.log_found_venvs(venvs_to_clean.len(), &venvs_to_clean)?;
-    }
-    if !sccache_to_clean.is_empty() {
-        logger.log_found_sccache(sccache_to_clean.len(), &sccache_to_clean)?;
-    }
-    if !stack_work_to_clean.is_empty() {
-        logger.log_found_stack_work(stack_work_to_clean.len(), &stack_work_to_clean)?;
-    }
-    if !rustup_to_clean.is_empty() {
-        logger.log_found_rustup(rustup_to_clean.len(), &rustup_to_clean)?;
-    }
-    if !next_to_clean.is_empty() {
-        logger.log_found_next(next_to_clean.len(), &next_to_clean)?;
-    }
-    if !cargo_nix_to_clean.is_empty() {
-        logger.log_found_cargo_nix(cargo_nix_to_clean.len(), &cargo_nix_to_clean)?;
+    logger.log_found_projects(&projects_to_clean, &workspaces)?;
+    for (detector, paths) in &artifacts_to_clean {
+        logger.log_found(detector, paths)?;
     }
+    logger.log_excluded(&excluded)?;
 
-    // Ask for confirmation unless --no-confirm is set
-    if !args.no_confirm && !args.dry_run {
+    // Ask for confirmation unless --no-confirm is set or the selection was already confirmed
+    if !args.no_confirm && !args.dry_run && !args.interactive {
         println!("\n{}", "Proceed with cleaning? (y/N)".yellow());
         let mut input = String::new();
         std::io::stdin().read_line(&mut input)?;
@@ -349,24 +571,64 @@ fn main() -> Result<()> {
         }
     }
 
-    println!(); // Empty line for better readability
+    if human {
+        println!(); // Empty line for better readability
+    }
 
     logger.log_cleaning_start()?;
 
     // Clean each project
     let mut results = Vec::new();
     let mut total_space_freed = 0u64;
-    let mut orphaned_cleaned = 0usize;
-    let mut node_modules_cleaned = 0usize;
-    let mut venvs_cleaned = 0usize;
-    let mut sccache_cleaned = 0usize;
-    let mut stack_work_cleaned = 0usize;
-    let mut rustup_cleaned = 0usize;
-    let mut next_cleaned = 0usize;
-    let mut cargo_nix_cleaned = 0usize;
-
-    for project in &projects_to_clean {
-        let result = cleaner::clean_project(project, args.dry_run, args.verbose, args.force, args.strict)?;
+
+    // Validate every project up front, several at once; cleaning then goes one project at a time
+    let members_of = |project: &PathBuf| workspaces.get(project).map(Vec::as_slice).unwrap_or(&[]);
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4));
+    let validated = cleaner::parallel_map_with(jobs, &projects_to_clean, |project| {
+        needs_validation.then(|| validation.validate(project, members_of(project)))
+    });
+
+    // Nothing a build, a running program or an open file still uses is removed, --force or not
+    let mut in_use = InUse::scan(args.in_use_window);
+    let wait_in_use = args.wait_in_use.unwrap_or_default();
+    // A dry run only reports what is in use; waiting for it to be released is for real removals
+    let mut usage_of = |path: &Path| {
+        if args.dry_run { in_use.check(path) } else { in_use.wait(path, wait_in_use) }
+    };
+
+    // The plan's sizes are estimates, so free space is re-read before each removal and cleaning
+    // stops once the goal is met; quarantining frees nothing until a purge, so it cleans the plan
+    let goal_reached = || {
+        space_goal.filter(|_| matches!(removal, cleaner::Removal::Delete)).is_some_and(|goal| {
+            budget::available_space(&root_path).is_ok_and(|available| available >= goal)
+        })
+    };
+    let mut left_for_goal = 0usize;
+
+    for (project, validated) in projects_to_clean.iter().zip(validated) {
+        let members = members_of(project);
+        let outcome = if goal_reached() {
+            left_for_goal += 1;
+            Ok(cleaner::CleanResult::new(project, members, cleaner::CleanStatus::Skipped(GOAL_REACHED_REASON.to_string())))
+        } else {
+            let busy = cleaner::project_target_dirs(project, members)
+                .iter()
+                .find_map(|target| usage_of(target));
+            match busy {
+                Some(usage) => Ok(cleaner::CleanResult::new(project, members, cleaner::CleanStatus::Refused(usage.reason()))),
+                None => cleaner::clean_validated(project, members, validated, &target_parts, removal, args.force, args.strict),
+            }
+        };
+        // A project that errors out is reported as failed rather than aborting the run
+        let result = outcome.unwrap_or_else(|e| {
+            cleaner::CleanResult::new(project, members, cleaner::CleanStatus::Failed(format!("{:#}", e)))
+        });
+
+        if human {
+            print_project_result(&result, args.dry_run, args.verbose);
+        }
 
         // Log the result
         match &result.status {
@@ -383,188 +645,157 @@ fn main() -> Result<()> {
             cleaner::CleanStatus::Skipped(reason) => {
                 logger.log_skipped(&result.project_path, reason)?;
             }
+            cleaner::CleanStatus::Refused(reason) => {
+                logger.log_refused(&result.project_path, reason)?;
+            }
             cleaner::CleanStatus::Failed(error) => {
                 logger.log_failed(&result.project_path, error)?;
             }
         }
 
+        reporter.record(ArtifactRecord::for_project(&result))?;
         results.push(result);
     }
 
-    // Clean orphaned target directories
-    for orphaned in &orphaned_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN ORPHANED]".yellow(), orphaned.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(orphaned).unwrap_or(0);
-            match cleaner::delete_orphaned_target_dir(orphaned, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {} (orphaned)", "⊗".cyan(), orphaned.display());
-                    logger.log_orphaned_cleaned(&orphaned.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    orphaned_cleaned += 1;
-                }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), orphaned.display());
-                    }
+    // Clean every other artifact kind through its detector
+    let mut cleaned_counts: Vec<(&Detector, usize, u64)> = Vec::new();
+
+    // A dry run sizes every candidate up front, in parallel; delete_artifact still
+    // re-validates each one so the estimate only covers what would really be deleted
+    let mut estimates: HashMap<&Path, Removed> = if args.dry_run {
+        let candidates: Vec<(&Detector, &Path)> = artifacts_to_clean
+            .iter()
+            .flat_map(|(detector, paths)| paths.iter().map(move |path| (*detector, path.as_path())))
+            .collect();
+        let sizes = cleaner::parallel_map(&candidates, |(detector, path)| {
+            clean_artifact(detector, path, cleaner::Removal::DryRun)
+        });
+        candidates.iter().map(|(_, path)| *path).zip(sizes).collect()
+    } else {
+        HashMap::new()
+    };
+    let mut estimated: Vec<(&Path, u64)> = Vec::new();
+
+    for (detector, paths) in &artifacts_to_clean {
+        let mut cleaned = 0usize;
+        let mut kind_space = 0u64;
+
+        for path in paths {
+            // Markers are gone once the directory is deleted
+            let markers = detector.matched_markers(path);
+
+            // Once a space goal is met, whatever it chose but no longer needs is left alone
+            if goal_reached() {
+                left_for_goal += 1;
+                if human {
+                    println!("{} {} - {}", "⊘".yellow(), path.display(), GOAL_REACHED_REASON);
                 }
+                logger.log_artifact_skipped(detector.kind, &path.display().to_string(), GOAL_REACHED_REASON)?;
+                reporter.record(ArtifactRecord::skipped_artifact(detector, path, markers, GOAL_REACHED_REASON.to_string()))?;
+                continue;
             }
-        }
-    }
 
-    // Clean node_modules directories
-    for node_modules in &node_modules_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN NODE_MODULES]".yellow(), node_modules.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(node_modules).unwrap_or(0);
-            match cleaner::delete_node_modules_dir(node_modules, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "📦".cyan(), node_modules.display());
-                    logger.log_node_modules_cleaned(&node_modules.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    node_modules_cleaned += 1;
-                }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), node_modules.display());
+            // Artifacts in use, or holding files tracked by git, are refused rather than failed
+            let mut pruned: Vec<(PathBuf, u64)> = Vec::new();
+            let (outcome, refused) = match usage_of(path) {
+                Some(usage) => (Ok(None), Some(usage.reason())),
+                None => {
+                    let removed = if args.dry_run {
+                        estimates.remove(path.as_path()).unwrap_or(Ok(None))
+                    } else {
+                        clean_artifact(detector, path, removal)
+                    };
+                    if let Ok(Some(removed)) = &removed {
+                        pruned = removed.iter().filter(|(part, _)| part != path).cloned().collect();
                     }
+                    let outcome = removed.map(|removed| removed.map(|removed| removed.iter().map(|(_, size)| size).sum()));
+                    let tracked = outcome.as_ref().err().and_then(|e| e.downcast_ref::<TrackedFiles>());
+                    let refused = tracked.map(|tracked| tracked.to_string());
+                    (outcome, refused)
                 }
-            }
-        }
-    }
-
-    // Clean Python virtual environments
-    for venv in &venvs_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN VENV]".yellow(), venv.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(venv).unwrap_or(0);
-            match cleaner::delete_venv_dir(venv, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "🐍".cyan(), venv.display());
-                    logger.log_venv_cleaned(&venv.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    venvs_cleaned += 1;
-                }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), venv.display());
-                    }
+            };
+            if let Some(reason) = refused {
+                if human {
+                    println!("{} {} - {}", "⊘".yellow(), path.display(), reason);
                 }
+                logger.log_artifact_refused(detector.kind, &path.display().to_string(), &reason)?;
+                reporter.record(ArtifactRecord::refused_artifact(detector, path, markers, reason))?;
+                continue;
             }
-        }
-    }
 
-    // Clean sccache directories
-    for sccache in &sccache_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN SCCACHE]".yellow(), sccache.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(sccache).unwrap_or(0);
-            match cleaner::delete_sccache_dir(sccache, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "🔧".cyan(), sccache.display());
-                    logger.log_sccache_cleaned(&sccache.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    sccache_cleaned += 1;
+            // The build output a cache-only clean leaves is reported next to the cache
+            let preserved = match &outcome {
+                Ok(Some(_)) if detector.kind == ArtifactKind::NEXT && args.next_cache_only => {
+                    cleaner::next_build_output(path)
                 }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), sccache.display());
+                _ => Vec::new(),
+            };
+
+            match &outcome {
+                Ok(Some(space_freed)) => {
+                    if human && args.dry_run {
+                        println!(
+                            "{} {} ({})",
+                            format!("[DRY RUN {}]", detector.log_tag).yellow(),
+                            path.display(),
+                            human_bytes(*space_freed)
+                        );
+                    } else if human {
+                        println!("{} {}", detector.icon.cyan(), path.display());
+                    }
+                    if human && args.verbose {
+                        for (part, bytes) in &pruned {
+                            println!("  {:>10}  {}", human_bytes(*bytes), part.display());
+                        }
+                    }
+                    if human && !preserved.is_empty() {
+                        let kept: u64 = preserved.iter().map(|(_, bytes)| bytes).sum();
+                        println!("  {} build output ({})", "kept".green(), human_bytes(kept));
+                    }
+                    logger.log_artifact_cleaned(detector, &path.display().to_string(), *space_freed)?;
+                    if args.dry_run {
+                        estimated.push((path, *space_freed));
                     }
-                }
-            }
-        }
-    }
-
-    // Clean Stack work directories
-    for stack_work in &stack_work_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN STACK-WORK]".yellow(), stack_work.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(stack_work).unwrap_or(0);
-            match cleaner::delete_stack_work_dir(stack_work, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "λ".cyan(), stack_work.display());
-                    logger.log_stack_work_cleaned(&stack_work.display().to_string(), space_freed)?;
                     total_space_freed += space_freed;
-                    stack_work_cleaned += 1;
+                    kind_space += space_freed;
+                    cleaned += 1;
                 }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), stack_work.display());
+                Ok(None) => {
+                    if args.verbose && human {
+                        println!("{} {} (skipped)", "⊘".yellow(), path.display());
                     }
+                    logger.log_artifact_skipped(detector.kind, &path.display().to_string(), SKIPPED_ARTIFACT_REASON)?;
                 }
-            }
-        }
-    }
-
-    // Clean rustup directories
-    for rustup in &rustup_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN RUSTUP]".yellow(), rustup.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(rustup).unwrap_or(0);
-            match cleaner::delete_rustup_dir(rustup, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "🦀".cyan(), rustup.display());
-                    logger.log_rustup_cleaned(&rustup.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    rustup_cleaned += 1;
-                }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), rustup.display());
+                Err(e) => {
+                    if args.verbose && human {
+                        println!("{} {} (failed to delete)", "✗".red(), path.display());
                     }
+                    logger.log_artifact_failed(detector.kind, &path.display().to_string(), &format!("{:#}", e))?;
                 }
             }
+
+            let mut record = ArtifactRecord::for_artifact(detector, path, markers, &outcome);
+            record.preserved = preserved.iter().map(PreservedFile::from).collect();
+            reporter.record(record)?;
         }
+
+        cleaned_counts.push((detector, cleaned, kind_space));
     }
 
-    // Clean Next.js build directories
-    for next in &next_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN NEXT]".yellow(), next.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(next).unwrap_or(0);
-            match cleaner::delete_next_dir(next, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "▲".cyan(), next.display());
-                    logger.log_next_cleaned(&next.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    next_cleaned += 1;
-                }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), next.display());
-                    }
-                }
-            }
-        }
+    // How the space goal turned out on the actual filesystem
+    let goal_outcome = match space_goal.filter(|_| !args.dry_run) {
+        Some(goal) => Some((goal, budget::available_space(&root_path)?)),
+        None => None,
+    };
+    if let Some((goal, available)) = goal_outcome {
+        logger.log_budget_result(goal, available)?;
     }
 
-    // Clean cargo-nix directories
-    for cargo_nix in &cargo_nix_to_clean {
-        if args.dry_run {
-            println!("{} {}", "[DRY RUN CARGO-NIX]".yellow(), cargo_nix.display());
-        } else {
-            let space_freed = cleaner::calculate_dir_size(cargo_nix).unwrap_or(0);
-            match cleaner::delete_cargo_nix_dir(cargo_nix, args.dry_run) {
-                Ok(Some(_)) => {
-                    println!("{} {}", "❄".cyan(), cargo_nix.display());
-                    logger.log_cargo_nix_cleaned(&cargo_nix.display().to_string(), space_freed)?;
-                    total_space_freed += space_freed;
-                    cargo_nix_cleaned += 1;
-                }
-                _ => {
-                    if args.verbose {
-                        println!("{} {} (failed to delete)", "✗".red(), cargo_nix.display());
-                    }
-                }
-            }
-        }
+    // Log summary
+    logger.log_summary(&results, &cleaned_counts, total_space_freed)?;
+
+    if !human {
+        return reporter.finish();
     }
 
     // Print summary
@@ -572,16 +803,57 @@ fn main() -> Result<()> {
     let successful = results.iter().filter(|r| r.is_success()).count();
     let target_only = results.iter().filter(|r| r.is_target_only()).count();
     let skipped = results.iter().filter(|r| r.is_skipped()).count();
+    let refused = results.iter().filter(|r| r.is_refused()).count();
     let failed = results.len() - successful - target_only - skipped;
+    let project_space: u64 = results.iter().filter_map(|r| r.space_freed()).sum();
 
     if args.dry_run {
-        let total_items = results.len() + orphaned_to_clean.len() + node_modules_to_clean.len() + venvs_to_clean.len() + sccache_to_clean.len() + stack_work_to_clean.len() + rustup_to_clean.len() + next_to_clean.len() + cargo_nix_to_clean.len();
+        let total_items = successful
+            + target_only
+            + cleaned_counts.iter().map(|(_, cleaned, _)| cleaned).sum::<usize>();
         println!(
             "{} {} {} would be cleaned",
             "Summary:".bold(),
             total_items,
             if total_items == 1 { "item" } else { "items" }
         );
+
+        if successful + target_only > 0 {
+            println!(
+                "         {} {} ({})",
+                successful + target_only,
+                if successful + target_only == 1 { "Rust project" } else { "Rust projects" },
+                human_bytes(project_space)
+            );
+        }
+
+        for (detector, cleaned, kind_space) in &cleaned_counts {
+            if *cleaned > 0 {
+                println!("         {} {} ({})", cleaned, detector.noun(*cleaned), human_bytes(*kind_space));
+            }
+        }
+
+        println!(
+            "         {} would be freed",
+            human_bytes(total_space_freed).bold().cyan()
+        );
+
+        // Show where most of the space is, to help decide whether cleaning is worth it
+        let mut largest: Vec<(String, u64)> = results
+            .iter()
+            .filter_map(|r| r.space_freed().map(|bytes| (r.project_path.clone(), bytes)))
+            .chain(estimated.iter().map(|(path, bytes)| (path.display().to_string(), *bytes)))
+            .filter(|(_, bytes)| *bytes > 0)
+            .collect();
+        largest.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        if !largest.is_empty() {
+            println!();
+            println!("{}", "Largest items:".bold());
+            for (path, bytes) in largest.iter().take(LARGEST_ITEMS_SHOWN) {
+                println!("  {:>10}  {}", human_bytes(*bytes), path);
+            }
+        }
     } else {
         println!("{}", "Summary:".bold().green());
 
@@ -601,142 +873,537 @@ fn main() -> Result<()> {
             );
         }
 
-        if orphaned_cleaned > 0 {
-            println!(
-                "         {} {} cleaned",
-                orphaned_cleaned,
-                if orphaned_cleaned == 1 { "orphaned target" } else { "orphaned targets" }
-            );
+        for (detector, cleaned, _) in &cleaned_counts {
+            if *cleaned > 0 {
+                println!("         {} {}", cleaned, detector.noun(*cleaned));
+            }
         }
 
-        if node_modules_cleaned > 0 {
+        if let Some(quarantine) = &quarantine {
+            // Nothing is freed until the quarantine is purged
             println!(
-                "         {} {}",
-                node_modules_cleaned,
-                if node_modules_cleaned == 1 { "node_modules" } else { "node_modules" }
+                "         {} moved to quarantine (run {})",
+                human_bytes(total_space_freed).bold().cyan(),
+                quarantine.run_id()
             );
-        }
-
-        if venvs_cleaned > 0 {
             println!(
-                "         {} {}",
-                venvs_cleaned,
-                if venvs_cleaned == 1 { "Python venv" } else { "Python venvs" }
+                "         Undo with `wd-40 restore {}`, free the space with `wd-40 purge`",
+                quarantine.run_id()
+            );
+        } else if total_space_freed > 0 {
+            println!(
+                "         {} total space freed",
+                human_bytes(total_space_freed).bold().cyan()
             );
         }
 
-        if sccache_cleaned > 0 {
+        let projects_left_for_goal = results
+            .iter()
+            .filter(|r| matches!(&r.status, cleaner::CleanStatus::Skipped(reason) if reason == GOAL_REACHED_REASON))
+            .count();
+        // Projects a .wd40ignore file excludes are counted apart, as the walker's exclusions are
+        let projects_excluded = results
+            .iter()
+            .filter(|r| matches!(&r.status, cleaner::CleanStatus::Skipped(reason) if reason.starts_with(cleaner::EXCLUDED_REASON)))
+            .count();
+        if projects_excluded > 0 {
             println!(
-                "         {} {}",
-                sccache_cleaned,
-                if sccache_cleaned == 1 { "sccache directory" } else { "sccache directories" }
+                "         {} {} excluded by .wd40ignore",
+                projects_excluded,
+                if projects_excluded == 1 { "project" } else { "projects" }
             );
         }
-
-        if stack_work_cleaned > 0 {
+        if skipped > refused + projects_left_for_goal + projects_excluded {
+            let no_target = skipped - refused - projects_left_for_goal - projects_excluded;
             println!(
-                "         {} {}",
-                stack_work_cleaned,
-                if stack_work_cleaned == 1 { "Stack work directory" } else { "Stack work directories" }
+                "         {} {} skipped (no target directory)",
+                no_target,
+                if no_target == 1 { "project" } else { "projects" }
             );
         }
 
-        if rustup_cleaned > 0 {
+        if left_for_goal > 0 {
             println!(
-                "         {} {}",
-                rustup_cleaned,
-                if rustup_cleaned == 1 { "rustup directory" } else { "rustup directories" }
+                "         {} {} left alone (space goal reached)",
+                left_for_goal,
+                if left_for_goal == 1 { "item" } else { "items" }
             );
         }
 
-        if next_cleaned > 0 {
+        if refused > 0 {
             println!(
-                "         {} {}",
-                next_cleaned,
-                if next_cleaned == 1 { "Next.js build" } else { "Next.js builds" }
+                "         {} {} skipped (in use or tracked by git)",
+                refused,
+                if refused == 1 { "project" } else { "projects" }
             );
         }
 
-        if cargo_nix_cleaned > 0 {
+        if failed > 0 {
             println!(
-                "         {} {}",
-                cargo_nix_cleaned,
-                if cargo_nix_cleaned == 1 { "cargo-nix directory" } else { "cargo-nix directories" }
+                "         {} {} failed",
+                failed,
+                if failed == 1 { "project" } else { "projects" }
             );
         }
+    }
 
-        if total_space_freed > 0 {
+    // Report how the space goal turned out, and by how much it was missed
+    if let Some((target, available)) = goal_outcome {
+        println!(
+            "         {} available now (goal {})",
+            human_bytes(available).bold(),
+            human_bytes(target)
+        );
+        if available < target && quarantine.is_none() {
             println!(
-                "         {} total space freed",
-                human_bytes(total_space_freed).bold().cyan()
+                "         {}",
+                format!("Space goal missed by {}", human_bytes(target - available)).yellow()
             );
         }
+    }
+
+    // Print log file location
+    println!();
+    if let Some(log_path) = logger.path() {
+        println!("{} {}", "Log file:".dimmed(), log_path.display().to_string().dimmed());
+    }
+
+    reporter.finish()
+}
+
+/// `wd-40 restore`: moves a run or a single artifact out of quarantine, or lists runs
+fn restore(target: Option<&str>) -> Result<()> {
+    let store = QuarantineStore::default_location()?;
 
-        if skipped > 0 {
+    let Some(target) = target else {
+        let manifests = store.manifests()?;
+        if manifests.is_empty() {
+            println!("{}", "Quarantine is empty.".yellow());
+        }
+        for manifest in manifests {
             println!(
-                "         {} {} skipped (no target directory)",
-                skipped,
-                if skipped == 1 { "project" } else { "projects" }
+                "{}  {}  {} {}",
+                manifest.run_id.bold(),
+                manifest.created.format("%Y-%m-%d %H:%M:%S"),
+                manifest.entries.len(),
+                if manifest.entries.len() == 1 { "artifact" } else { "artifacts" }
             );
+            for entry in &manifest.entries {
+                println!("  {}", entry.original.display());
+            }
         }
+        return Ok(());
+    };
 
-        if failed > 0 {
+    let restored = store.restore(target)?;
+    for entry in &restored.entries {
+        println!("{} {}", "↩".green(), entry.original.display());
+    }
+    for (entry, error) in &restored.failed {
+        println!("{} {} - {}", "✗".red(), entry.original.display(), error);
+    }
+    println!(
+        "\n{} {} restored",
+        "Summary:".bold().green(),
+        restored.entries.len()
+    );
+    if !restored.failed.is_empty() {
+        bail!("{} could not be restored and remain quarantined", restored.failed.len());
+    }
+    Ok(())
+}
+
+/// `wd-40 purge`: permanently deletes quarantined runs
+fn purge(older_than: Option<Duration>, dry_run: bool) -> Result<()> {
+    let store = QuarantineStore::default_location()?;
+    let purged = store.purge(older_than, dry_run)?;
+
+    let mut entries = 0;
+    for manifest in &purged {
+        entries += manifest.entries.len();
+        println!(
+            "{} run {} ({} {}, quarantined {})",
+            if dry_run { "[DRY RUN]".yellow() } else { "✓".green() },
+            manifest.run_id,
+            manifest.entries.len(),
+            if manifest.entries.len() == 1 { "artifact" } else { "artifacts" },
+            manifest.created.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+
+    println!(
+        "\n{} {} {} from {} {} {}",
+        "Summary:".bold().green(),
+        entries,
+        if entries == 1 { "artifact" } else { "artifacts" },
+        purged.len(),
+        if purged.len() == 1 { "run" } else { "runs" },
+        if dry_run { "would be purged" } else { "purged" }
+    );
+    Ok(())
+}
+
+/// Lists past runs, totals over them, and the paths that are cleaned again and again
+fn history(since: Option<Duration>, top: usize) -> Result<()> {
+    let history = History::default_location()?;
+    let mut records = history.load()?;
+    if let Some(since) = since {
+        let cutoff = chrono::Local::now() - chrono::Duration::from_std(since)?;
+        records.retain(|record| record.started >= cutoff);
+    }
+
+    if records.is_empty() {
+        println!("{}", "No runs recorded yet.".yellow());
+        return Ok(());
+    }
+
+    let window = since.map(|since| format!(" (last {})", age::format_age(since))).unwrap_or_default();
+    println!("{}{}", "Past runs".bold().cyan(), window);
+    for record in &records {
+        let mode = if record.dry_run {
+            "dry run".yellow()
+        } else if record.quarantine {
+            "quarantine".blue()
+        } else {
+            "clean".green()
+        };
+        let kinds: Vec<String> = record
+            .by_kind()
+            .iter()
+            .map(|(kind, (count, _))| format!("{} {}", kind, count))
+            .collect();
+        println!(
+            "  {}  {:<10}  {}  {} {}, {}{}",
+            record.started.format("%Y-%m-%d %H:%M"),
+            mode,
+            record.root.display(),
+            record.cleaned.len(),
+            if record.cleaned.len() == 1 { "item" } else { "items" },
+            human_bytes(record.size_bytes()),
+            if kinds.is_empty() { String::new() } else { format!(" ({})", kinds.join(", ")) }
+        );
+    }
+
+    // Dry runs only estimate, so they don't count towards what was freed
+    let real: Vec<&RunRecord> = records.iter().filter(|record| !record.dry_run).collect();
+    let mut by_kind: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+    for record in &real {
+        for (kind, (count, bytes)) in record.by_kind() {
+            let totals = by_kind.entry(kind).or_default();
+            totals.0 += count;
+            totals.1 += bytes;
+        }
+    }
+    let items: usize = by_kind.values().map(|(count, _)| count).sum();
+    let bytes: u64 = by_kind.values().map(|(_, bytes)| bytes).sum();
+
+    println!(
+        "\n{} {} {} ({} dry), {} {} cleaned, {} freed",
+        "Totals:".bold().green(),
+        records.len(),
+        if records.len() == 1 { "run" } else { "runs" },
+        records.len() - real.len(),
+        items,
+        if items == 1 { "item" } else { "items" },
+        human_bytes(bytes)
+    );
+    for (kind, (count, bytes)) in &by_kind {
+        println!("  {:<16} {:>5}  {}", kind, count, human_bytes(*bytes));
+    }
+
+    let regrowth = history::regrowth(&records);
+    if !regrowth.is_empty() {
+        println!("\n{}", "Regrowing fastest:".bold());
+        for entry in regrowth.iter().take(top) {
             println!(
-                "         {} {} failed",
-                failed,
-                if failed == 1 { "project" } else { "projects" }
+                "  {}/day  {} (cleaned {} times, last {})",
+                human_bytes(entry.bytes_per_day),
+                entry.path.display(),
+                entry.times_cleaned,
+                entry.last_cleaned.format("%Y-%m-%d")
             );
         }
     }
 
-    // Log summary
-    logger.log_summary(
-        results.len(),
-        successful,
-        target_only,
-        skipped,
-        failed,
-        orphaned_cleaned,
-        node_modules_cleaned,
-        venvs_cleaned,
-        sccache_cleaned,
-        stack_work_cleaned,
-        rustup_cleaned,
-        next_cleaned,
-        cargo_nix_cleaned,
-        total_space_freed,
-    )?;
+    println!("\n{} {}", "History file:".dimmed(), history.path().display().to_string().dimmed());
+    Ok(())
+}
+
+/// Works out which artifact kinds to clean from `--only` and the `--*-only` shortcuts
+///
+/// With no selection flags every kind is cleaned; several flags combine.
+fn selected_kinds(args: &Cli, registry: &Registry) -> Result<Vec<ArtifactKind>> {
+    let mut kinds = Vec::new();
+
+    for id in &args.only {
+        if id == ArtifactKind::RUST_PROJECT.id() {
+            kinds.push(ArtifactKind::RUST_PROJECT);
+        } else if let Some(detector) = registry.find_by_id(id) {
+            kinds.push(detector.kind);
+        } else {
+            let known: Vec<&str> = std::iter::once(ArtifactKind::RUST_PROJECT.id())
+                .chain(registry.detectors().iter().map(|d| d.kind.id()))
+                .collect();
+            bail!("Unknown artifact kind '{}' (expected one of: {})", id, known.join(", "));
+        }
+    }
+
+    let shortcuts: [(bool, &[ArtifactKind]); 9] = [
+        (args.orphaned_only, &[ArtifactKind::ORPHANED_TARGET]),
+        (args.rust_only, &[ArtifactKind::RUST_PROJECT, ArtifactKind::ORPHANED_TARGET, ArtifactKind::SHARED_TARGET]),
+        (args.node_only, &[ArtifactKind::NODE_MODULES, ArtifactKind::ORPHANED_NODE_MODULES]),
+        (args.orphaned_node_modules_only, &[ArtifactKind::ORPHANED_NODE_MODULES]),
+        (args.python_only, &[ArtifactKind::PYTHON_VENV]),
+        (args.haskell_only, &[ArtifactKind::STACK_WORK]),
+        (args.rustup_only, &[ArtifactKind::RUSTUP]),
+        (args.next_only, &[ArtifactKind::NEXT]),
+        (args.cargo_nix_only, &[ArtifactKind::CARGO_NIX]),
+    ];
+    for (enabled, shortcut_kinds) in shortcuts {
+        if enabled {
+            kinds.extend_from_slice(shortcut_kinds);
+        }
+    }
+
+    // Clean everything by default
+    if kinds.is_empty() {
+        kinds.push(ArtifactKind::RUST_PROJECT);
+        kinds.extend(registry.detectors().iter().map(|d| d.kind));
+    }
+
+    Ok(kinds)
+}
+
+/// Reports which items --older-than kept back, listing every decision when verbose
+fn print_age_decisions(decisions: &[AgeDecision], min_age: Duration, verbose: bool) {
+    if verbose {
+        for decision in decisions {
+            let path = decision.path.display();
+            match decision.age {
+                Some(age) if decision.selected => {
+                    println!("{} {} - last used {} ago", "⏱".cyan(), path, age::format_age(age));
+                }
+                Some(age) => println!(
+                    "{} {} - last used {} ago, newer than {}",
+                    "⊘".yellow(),
+                    path,
+                    age::format_age(age),
+                    age::format_age(min_age)
+                ),
+                None => println!("{} {} - last use unknown", "⊘".yellow(), path),
+            }
+        }
+    }
+
+    let recent = decisions.iter().filter(|d| !d.selected).count();
+    if recent > 0 {
+        println!(
+            "{} {} recently used {} (newer than {})",
+            "Skipping".dimmed(),
+            recent,
+            if recent == 1 { "item" } else { "items" },
+            age::format_age(min_age)
+        );
+    }
+}
+
+/// Explains which items a space goal selected, and why, and what it left alone
+fn print_budget_plan(plan: &Plan, goal: Goal, verbose: bool) {
+    match goal {
+        Goal::FreeAtLeast(bytes) => println!(
+            "{} free at least {} ({} available now)",
+            "Space goal:".bold(),
+            human_bytes(bytes),
+            human_bytes(plan.available)
+        ),
+        Goal::TargetFree(bytes) => println!(
+            "{} {} available ({} available now, {} to free)",
+            "Space goal:".bold(),
+            human_bytes(bytes),
+            human_bytes(plan.available),
+            human_bytes(plan.needed)
+        ),
+    }
+
+    if plan.needed == 0 {
+        println!("{}", "Goal already met; nothing needs cleaning.".green());
+        return;
+    }
 
-    // Print log file location
-    println!();
     println!(
-        "{} {}",
-        "Log file:".dimmed(),
-        logger.path().display().to_string().dimmed()
+        "Chose {} of {} {} ({}), least recently used and largest first:",
+        plan.chosen.len(),
+        plan.chosen.len() + plan.left.len(),
+        if plan.chosen.len() + plan.left.len() == 1 { "item" } else { "items" },
+        human_bytes(plan.planned())
     );
+    for candidate in &plan.chosen {
+        println!(
+            "  {:>10}  {:<20}  {}",
+            human_bytes(candidate.size),
+            age::describe_last_use(candidate.last_used),
+            candidate.path.display()
+        );
+    }
 
-    Ok(())
+    if !plan.meets_goal() {
+        println!(
+            "{} cleaning everything frees only about {}, short of {}",
+            "Warning:".yellow().bold(),
+            human_bytes(plan.planned()),
+            human_bytes(plan.needed)
+        );
+    } else if !plan.left.is_empty() {
+        println!(
+            "{} {} {} alone (not needed to reach the goal)",
+            "Leaving".dimmed(),
+            plan.left.len(),
+            if plan.left.len() == 1 { "item" } else { "items" }
+        );
+        if verbose {
+            for candidate in &plan.left {
+                println!(
+                    "  {:>10}  {:<20}  {}",
+                    human_bytes(candidate.size),
+                    age::describe_last_use(candidate.last_used),
+                    candidate.path.display()
+                );
+            }
+        }
+    }
+    println!();
+}
+
+/// Lists how many projects and artifacts of each kind were found
+fn print_found(
+    projects: &[PathBuf],
+    workspaces: &BTreeMap<PathBuf, Vec<PathBuf>>,
+    artifacts: &[(&Detector, Vec<PathBuf>)],
+    list_paths: bool,
+) {
+    if !projects.is_empty() {
+        let members: usize = projects.iter().filter_map(|p| workspaces.get(p)).map(Vec::len).sum();
+        println!(
+            "{} {} {}{}",
+            "Found".green(),
+            projects.len(),
+            if projects.len() == 1 {
+                "Rust project"
+            } else {
+                "Rust projects"
+            },
+            if members > 0 {
+                format!(" ({} workspace {} grouped under their roots)", members, if members == 1 { "member" } else { "members" })
+            } else {
+                String::new()
+            }
+        );
+        if list_paths {
+            for project in projects {
+                println!("  {}{}", project.display(), describe_members(workspaces.get(project)));
+                for member in workspaces.get(project).into_iter().flatten() {
+                    println!("    {}", member.display().to_string().dimmed());
+                }
+            }
+        }
+    }
+
+    for (detector, paths) in artifacts {
+        println!(
+            "{} {} {}",
+            "Found".green(),
+            paths.len(),
+            detector.noun(paths.len())
+        );
+        if list_paths {
+            for path in paths {
+                println!("  {}", path.display());
+            }
+        }
+    }
+}
+
+/// Describes a workspace root's members, e.g. " (workspace, 40 members)"
+fn describe_members(members: Option<&Vec<PathBuf>>) -> String {
+    match members {
+        Some(members) if !members.is_empty() => format!(
+            " (workspace, {} {})",
+            members.len(),
+            if members.len() == 1 { "member" } else { "members" }
+        ),
+        _ => String::new(),
+    }
 }
 
-/// Converts bytes to human-readable format
-fn human_bytes(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+/// Prints the outcome of cleaning (or sizing, in a dry run) one project
+fn print_project_result(result: &cleaner::CleanResult, dry_run: bool, verbose: bool) {
+    let path = &format!("{}{}", result.project_path, describe_members(Some(&result.members)));
+    match &result.status {
+        cleaner::CleanStatus::Success { space_freed } => match (dry_run, space_freed) {
+            (true, Some(bytes)) => println!("{} {} ({})", "[DRY RUN]".yellow(), path, human_bytes(*bytes)),
+            (true, None) => println!("{} {}", "[DRY RUN]".yellow(), path),
+            (false, _) => println!("{} {}", "✓".green(), path),
+        },
+        cleaner::CleanStatus::TargetOnly { space_freed, .. } => {
+            if verbose {
+                println!("{} {} - cleaning target only (invalid project config)", "⊙".yellow(), path);
+            }
+            if dry_run {
+                println!("{} {} (target only, {})", "[DRY RUN]".yellow(), path, human_bytes(*space_freed));
+            } else {
+                println!("{} {} (target only)", "⊙".cyan(), path);
+            }
+        }
+        cleaner::CleanStatus::Skipped(reason) => {
+            if verbose {
+                println!("{} {} - {}", "⊘".yellow(), path, reason);
+            }
+        }
+        // Something still needed is worth knowing about even without -v
+        cleaner::CleanStatus::Refused(reason) => {
+            println!("{} {} - {}", "⊘".yellow(), path, reason);
+        }
+        cleaner::CleanStatus::Failed(error) => {
+            println!("{} {} - {}", "✗".red(), path, error);
+        }
+    }
 
-    if bytes == 0 {
-        return "0 B".to_string();
+    for (file, bytes) in &result.preserved {
+        println!("  {} {} ({})", "kept".green(), file.display(), human_bytes(*bytes));
+    }
+    if verbose {
+        for (part, bytes) in &result.parts {
+            println!("  {:>10}  {}", human_bytes(*bytes), part.display());
+        }
     }
+}
 
-    let mut size = bytes as f64;
-    let mut unit_index = 0;
+/// Lists the candidates left alone for failing their detector's stricter checks, with why
+fn print_suspicious(suspicious: &[SuspiciousPath]) {
+    for entry in suspicious {
+        println!("{} {} - {}", "⚠".yellow(), entry.path.display(), entry.reason.yellow());
+    }
+}
 
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
+/// Reports how many candidates each .wd40ignore file excluded
+fn print_exclusions(excluded: &[ExcludedPath], verbose: bool) {
+    let mut by_file: BTreeMap<&Path, Vec<&Path>> = BTreeMap::new();
+    for entry in excluded {
+        by_file.entry(&entry.ignore_file).or_default().push(&entry.path);
     }
 
-    if unit_index == 0 {
-        format!("{} {}", size as u64, UNITS[unit_index])
-    } else {
-        format!("{:.2} {}", size, UNITS[unit_index])
+    for (ignore_file, paths) in by_file {
+        println!(
+            "{} {} {} via {}",
+            "Excluded".dimmed(),
+            paths.len(),
+            if paths.len() == 1 { "candidate" } else { "candidates" },
+            ignore_file.display()
+        );
+        if verbose {
+            for path in paths {
+                println!("  {}", path.display());
+            }
+        }
     }
 }
@@ -0,0 +1,375 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Name of the quarantine area created on filesystems other than the store's own
+pub const AREA_DIR_NAME: &str = ".wd40-quarantine";
+
+/// One artifact moved into quarantine
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Entry {
+    pub original: PathBuf,
+    pub quarantined: PathBuf,
+}
+
+/// Everything one run moved into quarantine, stored as `<run-id>.json` in the store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub run_id: String,
+    pub created: DateTime<Local>,
+    pub entries: Vec<Entry>,
+}
+
+/// What `QuarantineStore::restore` moved back, and what it had to leave in quarantine
+#[derive(Debug, Default)]
+pub struct Restored {
+    pub entries: Vec<Entry>,
+    /// Entries that stay quarantined, with the reason
+    pub failed: Vec<(Entry, String)>,
+}
+
+/// Where quarantine manifests live (and, for artifacts on the same filesystem, the artifacts too)
+#[derive(Debug, Clone)]
+pub struct QuarantineStore {
+    root: PathBuf,
+}
+
+impl QuarantineStore {
+    pub fn new(root: PathBuf) -> Self {
+        QuarantineStore { root }
+    }
+
+    /// The default store: ~/.cache/wd-40/quarantine
+    pub fn default_location() -> Result<Self> {
+        let root = dirs::cache_dir()
+            .context("Failed to determine cache directory")?
+            .join("wd-40")
+            .join("quarantine");
+        Ok(QuarantineStore::new(root))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Starts recording a run that moves artifacts into quarantine
+    pub fn begin(&self, run_id: &str) -> Result<Quarantine> {
+        fs::create_dir_all(&self.root).with_context(|| {
+            format!(
+                "Failed to create quarantine directory: {}",
+                self.root.display()
+            )
+        })?;
+
+        Ok(Quarantine {
+            store: self.clone(),
+            manifest: Mutex::new(Manifest {
+                run_id: run_id.to_string(),
+                created: Local::now(),
+                entries: Vec::new(),
+            }),
+            areas: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// All runs with something still in quarantine, oldest first
+    pub fn manifests(&self) -> Result<Vec<Manifest>> {
+        let mut manifests = Vec::new();
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(manifests),
+            Err(e) => return Err(e).context("Failed to read quarantine directory"),
+        };
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+                manifests.push(self.load(&path)?);
+            }
+        }
+
+        manifests.sort_by(|a, b| {
+            a.created
+                .cmp(&b.created)
+                .then_with(|| a.run_id.cmp(&b.run_id))
+        });
+        Ok(manifests)
+    }
+
+    /// Moves artifacts back where they came from
+    ///
+    /// `target` is either a run id (restoring the whole run) or the original path of one
+    /// quarantined artifact. Entries that can't be restored stay in quarantine.
+    pub fn restore(&self, target: &str) -> Result<Restored> {
+        let mut restored = Restored::default();
+        let target_path = Path::new(target);
+        let target_path = target_path
+            .canonicalize()
+            .unwrap_or_else(|_| absolute(target_path));
+
+        let mut matched = false;
+        for mut manifest in self.manifests()? {
+            let whole_run = manifest.run_id == target;
+            let (wanted, kept): (Vec<Entry>, Vec<Entry>) = manifest
+                .entries
+                .drain(..)
+                .partition(|entry| whole_run || entry.original == target_path);
+            if wanted.is_empty() {
+                manifest.entries = kept;
+                continue;
+            }
+            matched = true;
+
+            manifest.entries = kept;
+            for entry in wanted {
+                match restore_entry(&entry) {
+                    Ok(()) => restored.entries.push(entry),
+                    Err(e) => {
+                        // Keep it in the manifest so it can still be restored or purged
+                        manifest.entries.push(entry.clone());
+                        restored.failed.push((entry, format!("{:#}", e)));
+                    }
+                }
+            }
+            self.save_or_remove(&manifest)?;
+        }
+
+        if !matched {
+            bail!(
+                "Nothing in quarantine matches '{}' (expected a run id or an original path)",
+                target
+            );
+        }
+        Ok(restored)
+    }
+
+    /// Permanently deletes quarantined runs, optionally only those older than `older_than`
+    ///
+    /// Returns the manifests that were (or, in a dry run, would be) purged.
+    pub fn purge(&self, older_than: Option<Duration>, dry_run: bool) -> Result<Vec<Manifest>> {
+        let now = Local::now();
+        let mut purged = Vec::new();
+
+        for manifest in self.manifests()? {
+            let age = now
+                .signed_duration_since(manifest.created)
+                .to_std()
+                .unwrap_or_default();
+            if older_than.is_some_and(|min_age| age < min_age) {
+                continue;
+            }
+
+            if !dry_run {
+                for entry in &manifest.entries {
+                    if entry.quarantined.is_dir() {
+                        fs::remove_dir_all(&entry.quarantined).with_context(|| {
+                            format!("Failed to purge {}", entry.quarantined.display())
+                        })?;
+                    } else if entry.quarantined.exists() {
+                        fs::remove_file(&entry.quarantined).with_context(|| {
+                            format!("Failed to purge {}", entry.quarantined.display())
+                        })?;
+                    }
+                    remove_empty_run_dir(&entry.quarantined);
+                }
+                fs::remove_file(self.manifest_path(&manifest.run_id)).with_context(|| {
+                    format!("Failed to remove manifest for run {}", manifest.run_id)
+                })?;
+            }
+            purged.push(manifest);
+        }
+
+        Ok(purged)
+    }
+
+    fn manifest_path(&self, run_id: &str) -> PathBuf {
+        self.root.join(format!("{}.json", run_id))
+    }
+
+    fn load(&self, path: &Path) -> Result<Manifest> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read quarantine manifest: {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse quarantine manifest: {}", path.display()))
+    }
+
+    /// Writes the manifest atomically, so a crash never leaves a half-written one behind
+    fn save(&self, manifest: &Manifest) -> Result<()> {
+        let path = self.manifest_path(&manifest.run_id);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_vec_pretty(manifest)?).with_context(|| {
+            format!("Failed to write quarantine manifest: {}", partial.display())
+        })?;
+        fs::rename(&partial, &path)
+            .with_context(|| format!("Failed to write quarantine manifest: {}", path.display()))?;
+        Ok(())
+    }
+
+    fn save_or_remove(&self, manifest: &Manifest) -> Result<()> {
+        if manifest.entries.is_empty() {
+            let path = self.manifest_path(&manifest.run_id);
+            fs::remove_file(&path).with_context(|| {
+                format!("Failed to remove quarantine manifest: {}", path.display())
+            })
+        } else {
+            self.save(manifest)
+        }
+    }
+}
+
+/// A run in progress that moves artifacts into quarantine instead of deleting them
+///
+/// Safe to share between threads; the manifest is rewritten after every move.
+pub struct Quarantine {
+    store: QuarantineStore,
+    manifest: Mutex<Manifest>,
+    /// Quarantine directory for this run, per filesystem
+    areas: Mutex<HashMap<Option<u64>, PathBuf>>,
+}
+
+impl Quarantine {
+    pub fn run_id(&self) -> String {
+        self.manifest
+            .lock()
+            .map(|m| m.run_id.clone())
+            .unwrap_or_default()
+    }
+
+    /// Atomically renames `path` into this run's quarantine area on the same filesystem
+    pub fn quarantine(&self, path: &Path) -> Result<PathBuf> {
+        let area = self.area_for(path)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "artifact".to_string());
+
+        let mut manifest = self
+            .manifest
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Quarantine manifest lock poisoned"))?;
+
+        // Number entries so artifacts with the same name never collide
+        let quarantined = area.join(format!("{}-{}", manifest.entries.len() + 1, name));
+        fs::rename(path, &quarantined).with_context(|| {
+            format!(
+                "Failed to move {} into quarantine at {}",
+                path.display(),
+                quarantined.display()
+            )
+        })?;
+
+        manifest.entries.push(Entry {
+            original: path.to_path_buf(),
+            quarantined: quarantined.clone(),
+        });
+        self.store.save(&manifest)?;
+
+        Ok(quarantined)
+    }
+
+    /// Finds (creating it on first use) this run's quarantine directory on `path`'s filesystem
+    ///
+    /// The store itself is used when it shares the filesystem; otherwise the area goes in the
+    /// highest writable directory above `path` on the same filesystem, so the rename never copies.
+    fn area_for(&self, path: &Path) -> Result<PathBuf> {
+        let device = device_id(path);
+        let mut areas = self
+            .areas
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Quarantine area lock poisoned"))?;
+        if let Some(area) = areas.get(&device) {
+            return Ok(area.clone());
+        }
+
+        let run_id = self.run_id();
+        let mut candidates = vec![self.store.root.clone()];
+        if device.is_some() && device_id(&self.store.root) != device {
+            let same_fs: Vec<&Path> = path
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| device_id(dir) == device)
+                .collect();
+            candidates = same_fs
+                .iter()
+                .rev()
+                .map(|dir| dir.join(AREA_DIR_NAME))
+                .collect();
+        }
+
+        for base in candidates {
+            let area = base.join(&run_id);
+            if fs::create_dir_all(&area).is_ok() {
+                areas.insert(device, area.clone());
+                return Ok(area);
+            }
+        }
+
+        bail!(
+            "No writable quarantine location on the filesystem of {}",
+            path.display()
+        )
+    }
+}
+
+/// Renames one quarantined artifact back to its original path
+fn restore_entry(entry: &Entry) -> Result<()> {
+    if entry.original.exists() {
+        bail!(
+            "Not restoring {}: something already exists there (still quarantined at {})",
+            entry.original.display(),
+            entry.quarantined.display()
+        );
+    }
+    if let Some(parent) = entry.original.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to recreate {}", parent.display()))?;
+    }
+    fs::rename(&entry.quarantined, &entry.original).with_context(|| {
+        format!(
+            "Failed to restore {} to {}",
+            entry.quarantined.display(),
+            entry.original.display()
+        )
+    })?;
+    remove_empty_run_dir(&entry.quarantined);
+    Ok(())
+}
+
+/// Removes a run's quarantine directory (and an emptied per-filesystem area) once its last artifact is gone
+fn remove_empty_run_dir(quarantined: &Path) {
+    // Each removal fails (harmlessly) while something else is still in there
+    if let Some(run_dir) = quarantined.parent() {
+        if fs::remove_dir(run_dir).is_ok() {
+            if let Some(area) = run_dir
+                .parent()
+                .filter(|a| a.file_name().is_some_and(|n| n == AREA_DIR_NAME))
+            {
+                let _ = fs::remove_dir(area);
+            }
+        }
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::symlink_metadata(path)
+        .ok()
+        .map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
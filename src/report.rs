@@ -0,0 +1,454 @@
+use crate::artifacts::{matched_markers, owning_project, ArtifactKind, Detector, PROJECT_MARKERS};
+use crate::cleaner::{CleanResult, CleanStatus};
+use crate::ignore_rules::ExcludedPath;
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Version of the JSON output schema, emitted with every document and line.
+///
+/// Adding fields keeps the version; removing or changing the meaning of one bumps it. Version 2
+/// reports items refused for being in use or tracked by git as `refused`, no longer `skipped`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Why `delete_artifact` left an artifact alone when it returns `Ok(None)`
+pub const SKIPPED_ARTIFACT_REASON: &str = "no longer validates or is excluded by .wd40ignore";
+
+/// How results are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Colored output for people
+    Human,
+    /// A single JSON document once the run completes
+    Json,
+    /// One JSON object per line, streamed as each item is processed
+    #[value(name = "jsonl")]
+    JsonLines,
+}
+
+/// What happened to one project or artifact
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    /// Only the target directory of a project with an invalid configuration was cleaned
+    TargetOnly {
+        reason: String,
+    },
+    Skipped {
+        reason: String,
+    },
+    /// Left alone because it's in use, or holds files tracked by git and `--force` wasn't given
+    Refused {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl From<&CleanStatus> for Outcome {
+    fn from(status: &CleanStatus) -> Self {
+        match status {
+            CleanStatus::Success { .. } => Outcome::Success,
+            CleanStatus::TargetOnly { reason, .. } => Outcome::TargetOnly {
+                reason: reason.clone(),
+            },
+            CleanStatus::Skipped(reason) => Outcome::Skipped {
+                reason: reason.clone(),
+            },
+            CleanStatus::Refused(reason) => Outcome::Refused {
+                reason: reason.clone(),
+            },
+            CleanStatus::Failed(error) => Outcome::Failed {
+                error: error.clone(),
+            },
+        }
+    }
+}
+
+/// One processed project or artifact
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtifactRecord {
+    pub kind: &'static str,
+    pub path: String,
+    /// Directory of the project the artifact belongs to, if it has a recognizable manifest
+    pub project: Option<String>,
+    /// Validation markers that were present
+    pub markers: Vec<&'static str>,
+    /// Members of a workspace root, cleaned along with it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+    /// Files kept in place: final artifacts in a project's target directories, or the build
+    /// output of a Next.js directory whose cache alone was cleaned
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub preserved: Vec<PreservedFile>,
+    /// Bytes freed (or that would be freed in a dry run); null if nothing was deleted
+    pub size_bytes: Option<u64>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+/// A file left in place while the rest of the artifact was cleaned
+#[derive(Debug, Clone, Serialize)]
+pub struct PreservedFile {
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+impl From<&(PathBuf, u64)> for PreservedFile {
+    fn from((path, size_bytes): &(PathBuf, u64)) -> Self {
+        PreservedFile {
+            path: path.display().to_string(),
+            size_bytes: *size_bytes,
+        }
+    }
+}
+
+impl ArtifactRecord {
+    /// Describes a project cleaned by `clean_project`
+    pub fn for_project(result: &CleanResult) -> Self {
+        let path = Path::new(&result.project_path);
+        ArtifactRecord {
+            kind: ArtifactKind::RUST_PROJECT.id(),
+            path: result.project_path.clone(),
+            project: Some(result.project_path.clone()),
+            markers: matched_markers(path, PROJECT_MARKERS),
+            members: result
+                .members
+                .iter()
+                .map(|member| member.display().to_string())
+                .collect(),
+            preserved: result.preserved.iter().map(PreservedFile::from).collect(),
+            size_bytes: result.space_freed(),
+            outcome: Outcome::from(&result.status),
+        }
+    }
+
+    /// Describes an artifact handed to `delete_artifact`
+    ///
+    /// `markers` must be collected before deletion, while they still exist.
+    pub fn for_artifact(
+        detector: &Detector,
+        path: &Path,
+        markers: Vec<&'static str>,
+        deleted: &Result<Option<u64>>,
+    ) -> Self {
+        let (size_bytes, outcome) = match deleted {
+            Ok(Some(bytes)) => (Some(*bytes), Outcome::Success),
+            Ok(None) => (
+                None,
+                Outcome::Skipped {
+                    reason: SKIPPED_ARTIFACT_REASON.to_string(),
+                },
+            ),
+            Err(e) => (
+                None,
+                Outcome::Failed {
+                    error: format!("{:#}", e),
+                },
+            ),
+        };
+
+        ArtifactRecord {
+            kind: detector.kind.id(),
+            path: path.display().to_string(),
+            project: owning_project(path).map(|dir| dir.display().to_string()),
+            markers,
+            members: Vec::new(),
+            preserved: Vec::new(),
+            size_bytes,
+            outcome,
+        }
+    }
+
+    /// Describes an artifact left alone before `delete_artifact` was tried, for `reason`
+    pub fn skipped_artifact(
+        detector: &Detector,
+        path: &Path,
+        markers: Vec<&'static str>,
+        reason: String,
+    ) -> Self {
+        ArtifactRecord {
+            size_bytes: None,
+            outcome: Outcome::Skipped { reason },
+            ..ArtifactRecord::for_artifact(detector, path, markers, &Ok(None))
+        }
+    }
+
+    /// Describes an artifact refused before `delete_artifact` was tried, for `reason`
+    pub fn refused_artifact(
+        detector: &Detector,
+        path: &Path,
+        markers: Vec<&'static str>,
+        reason: String,
+    ) -> Self {
+        ArtifactRecord {
+            outcome: Outcome::Refused { reason },
+            ..ArtifactRecord::skipped_artifact(detector, path, markers, String::new())
+        }
+    }
+}
+
+/// A candidate left alone because of a `.wd40ignore` file
+#[derive(Debug, Clone, Serialize)]
+pub struct ExcludedRecord {
+    pub path: String,
+    pub ignore_file: String,
+}
+
+impl From<&ExcludedPath> for ExcludedRecord {
+    fn from(excluded: &ExcludedPath) -> Self {
+        ExcludedRecord {
+            path: excluded.path.display().to_string(),
+            ignore_file: excluded.ignore_file.display().to_string(),
+        }
+    }
+}
+
+/// Cleaned items and bytes for one kind
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct KindTotals {
+    pub cleaned: usize,
+    pub size_bytes: u64,
+}
+
+/// Final totals over every record of a run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Totals {
+    /// Successful and target-only items
+    pub cleaned: usize,
+    pub skipped: usize,
+    pub refused: usize,
+    pub failed: usize,
+    pub size_bytes: u64,
+    pub by_kind: BTreeMap<&'static str, KindTotals>,
+}
+
+impl Totals {
+    fn add(&mut self, record: &ArtifactRecord) {
+        match record.outcome {
+            Outcome::Success | Outcome::TargetOnly { .. } => {
+                let bytes = record.size_bytes.unwrap_or(0);
+                self.cleaned += 1;
+                self.size_bytes += bytes;
+                let kind = self.by_kind.entry(record.kind).or_default();
+                kind.cleaned += 1;
+                kind.size_bytes += bytes;
+            }
+            Outcome::Skipped { .. } => self.skipped += 1,
+            Outcome::Refused { .. } => self.refused += 1,
+            Outcome::Failed { .. } => self.failed += 1,
+        }
+    }
+}
+
+/// One line of `--format jsonl` output
+#[derive(Serialize)]
+struct Line<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event<'a> {
+    Start { root: &'a str, dry_run: bool },
+    Excluded(&'a ExcludedRecord),
+    Artifact(&'a ArtifactRecord),
+    Summary(&'a Totals),
+}
+
+/// The single document written by `--format json`
+#[derive(Serialize)]
+struct Document<'a> {
+    schema_version: u32,
+    root: &'a str,
+    dry_run: bool,
+    artifacts: &'a [ArtifactRecord],
+    excluded: &'a [ExcludedRecord],
+    totals: &'a Totals,
+}
+
+/// Collects the outcome of a run and writes it in the selected machine-readable format
+///
+/// With `OutputFormat::Human` nothing is written; the CLI prints its own output.
+pub struct Reporter<W: Write> {
+    format: OutputFormat,
+    out: W,
+    root: String,
+    dry_run: bool,
+    artifacts: Vec<ArtifactRecord>,
+    excluded: Vec<ExcludedRecord>,
+    totals: Totals,
+}
+
+impl<W: Write> Reporter<W> {
+    pub fn new(format: OutputFormat, out: W, root: &Path, dry_run: bool) -> Result<Self> {
+        let mut reporter = Reporter {
+            format,
+            out,
+            root: root.display().to_string(),
+            dry_run,
+            artifacts: Vec::new(),
+            excluded: Vec::new(),
+            totals: Totals::default(),
+        };
+        let root = reporter.root.clone();
+        reporter.emit(Event::Start {
+            root: &root,
+            dry_run,
+        })?;
+        Ok(reporter)
+    }
+
+    pub fn excluded(&mut self, excluded: &[ExcludedPath]) -> Result<()> {
+        for entry in excluded {
+            let record = ExcludedRecord::from(entry);
+            self.emit(Event::Excluded(&record))?;
+            self.excluded.push(record);
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, record: ArtifactRecord) -> Result<()> {
+        self.totals.add(&record);
+        self.emit(Event::Artifact(&record))?;
+        self.artifacts.push(record);
+        Ok(())
+    }
+
+    /// Writes the summary line or the whole document
+    pub fn finish(mut self) -> Result<()> {
+        match self.format {
+            OutputFormat::Human => {}
+            OutputFormat::JsonLines => {
+                let totals = std::mem::take(&mut self.totals);
+                self.emit(Event::Summary(&totals))?;
+            }
+            OutputFormat::Json => {
+                let document = Document {
+                    schema_version: SCHEMA_VERSION,
+                    root: &self.root,
+                    dry_run: self.dry_run,
+                    artifacts: &self.artifacts,
+                    excluded: &self.excluded,
+                    totals: &self.totals,
+                };
+                serde_json::to_writer_pretty(&mut self.out, &document)?;
+                writeln!(self.out)?;
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Streams one event when writing JSON lines
+    fn emit(&mut self, event: Event) -> Result<()> {
+        if self.format == OutputFormat::JsonLines {
+            let line = Line {
+                schema_version: SCHEMA_VERSION,
+                event,
+            };
+            serde_json::to_writer(&mut self.out, &line)?;
+            writeln!(self.out)?;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::Registry;
+    use serde_json::Value;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_json_lines_schema() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let node_modules = root.join("web/node_modules");
+        fs::create_dir_all(node_modules.join(".bin")).unwrap();
+        fs::write(root.join("web/package.json"), "{}").unwrap();
+
+        let registry = Registry::builtin();
+        let detector = registry.get(ArtifactKind::NODE_MODULES).unwrap();
+        let project = CleanResult {
+            project_path: root.join("broken").display().to_string(),
+            members: Vec::new(),
+            parts: Vec::new(),
+            preserved: Vec::new(),
+            status: CleanStatus::TargetOnly {
+                space_freed: 10,
+                reason: "invalid manifest".to_string(),
+            },
+        };
+
+        let mut out = Vec::new();
+        let mut reporter = Reporter::new(OutputFormat::JsonLines, &mut out, root, true).unwrap();
+        reporter
+            .record(ArtifactRecord::for_project(&project))
+            .unwrap();
+        let markers = detector.matched_markers(&node_modules);
+        reporter
+            .record(ArtifactRecord::for_artifact(
+                detector,
+                &node_modules,
+                markers.clone(),
+                &Ok(Some(32)),
+            ))
+            .unwrap();
+        let reason = "in use by a running build".to_string();
+        reporter
+            .record(ArtifactRecord::refused_artifact(
+                detector,
+                &node_modules,
+                markers,
+                reason,
+            ))
+            .unwrap();
+        reporter.finish().unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines
+            .iter()
+            .all(|line| line["schema_version"] == SCHEMA_VERSION));
+        assert_eq!(lines[0]["type"], "start");
+        assert_eq!(lines[0]["dry_run"], true);
+
+        assert_eq!(lines[1]["kind"], "rust-project");
+        assert_eq!(lines[1]["status"], "target_only");
+        assert_eq!(lines[1]["reason"], "invalid manifest");
+        assert_eq!(lines[1]["size_bytes"], 10);
+
+        assert_eq!(lines[2]["kind"], "node-modules");
+        assert_eq!(lines[2]["status"], "success");
+        assert_eq!(lines[2]["project"], root.join("web").display().to_string());
+        assert_eq!(
+            lines[2]["markers"],
+            serde_json::json!(["../package.json", ".bin"])
+        );
+
+        assert_eq!(lines[3]["status"], "refused");
+        assert_eq!(lines[3]["reason"], "in use by a running build");
+        assert_eq!(lines[3]["size_bytes"], Value::Null);
+
+        assert_eq!(lines[4]["type"], "summary");
+        assert_eq!(lines[4]["cleaned"], 2);
+        assert_eq!(lines[4]["skipped"], 0);
+        assert_eq!(lines[4]["refused"], 1);
+        assert_eq!(lines[4]["size_bytes"], 42);
+        assert_eq!(lines[4]["by_kind"]["node-modules"]["size_bytes"], 32);
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Which run logs to keep in the log directory
+///
+/// Every limit is optional; a log is removed as soon as it breaks any of them.
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    /// Keep at most this many logs, newest first
+    pub keep_last: Option<usize>,
+    /// Remove logs last written longer ago than this
+    pub max_age: Option<Duration>,
+    /// Keep the newest logs while together they fit in this many bytes
+    pub max_total_size: Option<u64>,
+    /// Gzip the logs that are kept
+    pub compress: bool,
+}
+
+/// What `Retention::enforce` did
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub removed: Vec<PathBuf>,
+    pub compressed: Vec<PathBuf>,
+}
+
+impl Retention {
+    /// Returns true if no policy is set, so there is nothing to enforce
+    pub fn is_empty(&self) -> bool {
+        self.keep_last.is_none()
+            && self.max_age.is_none()
+            && self.max_total_size.is_none()
+            && !self.compress
+    }
+
+    /// Compresses and removes the run logs in `dir` according to the policy
+    ///
+    /// Only `clean-*` logs are touched; the history and the quarantine live alongside them.
+    /// Compressed logs keep their modification time, so they keep their place in line.
+    pub fn enforce(&self, dir: &Path, now: SystemTime) -> Result<Pruned> {
+        let mut pruned = Pruned::default();
+        let mut logs = run_logs(dir)?;
+
+        // Newest first: the logs to keep are always a prefix
+        logs.sort_by(|a, b| {
+            b.modified
+                .cmp(&a.modified)
+                .then_with(|| b.path.cmp(&a.path))
+        });
+
+        let mut total_size = 0u64;
+        for (index, mut log) in logs.into_iter().enumerate() {
+            let age = now.duration_since(log.modified).unwrap_or_default();
+            let too_many = self.keep_last.is_some_and(|keep| index >= keep);
+            let too_old = self.max_age.is_some_and(|max_age| age > max_age);
+
+            // Size limits count what's left on disk, so compress before adding up
+            if !too_many && !too_old && self.compress && !is_compressed(&log.path) {
+                let compressed = compress(&log.path, log.modified)?;
+                pruned
+                    .compressed
+                    .push(std::mem::replace(&mut log.path, compressed));
+                log.size = fs::metadata(&log.path).map(|m| m.len()).unwrap_or(log.size);
+            }
+            total_size += log.size;
+            let too_big = self.max_total_size.is_some_and(|max| total_size > max);
+
+            if too_many || too_old || too_big {
+                fs::remove_file(&log.path)
+                    .with_context(|| format!("Failed to remove old log: {}", log.path.display()))?;
+                pruned.removed.push(log.path);
+            }
+        }
+
+        Ok(pruned)
+    }
+}
+
+struct RunLog {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Finds the logs left by earlier runs: `clean-<run-id>.log` or `.jsonl`, possibly gzipped
+fn run_logs(dir: &Path) -> Result<Vec<RunLog>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read log directory: {}", dir.display()))
+        }
+    };
+
+    let mut logs = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let name = name.strip_suffix(".gz").unwrap_or(name);
+        if !name.starts_with("clean-") || !(name.ends_with(".log") || name.ends_with(".jsonl")) {
+            continue;
+        }
+
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() {
+            logs.push(RunLog {
+                path,
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                size: metadata.len(),
+            });
+        }
+    }
+    Ok(logs)
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Replaces `path` with `path.gz`, keeping the original modification time
+fn compress(path: &Path, modified: SystemTime) -> Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    let compressed = PathBuf::from(name);
+    let partial = compressed.with_extension("gz.partial");
+
+    let result = (|| -> io::Result<()> {
+        let mut input = File::open(path)?;
+        let mut encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        let file = encoder.finish()?;
+        file.set_modified(modified)?;
+        fs::rename(&partial, &compressed)
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e).with_context(|| format!("Failed to compress log: {}", path.display()));
+    }
+
+    fs::remove_file(path)
+        .with_context(|| format!("Failed to remove compressed log: {}", path.display()))?;
+    Ok(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Writes a log `days_ago` days before `now` with `size` bytes
+    fn write_log(dir: &Path, name: &str, now: SystemTime, days_ago: u64, size: usize) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, "x".repeat(size)).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(now - DAY * days_ago as u32)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_retention_keeps_newest_and_compresses() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let now = SystemTime::now();
+        for days_ago in 1..=5 {
+            write_log(
+                dir,
+                &format!("clean-run{}.log", days_ago),
+                now,
+                days_ago,
+                100,
+            );
+        }
+        write_log(dir, "history.jsonl", now, 100, 100);
+
+        // Keep the three newest, then drop anything over two days old
+        let keep_three = Retention {
+            keep_last: Some(3),
+            ..Retention::default()
+        };
+        let pruned = keep_three.enforce(dir, now).unwrap();
+        assert_eq!(pruned.removed.len(), 2);
+        assert!(!dir.join("clean-run4.log").exists());
+        assert!(dir.join("clean-run3.log").exists());
+        assert!(
+            dir.join("history.jsonl").exists(),
+            "Only run logs are pruned"
+        );
+
+        let two_days = Retention {
+            max_age: Some(DAY * 2),
+            compress: true,
+            ..Retention::default()
+        };
+        let pruned = two_days.enforce(dir, now).unwrap();
+        assert_eq!(pruned.compressed.len(), 2);
+        assert_eq!(pruned.removed, [dir.join("clean-run3.log")]);
+        assert!(!dir.join("clean-run1.log").exists());
+
+        let mut contents = String::new();
+        GzDecoder::new(File::open(dir.join("clean-run1.log.gz")).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "x".repeat(100));
+
+        // Once the newest logs exceed the size limit, everything older goes too
+        write_log(dir, "clean-big.jsonl", now, 0, 10_000);
+        let small = Retention {
+            max_total_size: Some(5_000),
+            ..Retention::default()
+        };
+        let pruned = small.enforce(dir, now).unwrap();
+        assert_eq!(pruned.removed.len(), 3);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Names of `rust-toolchain` files, which pin a toolchain for the directory they're in
+pub const TOOLCHAIN_FILES: &[&str] = &["rust-toolchain", "rust-toolchain.toml"];
+
+/// What pruning a rustup directory removes, leaving the default and every pinned toolchain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prunable {
+    /// Installed toolchains nothing refers to
+    pub toolchains: Vec<PathBuf>,
+    /// The contents of `downloads/` and `tmp/`, which rustup only needs while installing
+    pub scratch: Vec<PathBuf>,
+}
+
+/// Works out what can go from the rustup directory `rustup_dir`
+///
+/// A toolchain stays if it is the default in `settings.toml`, a directory override there (for a
+/// directory that still exists), named by `RUSTUP_TOOLCHAIN`, or pinned by one of
+/// `toolchain_files` (the `rust-toolchain` files found while searching). Toolchains linked with
+/// `rustup toolchain link` are symlinks to elsewhere and always stay. Without a readable
+/// `settings.toml` there's no telling the default, so every toolchain stays.
+pub fn prunable(rustup_dir: &Path, toolchain_files: &[PathBuf]) -> Prunable {
+    prunable_with(
+        rustup_dir,
+        toolchain_files,
+        env::var("RUSTUP_TOOLCHAIN").ok(),
+    )
+}
+
+/// `prunable`, with `env_toolchain` standing in for `RUSTUP_TOOLCHAIN`
+fn prunable_with(
+    rustup_dir: &Path,
+    toolchain_files: &[PathBuf],
+    env_toolchain: Option<String>,
+) -> Prunable {
+    let scratch = ["downloads", "tmp"]
+        .iter()
+        .flat_map(|dir| entries(&rustup_dir.join(dir)))
+        .collect();
+
+    let Some(mut references) = settings_references(rustup_dir) else {
+        return Prunable {
+            toolchains: Vec::new(),
+            scratch,
+        };
+    };
+    references.extend(
+        toolchain_files
+            .iter()
+            .filter_map(|file| pinned_toolchain(file)),
+    );
+    references.extend(env_toolchain.filter(|name| !name.is_empty()));
+
+    let toolchains = entries(&rustup_dir.join("toolchains"))
+        .into_iter()
+        .filter(|dir| dir.symlink_metadata().is_ok_and(|m| m.is_dir()))
+        .filter(|dir| {
+            let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            !references
+                .iter()
+                .any(|reference| refers_to(reference, name))
+        })
+        .collect();
+
+    Prunable {
+        toolchains,
+        scratch,
+    }
+}
+
+/// The default toolchain and the overrides of existing directories in `settings.toml`
+fn settings_references(rustup_dir: &Path) -> Option<BTreeSet<String>> {
+    let settings: toml::Table = fs::read_to_string(rustup_dir.join("settings.toml"))
+        .ok()?
+        .parse()
+        .ok()?;
+
+    let mut references = BTreeSet::new();
+    references.extend(
+        settings
+            .get("default_toolchain")
+            .and_then(|d| d.as_str())
+            .map(str::to_string),
+    );
+    let overrides = settings
+        .get("overrides")
+        .and_then(|o| o.as_table())
+        .into_iter()
+        .flatten();
+    for (dir, toolchain) in overrides {
+        if Path::new(dir).is_dir() {
+            references.extend(toolchain.as_str().map(str::to_string));
+        }
+    }
+    Some(references)
+}
+
+/// The toolchain a `rust-toolchain` or `rust-toolchain.toml` file pins, if it names one
+///
+/// The TOML form has `toolchain.channel`; the legacy form is just the name.
+fn pinned_toolchain(file: &Path) -> Option<String> {
+    let contents = fs::read_to_string(file).ok()?;
+    let channel = match contents.parse::<toml::Table>() {
+        Ok(table) => table
+            .get("toolchain")?
+            .get("channel")?
+            .as_str()?
+            .to_string(),
+        Err(_) => contents
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())?
+            .to_string(),
+    };
+    Some(channel)
+}
+
+/// Returns true if the toolchain `reference` names is installed as the directory `installed`
+///
+/// Rustup installs `stable` as `stable-<host>`, `1.75` as `1.75-<host>` and
+/// `nightly-2024-01-01` as `nightly-2024-01-01-<host>`, so a name refers to itself with any
+/// host triple appended, but `nightly` doesn't refer to a dated nightly.
+fn refers_to(reference: &str, installed: &str) -> bool {
+    installed == reference
+        || installed
+            .strip_prefix(reference)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|host| host.starts_with(|c: char| c.is_ascii_alphabetic()))
+}
+
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prunable_keeps_default_overrides_and_pins() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let rustup = root.join(".rustup");
+        let host = "x86_64-unknown-linux-gnu";
+        for toolchain in [
+            "stable",
+            "nightly",
+            "nightly-2024-01-01",
+            "1.75",
+            "1.75.0",
+            "beta",
+        ] {
+            fs::create_dir_all(
+                rustup
+                    .join("toolchains")
+                    .join(format!("{}-{}", toolchain, host)),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(rustup.join("downloads")).unwrap();
+        fs::write(rustup.join("downloads/0123abcd"), "partial download").unwrap();
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::write(
+            rustup.join("settings.toml"),
+            format!(
+                "default_toolchain = \"stable-{host}\"\n\n[overrides]\n\"{}\" = \"beta-{host}\"\n\"{}\" = \"1.75.0-{host}\"\n",
+                root.join("app").display(),
+                root.join("gone").display(),
+            ),
+        )
+        .unwrap();
+
+        let pinned = root.join("app/rust-toolchain.toml");
+        fs::write(&pinned, "[toolchain]\nchannel = \"nightly-2024-01-01\"\n").unwrap();
+        let legacy = root.join("app/rust-toolchain");
+        fs::write(&legacy, "1.75\n").unwrap();
+
+        let files = [pinned, legacy];
+        let pruned = prunable_with(&rustup, &files, None);
+        assert_eq!(
+            pruned,
+            Prunable {
+                toolchains: vec![
+                    rustup.join(format!("toolchains/1.75.0-{}", host)),
+                    rustup.join(format!("toolchains/nightly-{}", host)),
+                ],
+                scratch: vec![rustup.join("downloads/0123abcd")],
+            }
+        );
+
+        // RUSTUP_TOOLCHAIN keeps the toolchain it names too
+        let pruned = prunable_with(&rustup, &files, Some("nightly".to_string()));
+        assert_eq!(
+            pruned.toolchains,
+            [rustup.join(format!("toolchains/1.75.0-{}", host))]
+        );
+
+        fs::remove_file(rustup.join("settings.toml")).unwrap();
+        assert!(prunable_with(&rustup, &[], None).toolchains.is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::workspace::normalize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The local disk cache sccache has been pointed at, if any
+///
+/// That's `SCCACHE_DIR`, or else `[cache.disk] dir` in sccache's config file (`SCCACHE_CONF`, or
+/// `sccache/config` in the user's config directory). sccache's built-in default location doesn't
+/// count: only a cache someone configured is found under a name other than `.sccache`. Relative
+/// paths are relative to the current directory, as sccache reads them; the path comes back
+/// canonical when it exists.
+pub fn configured_dir() -> Option<PathBuf> {
+    let dir = match env::var_os("SCCACHE_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let config = env::var_os("SCCACHE_CONF")
+                .filter(|config| !config.is_empty())
+                .map(PathBuf::from)
+                .or_else(default_config_file)?;
+            let table: toml::Table = fs::read_to_string(config).ok()?.parse().ok()?;
+            PathBuf::from(table.get("cache")?.get("disk")?.get("dir")?.as_str()?)
+        }
+    };
+    let dir = env::current_dir().map(|cwd| cwd.join(&dir)).unwrap_or(dir);
+    Some(dir.canonicalize().unwrap_or_else(|_| normalize(&dir)))
+}
+
+/// Where sccache looks for its config file when `SCCACHE_CONF` isn't set
+fn default_config_file() -> Option<PathBuf> {
+    let config_dir = dirs::config_dir()?;
+    Some(if cfg!(target_os = "macos") {
+        config_dir.join("Mozilla.sccache/config")
+    } else if cfg!(windows) {
+        config_dir.join("Mozilla/sccache/config/config")
+    } else {
+        config_dir.join("sccache/config")
+    })
+}
+
+/// The cache files to remove from `cache_dir`, least recently used first, so that what's left
+/// fits in `max_size` bytes; empty if it already does
+///
+/// sccache refreshes an entry's modification time whenever it's read, so the later of that and
+/// the access time is when the entry was last used. Ties go to the larger file.
+pub fn trim(cache_dir: &Path, max_size: u64) -> Vec<(PathBuf, u64)> {
+    let mut entries = Vec::new();
+    collect_entries(cache_dir, &mut entries);
+
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    entries.sort_by(|(a_path, a_size, a_used), (b_path, b_size, b_used)| {
+        a_used
+            .cmp(b_used)
+            .then(b_size.cmp(a_size))
+            .then(a_path.cmp(b_path))
+    });
+
+    let mut trimmed = Vec::new();
+    for (path, size, _) in entries {
+        if total <= max_size {
+            break;
+        }
+        total -= size;
+        trimmed.push((path, size));
+    }
+    trimmed
+}
+
+/// Every file under `dir`, with its size and when it was last used; symlinks aren't followed
+fn collect_entries(dir: &Path, entries: &mut Vec<(PathBuf, u64, SystemTime)>) {
+    for entry in fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+    {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_entries(&entry.path(), entries);
+        } else if metadata.is_file() {
+            let used = [metadata.modified(), metadata.accessed()]
+                .into_iter()
+                .filter_map(|time| time.ok())
+                .max()
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((entry.path(), metadata.len(), used));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trim_removes_least_recently_used_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = temp_dir.path();
+        for (name, size, used) in [
+            ("a/1/old", 40, 100),
+            ("b/2/recent", 40, 300),
+            ("c/3/middle", 40, 200),
+        ] {
+            let path = cache.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, vec![0u8; size]).unwrap();
+            let time = FileTime::from_unix_time(used, 0);
+            filetime::set_file_times(&path, time, time).unwrap();
+        }
+
+        assert!(trim(cache, 120).is_empty());
+        assert_eq!(trim(cache, 80), [(cache.join("a/1/old"), 40)]);
+        assert_eq!(
+            trim(cache, 50),
+            [(cache.join("a/1/old"), 40), (cache.join("c/3/middle"), 40)]
+        );
+        assert_eq!(trim(cache, 0).len(), 3);
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+/// Which build units to prune from target directories, cargo-sweep style
+///
+/// A unit is one compiled crate (or build script) in a profile directory: its
+/// `.fingerprint/<name>-<hash>` entry plus everything in `deps/`, `examples/` and `build/`
+/// carrying the same hash. With neither field set nothing is stale.
+#[derive(Debug, Clone, Default)]
+pub struct Sweep {
+    /// Units not rebuilt for this long are stale
+    pub older_than: Option<Duration>,
+    /// Fingerprint `rustc` hashes of the installed toolchains; units built by any other are stale
+    pub installed: Option<HashSet<u64>>,
+}
+
+/// One compiled unit, as recorded in `.fingerprint`
+struct Unit {
+    fingerprint: PathBuf,
+    hash: String,
+    last_built: SystemTime,
+    rustc: Option<u64>,
+}
+
+impl Sweep {
+    /// The files and directories of stale units in `profile_dir`, part of the target directory `target`
+    ///
+    /// Units built by the toolchain that last used the target directory are never stale for
+    /// being built by another toolchain while that toolchain (the sysroot in `.rustc_info.json`)
+    /// is still installed, so the current build stays warm whatever the probe found.
+    pub fn stale_paths(&self, target: &Path, profile_dir: &Path, now: SystemTime) -> Vec<PathBuf> {
+        let units = units(profile_dir);
+        let current = if last_toolchain_installed(target) {
+            units
+                .iter()
+                .max_by_key(|unit| unit.last_built)
+                .and_then(|unit| unit.rustc)
+        } else {
+            None
+        };
+        let too_old = |time: SystemTime| {
+            self.older_than
+                .is_some_and(|max_age| now.duration_since(time).unwrap_or_default() > max_age)
+        };
+
+        let mut stale = Vec::new();
+        let mut stale_hashes = HashSet::new();
+        for unit in &units {
+            let uninstalled = match (&self.installed, unit.rustc) {
+                (Some(installed), Some(rustc)) => {
+                    Some(rustc) != current && !installed.contains(&rustc)
+                }
+                _ => false,
+            };
+            if too_old(unit.last_built) || uninstalled {
+                stale.push(unit.fingerprint.clone());
+                stale_hashes.insert(unit.hash.as_str());
+            }
+        }
+
+        for dir in ["deps", "examples", "build"] {
+            stale.extend(entries(&profile_dir.join(dir)).into_iter().filter(|path| {
+                artifact_hash(path).is_some_and(|hash| stale_hashes.contains(hash))
+            }));
+        }
+
+        // Incremental sessions use their own hashes, so only their age says anything
+        if self.older_than.is_some() {
+            stale.extend(
+                entries(&profile_dir.join("incremental"))
+                    .into_iter()
+                    .filter(|dir| newest_mtime(dir).is_some_and(too_old)),
+            );
+        }
+
+        stale.sort();
+        stale
+    }
+}
+
+/// The units recorded in `profile_dir/.fingerprint`
+fn units(profile_dir: &Path) -> Vec<Unit> {
+    entries(&profile_dir.join(".fingerprint"))
+        .into_iter()
+        .filter_map(|fingerprint| {
+            let hash = artifact_hash(&fingerprint)?.to_string();
+            let last_built = newest_mtime(&fingerprint)?;
+            let rustc = entries(&fingerprint)
+                .iter()
+                .filter(|file| file.extension().is_some_and(|ext| ext == "json"))
+                .find_map(|file| {
+                    let json: serde_json::Value =
+                        serde_json::from_slice(&fs::read(file).ok()?).ok()?;
+                    json.get("rustc")?.as_u64()
+                });
+            Some(Unit {
+                fingerprint,
+                hash,
+                last_built,
+                rustc,
+            })
+        })
+        .collect()
+}
+
+/// The hash in a unit's file name: `libserde-1a2b3c4d5e6f7a8b.rlib` has `1a2b3c4d5e6f7a8b`
+fn artifact_hash(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.split('.').next()?;
+    let (_, hash) = stem.rsplit_once('-')?;
+    (hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// Returns true if the sysroot recorded in `target/.rustc_info.json` still exists
+fn last_toolchain_installed(target: &Path) -> bool {
+    let Some(info) = fs::read(target.join(".rustc_info.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_slice::<serde_json::Value>(&contents).ok())
+    else {
+        return false;
+    };
+
+    // Cargo caches `rustc --print=sysroot` among the outputs; the sysroot is the line naming
+    // a directory with a `lib/rustlib`
+    info.get("outputs")
+        .and_then(|outputs| outputs.as_object())
+        .into_iter()
+        .flat_map(|outputs| outputs.values())
+        .filter_map(|output| output.get("stdout")?.as_str())
+        .flat_map(str::lines)
+        .any(|line| Path::new(line).is_absolute() && Path::new(line).join("lib/rustlib").is_dir())
+}
+
+/// Learns the fingerprint `rustc` hash of every installed toolchain, as cargo-sweep does
+///
+/// Cargo records a hash of `rustc -vV` in each fingerprint. Rather than reproduce Cargo's
+/// hashing, this builds an empty crate with each toolchain `rustup` lists (or just the
+/// `cargo` on the PATH without rustup) and reads the hash back.
+pub fn installed_rustc_hashes() -> Result<HashSet<u64>> {
+    let toolchains: Vec<Option<String>> =
+        match Command::new("rustup").args(["toolchain", "list"]).output() {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .map(|name| Some(name.to_string()))
+                .collect(),
+            _ => vec![None],
+        };
+
+    let probe = std::env::temp_dir().join(format!("wd-40-probe-{}", std::process::id()));
+    let hashes = probe_toolchains(&probe, &toolchains);
+    let _ = fs::remove_dir_all(&probe);
+
+    let hashes = hashes?;
+    if hashes.is_empty() {
+        bail!("Could not build with any installed toolchain to tell their builds apart");
+    }
+    Ok(hashes)
+}
+
+fn probe_toolchains(probe: &Path, toolchains: &[Option<String>]) -> Result<HashSet<u64>> {
+    fs::create_dir_all(probe.join("src"))?;
+    fs::write(
+        probe.join("Cargo.toml"),
+        "[package]\nname = \"wd40-probe\"\nversion = \"0.0.0\"\n\n[workspace]\n",
+    )?;
+    fs::write(probe.join("src/lib.rs"), "")?;
+
+    let mut hashes = HashSet::new();
+    for (index, toolchain) in toolchains.iter().enumerate() {
+        let target = probe.join(format!("target-{}", index));
+        let mut cargo = Command::new("cargo");
+        if let Some(toolchain) = toolchain {
+            cargo.arg(format!("+{}", toolchain));
+        }
+        let built = cargo
+            .args(["build", "--offline", "--quiet", "--target-dir"])
+            .arg(&target)
+            .current_dir(probe)
+            .output()
+            .is_ok_and(|output| output.status.success());
+
+        // A toolchain that can't build (say, a broken install) simply isn't counted
+        if built {
+            hashes.extend(
+                units(&target.join("debug"))
+                    .into_iter()
+                    .filter_map(|unit| unit.rustc),
+            );
+        }
+    }
+    Ok(hashes)
+}
+
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// The newest modification time of `path` and its direct children
+fn newest_mtime(path: &Path) -> Option<SystemTime> {
+    std::iter::once(path.to_path_buf())
+        .chain(entries(path))
+        .filter_map(|path| path.symlink_metadata().and_then(|m| m.modified()).ok())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Writes a fingerprint and its deps artifact built `days_ago` days before `now` by `rustc`
+    fn unit(profile: &Path, name: &str, hash: &str, rustc: u64, now: SystemTime, days_ago: u32) {
+        let built = FileTime::from_system_time(now - DAY * days_ago);
+        let fingerprint = profile
+            .join(".fingerprint")
+            .join(format!("{}-{}", name, hash));
+        fs::create_dir_all(&fingerprint).unwrap();
+        let json = fingerprint.join(format!("lib-{}.json", name));
+        fs::write(
+            &json,
+            format!("{{\"rustc\":{},\"features\":\"[]\"}}", rustc),
+        )
+        .unwrap();
+        let rlib = profile
+            .join("deps")
+            .join(format!("lib{}-{}.rlib", name, hash));
+        fs::create_dir_all(rlib.parent().unwrap()).unwrap();
+        fs::write(&rlib, "rlib").unwrap();
+        for path in [&json, &fingerprint, &rlib] {
+            filetime::set_file_mtime(path, built).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_stale_paths_by_age_and_toolchain() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        let profile = target.join("debug");
+        let now = SystemTime::now();
+
+        unit(&profile, "serde", "00000000000000a1", 1, now, 0);
+        unit(&profile, "serde", "00000000000000a2", 1, now, 40);
+        unit(&profile, "log", "00000000000000b1", 2, now, 1);
+        unit(&profile, "app", "00000000000000c1", 3, now, 2);
+        let incremental = profile.join("incremental/app-1x2y3z");
+        fs::create_dir_all(&incremental).unwrap();
+        filetime::set_file_mtime(&incremental, FileTime::from_system_time(now - DAY * 40)).unwrap();
+
+        let month = Sweep {
+            older_than: Some(DAY * 30),
+            installed: None,
+        };
+        assert_eq!(
+            month.stale_paths(&target, &profile, now),
+            [
+                profile.join(".fingerprint/serde-00000000000000a2"),
+                profile.join("deps/libserde-00000000000000a2.rlib"),
+                incremental,
+            ]
+        );
+
+        // Only toolchain 1 is installed; without a .rustc_info.json nothing else is protected
+        let toolchains = Sweep {
+            older_than: None,
+            installed: Some(HashSet::from([1])),
+        };
+        assert_eq!(
+            toolchains.stale_paths(&target, &profile, now),
+            [
+                profile.join(".fingerprint/app-00000000000000c1"),
+                profile.join(".fingerprint/log-00000000000000b1"),
+                profile.join("deps/libapp-00000000000000c1.rlib"),
+                profile.join("deps/liblog-00000000000000b1.rlib"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use crate::workspace::normalize;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A target directory configured away from the default `<project>/target`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CustomTarget {
+    /// Set in the project's own `.cargo/config.toml`; cleaned with the project
+    Project(PathBuf),
+    /// Set by `CARGO_TARGET_DIR` or a config file above the project, so other
+    /// projects build into it too; cleaned once as a shared target artifact
+    Shared(PathBuf),
+}
+
+impl CustomTarget {
+    pub fn path(&self) -> &Path {
+        match self {
+            CustomTarget::Project(path) | CustomTarget::Shared(path) => path,
+        }
+    }
+}
+
+/// Works out where Cargo puts `project_dir`'s build output when it isn't `<project>/target`
+///
+/// Follows Cargo's precedence: `CARGO_TARGET_DIR`, then `CARGO_BUILD_TARGET_DIR`, then
+/// `build.target-dir` from the nearest `.cargo/config.toml` (or legacy `.cargo/config`) at or
+/// above the project, then the one in `$CARGO_HOME`. Relative paths in a config file are
+/// relative to the directory holding its `.cargo`; relative paths in the environment to the
+/// current directory. Paths come back with `.` and `..` resolved.
+pub fn resolve(project_dir: &Path) -> Option<CustomTarget> {
+    resolve_with(project_dir, |var| env::var_os(var))
+}
+
+/// `resolve`, reading environment variables through `env_var`
+fn resolve_with(
+    project_dir: &Path,
+    env_var: impl Fn(&str) -> Option<OsString>,
+) -> Option<CustomTarget> {
+    for var in ["CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"] {
+        if let Some(value) = env_var(var).filter(|value| !value.is_empty()) {
+            let path = PathBuf::from(value);
+            let path = env::current_dir()
+                .map(|cwd| cwd.join(&path))
+                .unwrap_or(path);
+            return Some(CustomTarget::Shared(normalize(&path)));
+        }
+    }
+
+    for dir in project_dir.ancestors() {
+        if let Some(path) = configured_target_dir(&dir.join(".cargo"), dir) {
+            return Some(if dir == project_dir {
+                CustomTarget::Project(path)
+            } else {
+                CustomTarget::Shared(path)
+            });
+        }
+    }
+
+    let cargo_home = env_var("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))?;
+    let base = cargo_home.parent().unwrap_or(&cargo_home).to_path_buf();
+    configured_target_dir(&cargo_home, &base).map(CustomTarget::Shared)
+}
+
+/// Reads `build.target-dir` from `config_dir/config.toml` (or `config_dir/config`)
+fn configured_target_dir(config_dir: &Path, base: &Path) -> Option<PathBuf> {
+    ["config.toml", "config"].iter().find_map(|name| {
+        let table: toml::Table = fs::read_to_string(config_dir.join(name))
+            .ok()?
+            .parse()
+            .ok()?;
+        let target_dir = table.get("build")?.get("target-dir")?.as_str()?;
+        Some(normalize(&base.join(target_dir)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_nearest_config_wins_and_sets_scope() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let project = root.join("src/app");
+        let other = root.join("src/lib");
+        fs::create_dir_all(project.join(".cargo")).unwrap();
+        fs::create_dir_all(other.join(".cargo")).unwrap();
+        fs::create_dir_all(root.join("src/.cargo")).unwrap();
+
+        fs::write(
+            root.join("src/.cargo/config.toml"),
+            "[build]\ntarget-dir = \"../build-cache\"\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join(".cargo/config"),
+            "[build]\ntarget-dir = \"out\"\n",
+        )
+        .unwrap();
+        fs::write(
+            other.join(".cargo/config.toml"),
+            "[profile.dev]\ndebug = false\n",
+        )
+        .unwrap();
+
+        // Whatever the environment running the tests sets, only a Cargo home without config is seen
+        let cargo_home = root.join("cargo-home").into_os_string();
+        let no_target_env = |var: &str| (var == "CARGO_HOME").then(|| cargo_home.clone());
+        assert_eq!(
+            resolve_with(&project, no_target_env),
+            Some(CustomTarget::Project(project.join("out")))
+        );
+        assert_eq!(
+            resolve_with(&other, no_target_env),
+            Some(CustomTarget::Shared(root.join("build-cache")))
+        );
+
+        // The environment overrides every config file
+        let shared = root.join("shared").into_os_string();
+        let target_env = |var: &str| (var == "CARGO_BUILD_TARGET_DIR").then(|| shared.clone());
+        assert_eq!(
+            resolve_with(&project, target_env),
+            Some(CustomTarget::Shared(root.join("shared")))
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use crate::sweep::Sweep;
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A well-known subdirectory of a target directory that can be cleaned on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, ValueEnum)]
+pub enum TargetPart {
+    /// Incremental compilation caches (`<profile>/incremental`)
+    Incremental,
+    /// Generated documentation (`doc`)
+    Doc,
+    /// Packages built by `cargo package` (`package`)
+    Package,
+}
+
+/// Which parts of a target directory to clean
+///
+/// Every field narrows the selection; with none set the whole directory goes.
+#[derive(Debug, Clone, Default)]
+pub struct TargetParts {
+    /// Profiles to clean, by profile name (`dev`, `bench`, ...) or directory (`debug`, ...)
+    pub profiles: Vec<String>,
+    /// Cross-compilation target triples to clean, e.g. `wasm32-unknown-unknown`
+    pub triples: Vec<String>,
+    /// Well-known subdirectories to clean
+    pub parts: BTreeSet<TargetPart>,
+    /// Top-level entries that are never touched, e.g. `criterion`
+    pub preserve: Vec<String>,
+    /// Prune stale units from each selected profile instead of removing whole profiles
+    pub sweep: Option<Sweep>,
+    /// Keep each project's executables and libraries built for other programs, per its validation
+    pub keep_final_artifacts: bool,
+    /// Files never removed (their directories are cleaned around them); filled in per project
+    /// for `keep_final_artifacts`
+    pub keep: Vec<PathBuf>,
+}
+
+/// Entries that keep a target directory recognizable; never removed piecemeal
+const TARGET_MARKERS: &[&str] = &["CACHEDIR.TAG", ".rustc_info.json"];
+
+impl TargetParts {
+    /// Returns true if whole target directories are cleaned
+    pub fn is_whole(&self) -> bool {
+        self.profiles.is_empty()
+            && self.triples.is_empty()
+            && self.parts.is_empty()
+            && self.preserve.is_empty()
+            && self.sweep.is_none()
+            && !self.keep_final_artifacts
+            && self.keep.is_empty()
+    }
+
+    /// The existing subdirectories of `target` this selection cleans
+    ///
+    /// Profiles, `incremental` and `doc` are looked up at the top level and inside every
+    /// target triple (or only the given triples); `package` only exists at the top level.
+    /// With a sweep, profiles and triples only say where to sweep; they aren't removed whole.
+    pub fn select(&self, target: &Path) -> Vec<PathBuf> {
+        let trees = self.trees(target);
+        let sweeping = self.sweep.is_some();
+
+        let mut selected = Vec::new();
+        let incremental = self.parts.contains(&TargetPart::Incremental);
+        for tree in &trees {
+            if incremental {
+                selected.extend(
+                    self.profile_dirs(tree)
+                        .into_iter()
+                        .map(|profile| profile.join("incremental")),
+                );
+            } else if !self.profiles.is_empty() && !sweeping {
+                selected.extend(self.profile_dirs(tree));
+            }
+            if self.parts.contains(&TargetPart::Doc) {
+                selected.push(tree.join("doc"));
+            }
+        }
+        if self.parts.contains(&TargetPart::Package) {
+            selected.push(target.join("package"));
+        }
+
+        // A triple on its own means all of it; otherwise everything but what's preserved or kept
+        if self.profiles.is_empty() && self.parts.is_empty() && !sweeping {
+            if !self.triples.is_empty() {
+                selected = trees;
+            } else {
+                selected = entries(target)
+                    .into_iter()
+                    .filter(|path| {
+                        !path
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|name| TARGET_MARKERS.contains(&name))
+                    })
+                    .collect();
+            }
+        }
+
+        let selected = self.existing_unpreserved(target, selected);
+        self.spare_kept(selected)
+    }
+
+    /// The existing profile directories of `target` a sweep goes through
+    pub fn sweep_dirs(&self, target: &Path) -> Vec<PathBuf> {
+        if self.sweep.is_none() {
+            return Vec::new();
+        }
+        let profiles = self
+            .trees(target)
+            .iter()
+            .flat_map(|tree| self.profile_dirs(tree))
+            .collect();
+        self.existing_unpreserved(target, profiles)
+    }
+
+    /// The top level of `target` and every target triple in it, or only the given triples
+    ///
+    /// Triples that aren't a plain directory name (see `parse_dir_name`) select nothing.
+    fn trees(&self, target: &Path) -> Vec<PathBuf> {
+        if self.triples.is_empty() {
+            std::iter::once(target.to_path_buf())
+                .chain(triple_dirs(target))
+                .collect()
+        } else {
+            self.triples
+                .iter()
+                .filter(|triple| is_dir_name(triple))
+                .map(|triple| target.join(triple))
+                .collect()
+        }
+    }
+
+    /// The given profiles in `tree`, or all of them; like triples, only plain directory names
+    fn profile_dirs(&self, tree: &Path) -> Vec<PathBuf> {
+        if self.profiles.is_empty() {
+            subdirs(tree)
+                .into_iter()
+                .filter(|dir| is_profile_dir(dir))
+                .collect()
+        } else {
+            self.profiles
+                .iter()
+                .filter(|profile| is_dir_name(profile))
+                .map(|profile| tree.join(profile_dir(profile)))
+                .collect()
+        }
+    }
+
+    /// Replaces every path holding a file to keep with its other contents, recursively
+    fn spare_kept(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut spared = Vec::new();
+        for path in paths {
+            if self.keep.contains(&path) {
+                continue;
+            }
+            if path.is_dir() && self.keep.iter().any(|kept| kept.starts_with(&path)) {
+                let mut children = entries(&path);
+                children.sort();
+                spared.extend(self.spare_kept(children));
+            } else {
+                spared.push(path);
+            }
+        }
+        spared
+    }
+
+    /// Drops paths that don't exist, are preserved, or don't lie inside `target`
+    ///
+    /// Containment is checked on the canonical parent, so a symlink inside `target` is kept
+    /// (removing it doesn't touch what it points to) but nothing reached through `..` or a
+    /// symlinked directory is.
+    fn existing_unpreserved(&self, target: &Path, mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let Ok(canonical_target) = target.canonicalize() else {
+            return Vec::new();
+        };
+        paths.retain(|path| {
+            let top = path
+                .strip_prefix(target)
+                .ok()
+                .and_then(|p| p.components().next());
+            let preserved = top.is_some_and(|top| {
+                self.preserve
+                    .iter()
+                    .any(|name| top.as_os_str() == name.as_str())
+            });
+            !preserved && path.symlink_metadata().is_ok() && is_inside(path, &canonical_target)
+        });
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// The directories of `target` final artifacts can be in: profiles, also per target triple
+///
+/// That's every directory one or two levels down, since a profile that only holds kept
+/// executables no longer looks like a profile.
+pub fn output_dirs(target: &Path) -> Vec<PathBuf> {
+    subdirs(target)
+        .into_iter()
+        .flat_map(|dir| std::iter::once(dir.clone()).chain(subdirs(&dir)))
+        .collect()
+}
+
+/// Parses a `--profile` or `--triple` value, which must name a single directory
+///
+/// Anything else (`..`, an absolute path, a path with separators) would select something
+/// outside the target directory.
+pub fn parse_dir_name(input: &str) -> Result<String, String> {
+    if is_dir_name(input) {
+        Ok(input.to_string())
+    } else {
+        Err(format!(
+            "'{}' must be a single directory name, without `/`, `\\` or `..`",
+            input
+        ))
+    }
+}
+
+fn is_dir_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !name.contains(['/', '\\'])
+}
+
+/// Returns true if `path` itself (not what it may link to) is somewhere under `canonical_target`
+fn is_inside(path: &Path, canonical_target: &Path) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    parent.canonicalize().is_ok_and(|parent| {
+        parent.join(name).starts_with(canonical_target) && parent.join(name) != canonical_target
+    })
+}
+
+/// Maps a profile name to the directory Cargo builds it into
+fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        other => other,
+    }
+}
+
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect()
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Returns true if `dir` holds the output of one build profile
+fn is_profile_dir(dir: &Path) -> bool {
+    [".fingerprint", "deps", "build", "incremental"]
+        .iter()
+        .any(|name| dir.join(name).is_dir())
+}
+
+/// The per-triple subtrees of a target directory (`<target>/<triple>/<profile>/...`)
+fn triple_dirs(target: &Path) -> Vec<PathBuf> {
+    subdirs(target)
+        .into_iter()
+        .filter(|dir| !is_profile_dir(dir) && subdirs(dir).iter().any(|sub| is_profile_dir(sub)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_select_profiles_triples_and_parts() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        for dir in [
+            "debug/deps",
+            "debug/incremental",
+            "release/.fingerprint",
+            "release/incremental",
+            "wasm32-unknown-unknown/release/deps",
+            "wasm32-unknown-unknown/release/incremental",
+            "wasm32-unknown-unknown/doc",
+            "doc/core",
+            "package/core-0.1.0",
+            "criterion/parse/base",
+        ] {
+            fs::create_dir_all(target.join(dir)).unwrap();
+        }
+        fs::write(target.join("CACHEDIR.TAG"), "").unwrap();
+
+        let select = |parts: TargetParts| -> Vec<String> {
+            parts
+                .select(&target)
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&target)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        };
+
+        let incremental = TargetParts {
+            parts: BTreeSet::from([TargetPart::Incremental]),
+            ..TargetParts::default()
+        };
+        assert_eq!(
+            select(incremental),
+            [
+                "debug/incremental",
+                "release/incremental",
+                "wasm32-unknown-unknown/release/incremental"
+            ]
+        );
+
+        let dev_and_docs = TargetParts {
+            profiles: vec!["dev".to_string()],
+            parts: BTreeSet::from([TargetPart::Doc, TargetPart::Package]),
+            ..TargetParts::default()
+        };
+        assert_eq!(
+            select(dev_and_docs),
+            ["debug", "doc", "package", "wasm32-unknown-unknown/doc"]
+        );
+
+        let wasm_release = TargetParts {
+            profiles: vec!["release".to_string()],
+            triples: vec!["wasm32-unknown-unknown".to_string()],
+            ..TargetParts::default()
+        };
+        assert_eq!(select(wasm_release), ["wasm32-unknown-unknown/release"]);
+
+        // Everything but the benchmark baselines and the markers
+        let keep_criterion = TargetParts {
+            preserve: vec!["criterion".to_string()],
+            ..TargetParts::default()
+        };
+        assert_eq!(
+            select(keep_criterion),
+            [
+                "debug",
+                "doc",
+                "package",
+                "release",
+                "wasm32-unknown-unknown"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profiles_and_triples_stay_inside_target() {
+        for bad in [
+            "..",
+            ".",
+            "/usr",
+            "release/../..",
+            "wasm32/release",
+            "a\\b",
+            "",
+        ] {
+            assert!(parse_dir_name(bad).is_err(), "{:?} should be rejected", bad);
+        }
+        assert_eq!(
+            parse_dir_name("wasm32-unknown-unknown").as_deref(),
+            Ok("wasm32-unknown-unknown")
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("app/target");
+        fs::create_dir_all(target.join("debug/deps")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("outside")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(temp_dir.path().join("outside"), target.join("linked")).unwrap();
+
+        // Built directly, bypassing the parser, they still select nothing outside the target
+        for bad in ["..", "/usr", "../app"] {
+            let profile = TargetParts {
+                profiles: vec![bad.to_string()],
+                ..TargetParts::default()
+            };
+            assert!(profile.select(&target).is_empty(), "--profile {:?}", bad);
+            let triple = TargetParts {
+                triples: vec![bad.to_string()],
+                ..TargetParts::default()
+            };
+            assert!(triple.select(&target).is_empty(), "--triple {:?}", bad);
+        }
+        #[cfg(unix)]
+        {
+            let through_link =
+                TargetParts::default().existing_unpreserved(&target, vec![target.join("linked/x")]);
+            assert!(through_link.is_empty());
+        }
+    }
+}
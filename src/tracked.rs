@@ -0,0 +1,99 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How many tracked files are named when refusing to remove an artifact
+const FILES_SHOWN: usize = 3;
+
+/// An artifact holding files tracked by git, which is never removed without `--force`
+///
+/// Returned (through `anyhow`) by the removal functions in `cleaner`; callers can
+/// `downcast_ref` it to report the artifact as skipped rather than failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackedFiles {
+    pub path: PathBuf,
+    /// The tracked files under `path`
+    pub files: Vec<PathBuf>,
+}
+
+impl fmt::Display for TrackedFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown: Vec<String> = self
+            .files
+            .iter()
+            .take(FILES_SHOWN)
+            .map(|file| {
+                file.strip_prefix(&self.path)
+                    .unwrap_or(file)
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        write!(
+            f,
+            "holds {} git-tracked {} ({}",
+            self.files.len(),
+            if self.files.len() == 1 {
+                "file"
+            } else {
+                "files"
+            },
+            shown.join(", ")
+        )?;
+        if self.files.len() > FILES_SHOWN {
+            write!(f, " and {} more", self.files.len() - FILES_SHOWN)?;
+        }
+        write!(f, "); pass --force to remove it anyway")
+    }
+}
+
+impl std::error::Error for TrackedFiles {}
+
+/// Fails with `TrackedFiles` if anything under `path` is in the index of the git work tree it's in
+///
+/// Paths outside a work tree, or when `git` can't be run, pass: there's nothing to tell.
+pub fn ensure_untracked(path: &Path) -> Result<(), TrackedFiles> {
+    let files = tracked_files(path);
+    if files.is_empty() {
+        Ok(())
+    } else {
+        Err(TrackedFiles {
+            path: path.to_path_buf(),
+            files,
+        })
+    }
+}
+
+/// The files under `path` tracked by the nearest git work tree containing it
+fn tracked_files(path: &Path) -> Vec<PathBuf> {
+    // `.git` is a directory in a plain clone and a file in worktrees and submodules
+    let Some(root) = path.ancestors().find(|dir| dir.join(".git").exists()) else {
+        return Vec::new();
+    };
+    let Ok(relative) = path.strip_prefix(root) else {
+        return Vec::new();
+    };
+    let relative = if relative.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        relative
+    };
+
+    let output = Command::new("git")
+        .arg("--literal-pathspecs")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z", "--"])
+        .arg(relative)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|file| !file.is_empty())
+            .map(|file| root.join(file))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
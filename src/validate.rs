@@ -0,0 +1,531 @@
+use crate::age::format_age;
+use clap::ValueEnum;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How a Cargo project is checked before its target directories are cleaned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Validator {
+    /// Parse Cargo.toml and check its structure in-process
+    #[default]
+    Manifest,
+    /// Run `cargo metadata --no-deps`, which needs a working toolchain for the project
+    Cargo,
+}
+
+/// Which validator runs, and how long it may take per project
+#[derive(Debug, Clone, Copy)]
+pub struct Validation {
+    pub validator: Validator,
+    pub timeout: Duration,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Validation {
+            validator: Validator::default(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What validating a project learned about it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidProject {
+    /// File names of the executables and libraries for other programs it builds, on every platform
+    pub final_artifacts: Vec<String>,
+}
+
+impl Validation {
+    /// Validates the project at `project_dir` along with its workspace `members`
+    ///
+    /// Errors are one line, fit to show as the reason a project was skipped. A validator that
+    /// runs past the timeout fails; cargo is killed, while a manifest check stuck on the
+    /// filesystem is left behind on its thread.
+    pub fn validate(
+        &self,
+        project_dir: &Path,
+        members: &[PathBuf],
+    ) -> Result<ValidProject, String> {
+        match self.validator {
+            Validator::Manifest => {
+                let (project_dir, members) = (project_dir.to_path_buf(), members.to_vec());
+                let (sender, receiver) = mpsc::channel();
+                thread::spawn(move || {
+                    let _ = sender.send(check_manifests(&project_dir, &members));
+                });
+                receiver.recv_timeout(self.timeout).unwrap_or_else(|_| {
+                    Err(format!(
+                        "manifest check timed out after {}",
+                        format_age(self.timeout)
+                    ))
+                })
+            }
+            Validator::Cargo => {
+                cargo_metadata(project_dir, self.timeout).map(|metadata| ValidProject {
+                    final_artifacts: metadata_artifacts(&metadata),
+                })
+            }
+        }
+    }
+}
+
+/// Runs `cargo metadata --no-deps` in `project_dir`, returning its output
+fn cargo_metadata(project_dir: &Path, timeout: Duration) -> Result<String, String> {
+    let mut child = Command::new("cargo")
+        .arg("metadata")
+        .arg("--format-version=1")
+        .arg("--no-deps")
+        .current_dir(project_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute cargo metadata: {}", e))?;
+
+    // Drain both pipes while waiting so a chatty cargo can't block on a full one
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "cargo metadata timed out after {}",
+                    format_age(timeout)
+                ));
+            }
+            Err(e) => return Err(format!("Failed to wait for cargo metadata: {}", e)),
+        }
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if status.success() {
+        Ok(String::from_utf8_lossy(&stdout).into_owned())
+    } else {
+        let error_msg = String::from_utf8_lossy(&stderr);
+        // Extract just the first line of the error for cleaner output
+        let first_line = error_msg.lines().next().unwrap_or("Invalid project");
+        Err(first_line.to_string())
+    }
+}
+
+fn read_all(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut contents = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut contents);
+        }
+        contents
+    })
+}
+
+/// The final artifacts named by `cargo metadata` output: its packages' bin, cdylib and staticlib targets
+fn metadata_artifacts(metadata: &str) -> Vec<String> {
+    let Ok(metadata) = serde_json::from_str::<serde_json::Value>(metadata) else {
+        return Vec::new();
+    };
+
+    let mut names = Vec::new();
+    let packages = metadata
+        .get("packages")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten();
+    for target in packages
+        .filter_map(|p| p.get("targets")?.as_array())
+        .flatten()
+    {
+        let Some(name) = target.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let crate_types = target
+            .get("crate_types")
+            .and_then(|t| t.as_array())
+            .into_iter()
+            .flatten();
+        for crate_type in crate_types.filter_map(|t| t.as_str()) {
+            names.extend(file_names(crate_type, name));
+        }
+    }
+    names
+}
+
+/// The file names a target of `crate_type` called `name` is built into, on every platform
+fn file_names(crate_type: &str, name: &str) -> Vec<String> {
+    let lib_name = name.replace('-', "_");
+    match crate_type {
+        "bin" => vec![
+            name.to_string(),
+            format!("{}.exe", name),
+            format!("{}.pdb", name),
+        ],
+        "cdylib" => vec![
+            format!("lib{}.so", lib_name),
+            format!("lib{}.dylib", lib_name),
+            format!("{}.dll", lib_name),
+            format!("{}.dll.lib", lib_name),
+        ],
+        "staticlib" => vec![format!("lib{}.a", lib_name), format!("{}.lib", lib_name)],
+        _ => Vec::new(),
+    }
+}
+
+/// Checks the manifests of a project and its `members`, collecting what they build
+fn check_manifests(project_dir: &Path, members: &[PathBuf]) -> Result<ValidProject, String> {
+    let mut final_artifacts = check_manifest(project_dir)?;
+    for member in members {
+        let artifacts = check_manifest(member).map_err(|reason| {
+            format!(
+                "failed to load manifest for workspace member `{}`: {}",
+                member.display(),
+                reason
+            )
+        })?;
+        final_artifacts.extend(artifacts);
+    }
+    final_artifacts.sort();
+    final_artifacts.dedup();
+    Ok(ValidProject { final_artifacts })
+}
+
+/// Checks what cleaning relies on in `dir/Cargo.toml`, returning the final artifacts it builds
+///
+/// That's the things Cargo would refuse the project for without looking at dependencies: valid
+/// TOML with a `[package]` or `[workspace]`, a valid package name, at least one target to build
+/// (a build script doesn't count), existing workspace members, and a workspace to inherit
+/// `workspace = true` keys from.
+fn check_manifest(dir: &Path) -> Result<Vec<String>, String> {
+    let manifest = read_manifest(dir)?;
+    let package = table(&manifest, "package")?;
+    let workspace = table(&manifest, "workspace")?;
+    if package.is_none() && workspace.is_none() {
+        return Err("manifest is missing either a `[package]` or a `[workspace]`".to_string());
+    }
+
+    if let Some(workspace) = workspace {
+        let members = workspace
+            .get("members")
+            .and_then(|m| m.as_array())
+            .into_iter()
+            .flatten();
+        for member in members.filter_map(|m| m.as_str()) {
+            // Globs only match what exists; literal members have to
+            let is_glob = member.contains(['*', '?', '[']);
+            if !is_glob && !dir.join(member).join("Cargo.toml").is_file() {
+                return Err(format!(
+                    "failed to load manifest for workspace member `{}`",
+                    member
+                ));
+            }
+        }
+    }
+
+    let Some(package) = package else {
+        return Ok(Vec::new());
+    };
+    let name = match package.get("name") {
+        None => return Err("missing field `name` in `[package]`".to_string()),
+        Some(name) => name.as_str().ok_or("`package.name` must be a string")?,
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("invalid package name `{}`", name));
+    }
+
+    if workspace.is_none() {
+        if let Some(key) = inherited_key(&manifest) {
+            if !in_workspace(dir, package) {
+                return Err(format!(
+                    "`{}` inherits from a workspace, but the package isn't in one",
+                    key
+                ));
+            }
+        }
+    }
+
+    let mut names = Vec::new();
+    let lib = table(&manifest, "lib")?;
+    let has_lib = lib.is_some() || dir.join("src/lib.rs").is_file();
+    if has_lib {
+        let lib_name = lib
+            .and_then(|lib| lib.get("name")?.as_str())
+            .unwrap_or(name);
+        let crate_types = lib
+            .and_then(|lib| {
+                lib.get("crate-type")
+                    .or_else(|| lib.get("crate_type"))?
+                    .as_array()
+            })
+            .into_iter()
+            .flatten();
+        for crate_type in crate_types.filter_map(|t| t.as_str()) {
+            names.extend(file_names(crate_type, lib_name));
+        }
+    }
+
+    let mut bins: Vec<String> = manifest
+        .get("bin")
+        .and_then(|bins| bins.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|bin| Some(bin.get("name")?.as_str()?.to_string()))
+        .collect();
+    if package.get("autobins").and_then(|a| a.as_bool()) != Some(false) {
+        bins.extend(inferred_bins(dir, name));
+    }
+    if !has_lib && bins.is_empty() && !has_other_targets(dir, &manifest, package) {
+        return Err("no targets specified in the manifest".to_string());
+    }
+    for bin in &bins {
+        names.extend(file_names("bin", bin));
+    }
+    Ok(names)
+}
+
+fn read_manifest(dir: &Path) -> Result<toml::Table, String> {
+    let contents = fs::read_to_string(dir.join("Cargo.toml"))
+        .map_err(|e| format!("failed to read Cargo.toml: {}", e))?;
+    contents.parse::<toml::Table>().map_err(|e| {
+        let message = e.to_string();
+        format!(
+            "failed to parse manifest: {}",
+            message.lines().last().unwrap_or("invalid TOML").trim()
+        )
+    })
+}
+
+/// The table at `key`, if present; anything else there is an error
+fn table<'a>(manifest: &'a toml::Table, key: &str) -> Result<Option<&'a toml::Table>, String> {
+    match manifest.get(key) {
+        None => Ok(None),
+        Some(value) => value
+            .as_table()
+            .map(Some)
+            .ok_or_else(|| format!("`{}` must be a table", key)),
+    }
+}
+
+/// The binaries Cargo finds on its own: `src/main.rs`, `src/bin/*.rs` and `src/bin/*/main.rs`
+fn inferred_bins(dir: &Path, package_name: &str) -> Vec<String> {
+    let mut bins = Vec::new();
+    if dir.join("src/main.rs").is_file() {
+        bins.push(package_name.to_string());
+    }
+    bins.extend(inferred_targets(&dir.join("src/bin")));
+    bins
+}
+
+/// The targets Cargo finds on its own in `target_dir`: `*.rs` files and `*/main.rs`
+fn inferred_targets(target_dir: &Path) -> Vec<String> {
+    let mut targets = Vec::new();
+    for entry in fs::read_dir(target_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        let name = if path.is_dir() {
+            path.join("main.rs").is_file().then(|| path.file_name())
+        } else {
+            (path.extension().is_some_and(|ext| ext == "rs")).then(|| path.file_stem())
+        };
+        if let Some(name) = name.flatten().and_then(|n| n.to_str()) {
+            targets.push(name.to_string());
+        }
+    }
+    targets
+}
+
+/// Whether the package has examples, tests or benches, listed or found in `examples/`,
+/// `tests/` and `benches/`; Cargo accepts a package with only those as targets
+fn has_other_targets(dir: &Path, manifest: &toml::Table, package: &toml::Table) -> bool {
+    [
+        ("example", "autoexamples", "examples"),
+        ("test", "autotests", "tests"),
+        ("bench", "autobenches", "benches"),
+    ]
+    .iter()
+    .any(|(key, auto, target_dir)| {
+        let listed = manifest
+            .get(*key)
+            .and_then(|targets| targets.as_array())
+            .is_some_and(|targets| !targets.is_empty());
+        let inferred = package.get(*auto).and_then(|a| a.as_bool()) != Some(false)
+            && !inferred_targets(&dir.join(target_dir)).is_empty();
+        listed || inferred
+    })
+}
+
+/// The first `package` field or dependency inherited with `workspace = true`, if any
+fn inherited_key(manifest: &toml::Table) -> Option<String> {
+    let inherits =
+        |value: &toml::Value| value.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+
+    let sections = [
+        "package",
+        "dependencies",
+        "dev-dependencies",
+        "build-dependencies",
+    ];
+    let mut tables: Vec<(String, &toml::Table)> = sections
+        .iter()
+        .filter_map(|section| Some((section.to_string(), manifest.get(*section)?.as_table()?)))
+        .collect();
+    let targets = manifest
+        .get("target")
+        .and_then(|t| t.as_table())
+        .into_iter()
+        .flatten();
+    for (platform, target) in targets {
+        for section in &sections[1..] {
+            if let Some(deps) = target.get(*section).and_then(|d| d.as_table()) {
+                tables.push((format!("target.{}.{}", platform, section), deps));
+            }
+        }
+    }
+
+    if manifest.get("lints").is_some_and(inherits) {
+        return Some("lints".to_string());
+    }
+    tables.into_iter().find_map(|(section, table)| {
+        table
+            .iter()
+            .find(|(_, value)| inherits(value))
+            .map(|(key, _)| format!("{}.{}", section, key))
+    })
+}
+
+/// Returns true if a `[workspace]` is found at `package.workspace` or above `dir`
+fn in_workspace(dir: &Path, package: &toml::Table) -> bool {
+    let has_workspace =
+        |root: &Path| read_manifest(root).is_ok_and(|manifest| manifest.contains_key("workspace"));
+    match package.get("workspace").and_then(|w| w.as_str()) {
+        Some(root) => has_workspace(&dir.join(root)),
+        None => dir.ancestors().skip(1).any(has_workspace),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_manifest_structure_and_final_artifacts() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let check = |dir: &str| Validation::default().validate(&root.join(dir), &[]);
+
+        write(
+            &root.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[lib]\ncrate-type = [\"cdylib\", \"rlib\"]\n",
+        );
+        write(&root.join("app/src/main.rs"), "fn main() {}");
+        write(&root.join("app/src/bin/tool.rs"), "fn main() {}");
+        write(&root.join("app/src/bin/server/main.rs"), "fn main() {}");
+        let mut expected: Vec<String> = ["app", "server", "tool"]
+            .iter()
+            .flat_map(|bin| file_names("bin", bin))
+            .chain(file_names("cdylib", "app"))
+            .collect();
+        expected.sort();
+        assert_eq!(
+            check("app"),
+            Ok(ValidProject {
+                final_artifacts: expected
+            })
+        );
+
+        write(
+            &root.join("no-targets/Cargo.toml"),
+            "[package]\nname = \"no-targets\"\n",
+        );
+        assert_eq!(
+            check("no-targets"),
+            Err("no targets specified in the manifest".to_string())
+        );
+
+        // Examples, tests and benches are targets too, listed or found on their own
+        for kind in ["example", "test", "bench"] {
+            let package = format!("{}-only", kind);
+            write(
+                &root.join(&package).join("Cargo.toml"),
+                &format!(
+                    "[package]\nname = \"{}\"\n\n[[{}]]\nname = \"demo\"\npath = \"demo.rs\"\n",
+                    package, kind
+                ),
+            );
+            assert_eq!(check(&package), Ok(ValidProject::default()));
+        }
+        for (package, target) in [
+            ("built-examples", "examples/demo.rs"),
+            ("built-tests", "tests/smoke/main.rs"),
+            ("built-benches", "benches/speed.rs"),
+        ] {
+            write(
+                &root.join(package).join("Cargo.toml"),
+                &format!("[package]\nname = \"{}\"\nbuild = \"build.rs\"\n", package),
+            );
+            write(&root.join(package).join("build.rs"), "fn main() {}");
+            write(&root.join(package).join(target), "fn main() {}");
+            assert_eq!(check(package), Ok(ValidProject::default()));
+        }
+
+        write(
+            &root.join("broken/Cargo.toml"),
+            "[package\nname = \"broken\"\n",
+        );
+        assert!(check("broken")
+            .unwrap_err()
+            .starts_with("failed to parse manifest"));
+
+        write(
+            &root.join("orphan/Cargo.toml"),
+            "[package]\nname = \"orphan\"\nversion.workspace = true\n",
+        );
+        write(&root.join("orphan/src/lib.rs"), "");
+        assert_eq!(
+            check("orphan"),
+            Err(
+                "`package.version` inherits from a workspace, but the package isn't in one"
+                    .to_string()
+            )
+        );
+
+        write(
+            &root.join("ws/Cargo.toml"),
+            "[workspace]\nmembers = [\"member\", \"crates/*\"]\n",
+        );
+        write(
+            &root.join("ws/member/Cargo.toml"),
+            "[package]\nname = \"member\"\nversion.workspace = true\n",
+        );
+        write(&root.join("ws/member/src/lib.rs"), "");
+        assert_eq!(check("ws/member"), Ok(ValidProject::default()));
+        assert_eq!(check("ws"), Ok(ValidProject::default()));
+        fs::remove_dir_all(root.join("ws/member")).unwrap();
+        assert_eq!(
+            check("ws"),
+            Err("failed to load manifest for workspace member `member`".to_string())
+        );
+    }
+}
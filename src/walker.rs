@@ -1,47 +1,227 @@
 use anyhow::Result;
 use ignore::WalkBuilder;
+use std::collections::{BTreeMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use crate::cleaner::{is_cargo_nix_dir, is_next_dir, is_node_modules_dir, is_python_venv_dir, is_rustup_dir, is_sccache_dir, is_stack_work_dir};
+use crate::artifacts::{ArtifactKind, Registry, SuspiciousPath};
+use crate::cleaner::{is_cargo_target_dir, is_rust_target_dir};
+use crate::ignore_rules::{ExcludedPath, IgnoreRules};
+use crate::quarantine::{self, QuarantineStore};
+use crate::rustup;
+use crate::sccache;
+use crate::target_dir::{self, CustomTarget};
+use crate::workspace;
 
 pub struct DiscoveredPaths {
+    /// Standalone packages and workspace roots
     pub projects: Vec<PathBuf>,
-    pub orphaned_targets: Vec<PathBuf>,
-    pub node_modules: Vec<PathBuf>,
-    pub python_venvs: Vec<PathBuf>,
-    pub sccache_dirs: Vec<PathBuf>,
-    pub stack_work_dirs: Vec<PathBuf>,
-    pub rustup_dirs: Vec<PathBuf>,
-    pub next_dirs: Vec<PathBuf>,
-    pub cargo_nix_dirs: Vec<PathBuf>,
+    /// Members of each workspace root in `projects`, cleaned along with their root
+    pub workspaces: BTreeMap<PathBuf, Vec<PathBuf>>,
+    /// Validated artifacts, keyed by the kind of the detector that found them
+    pub artifacts: BTreeMap<ArtifactKind, Vec<PathBuf>>,
+    /// Candidates that passed validation but are excluded by a `.wd40ignore` file
+    pub excluded: Vec<ExcludedPath>,
+    /// `rust-toolchain` files seen, whose toolchains pruning a rustup directory keeps
+    pub toolchain_files: Vec<PathBuf>,
+    /// Candidates a detector only half recognized (see `Detector::suspicion`), left alone
+    pub suspicious: Vec<SuspiciousPath>,
 }
 
-/// Finds all directories containing a Cargo.toml file by walking the given directory
+impl DiscoveredPaths {
+    /// Returns the artifacts found for a kind (empty if none were found)
+    pub fn get(&self, kind: ArtifactKind) -> &[PathBuf] {
+        self.artifacts.get(&kind).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Removes and returns the artifacts found for a kind
+    pub fn take(&mut self, kind: ArtifactKind) -> Vec<PathBuf> {
+        self.artifacts.remove(&kind).unwrap_or_default()
+    }
+
+    /// Members of the workspace rooted at `project` (empty for a standalone package)
+    pub fn members(&self, project: &Path) -> &[PathBuf] {
+        self.workspaces.get(project).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Every project (as `ArtifactKind::RUST_PROJECT`) and artifact found
+    pub fn iter(&self) -> impl Iterator<Item = (ArtifactKind, &Path)> {
+        let projects = self
+            .projects
+            .iter()
+            .map(|path| (ArtifactKind::RUST_PROJECT, path.as_path()));
+        let artifacts = self
+            .artifacts
+            .iter()
+            .flat_map(|(kind, paths)| paths.iter().map(move |path| (*kind, path.as_path())));
+        projects.chain(artifacts)
+    }
+
+    /// Keeps only the projects and artifacts for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(ArtifactKind, &Path) -> bool) {
+        self.projects.retain(|path| keep(ArtifactKind::RUST_PROJECT, path));
+        let projects = &self.projects;
+        self.workspaces.retain(|root, _| projects.contains(root));
+        for (kind, paths) in self.artifacts.iter_mut() {
+            paths.retain(|path| keep(*kind, path));
+        }
+        self.artifacts.retain(|_, paths| !paths.is_empty());
+    }
+
+    /// Drops everything whose kind is not in `kinds`
+    pub fn select(&mut self, kinds: &[ArtifactKind]) {
+        self.retain(|kind, _| kinds.contains(&kind));
+    }
+}
+
+/// Records a validated candidate, or notes which ignore file excluded it
+fn record<T>(
+    found: &Mutex<Vec<T>>,
+    excluded: &Mutex<Vec<ExcludedPath>>,
+    rules: &IgnoreRules,
+    path: &Path,
+    item: impl FnOnce() -> T,
+) {
+    if let Some(ignore_file) = rules.excluded_by(path) {
+        if let Ok(mut excluded) = excluded.lock() {
+            excluded.push(ExcludedPath {
+                path: path.to_path_buf(),
+                ignore_file,
+            });
+        }
+    } else if let Ok(mut found) = found.lock() {
+        found.push(item());
+    }
+}
+
+/// Returns true if `path` lies strictly inside any of `roots`
+fn is_nested_in(path: &Path, roots: &HashSet<PathBuf>) -> bool {
+    path.ancestors().skip(1).any(|ancestor| roots.contains(ancestor))
+}
+
+/// Sorts out target directories configured with `build.target-dir` or `CARGO_TARGET_DIR`
+///
+/// Nothing found inside one (such as the packages `cargo package` leaves there) is a project or
+/// artifact of its own, and one named `target` isn't orphaned just because no Cargo.toml sits
+/// next to it. Shared targets under `root` become a single `ArtifactKind::SHARED_TARGET` each;
+/// one outside it is left alone, since it also holds builds of projects that weren't searched.
+fn claim_custom_targets(
+    root: &Path,
+    registry: &Registry,
+    projects: &mut Vec<PathBuf>,
+    artifacts: &mut BTreeMap<ArtifactKind, Vec<PathBuf>>,
+    excluded: &mut Vec<ExcludedPath>,
+    rules: &IgnoreRules,
+) {
+    let custom: Vec<CustomTarget> = projects.iter().filter_map(|project| target_dir::resolve(project)).collect();
+    let targets: HashSet<PathBuf> = custom.iter().map(|target| target.path().to_path_buf()).collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    projects.retain(|project| !is_nested_in(project, &targets));
+    for (kind, paths) in artifacts.iter_mut() {
+        let claimed = |path: &PathBuf| *kind == ArtifactKind::ORPHANED_TARGET && targets.contains(path);
+        paths.retain(|path| !is_nested_in(path, &targets) && !claimed(path));
+    }
+    artifacts.retain(|_, paths| !paths.is_empty());
+
+    if registry.get(ArtifactKind::SHARED_TARGET).is_none() {
+        return;
+    }
+    // A workspace root's own config is seen from its members as one above them, but the target
+    // it sets is cleaned with the root
+    let owned: HashSet<&Path> = custom
+        .iter()
+        .filter_map(|target| match target {
+            CustomTarget::Project(path) => Some(path.as_path()),
+            CustomTarget::Shared(_) => None,
+        })
+        .collect();
+    let mut shared: Vec<PathBuf> = custom
+        .iter()
+        .filter_map(|target| match target {
+            CustomTarget::Shared(path) if !owned.contains(path.as_path()) => Some(path.clone()),
+            _ => None,
+        })
+        .filter(|path| path.starts_with(root) && is_cargo_target_dir(path))
+        .collect();
+    shared.sort();
+    shared.dedup();
+
+    for path in shared {
+        if let Some(ignore_file) = rules.excluded_by(&path) {
+            excluded.push(ExcludedPath { path, ignore_file });
+        } else {
+            artifacts.entry(ArtifactKind::SHARED_TARGET).or_default().push(path);
+        }
+    }
+}
+
+/// Adds the cache sccache is configured to use (`SCCACHE_DIR` or its config file), whatever it's
+/// called and wherever it is; the walker only recognizes caches named `.sccache` under the root
+fn claim_sccache_dir(
+    registry: &Registry,
+    artifacts: &mut BTreeMap<ArtifactKind, Vec<PathBuf>>,
+    excluded: &mut Vec<ExcludedPath>,
+    rules: &IgnoreRules,
+) {
+    let Some(detector) = registry.get(ArtifactKind::SCCACHE) else {
+        return;
+    };
+    let Some(path) = sccache::configured_dir() else {
+        return;
+    };
+    let found = artifacts.values().flatten().any(|artifact| path.starts_with(artifact));
+    if found || !(detector.validate)(&path) {
+        return;
+    }
+
+    if let Some(ignore_file) = rules.excluded_by(&path) {
+        excluded.push(ExcludedPath { path, ignore_file });
+    } else {
+        artifacts.entry(ArtifactKind::SCCACHE).or_default().push(path);
+    }
+}
+
+/// Finds Cargo projects (standalone packages and workspace roots) by walking the given directory
 pub fn find_cargo_projects(root: &Path) -> Result<Vec<PathBuf>> {
     let discovered = find_all_rust_artifacts(root)?;
     Ok(discovered.projects)
 }
 
-/// Finds both Cargo projects and orphaned target directories
+/// Finds Cargo projects and every artifact type in the built-in registry
 pub fn find_all_rust_artifacts(root: &Path) -> Result<DiscoveredPaths> {
+    find_artifacts(root, &Registry::builtin())
+}
+
+/// Finds Cargo projects and every artifact type known to `registry`
+pub fn find_artifacts(root: &Path, registry: &Registry) -> Result<DiscoveredPaths> {
     // Thread-safe collections for results
     let projects = Arc::new(Mutex::new(Vec::new()));
-    let orphaned_targets = Arc::new(Mutex::new(Vec::new()));
-    let node_modules = Arc::new(Mutex::new(Vec::new()));
-    let python_venvs = Arc::new(Mutex::new(Vec::new()));
-    let sccache_dirs = Arc::new(Mutex::new(Vec::new()));
-    let stack_work_dirs = Arc::new(Mutex::new(Vec::new()));
-    let rustup_dirs = Arc::new(Mutex::new(Vec::new()));
-    let next_dirs = Arc::new(Mutex::new(Vec::new()));
-    let cargo_nix_dirs = Arc::new(Mutex::new(Vec::new()));
+    let artifacts = Arc::new(Mutex::new(Vec::new()));
+    let excluded = Arc::new(Mutex::new(Vec::new()));
+    let toolchain_files = Arc::new(Mutex::new(Vec::new()));
+    let suspicious = Arc::new(Mutex::new(Vec::new()));
+
+    // .wd40ignore files are evaluated per candidate (rather than by the walker's own
+    // ignore handling) so we can report which file excluded what
+    let rules = Arc::new(IgnoreRules::new());
+    let registry = Arc::new(registry.clone());
+
+    // Quarantined artifacts are only ever restored or purged, never cleaned again
+    let quarantine_store = QuarantineStore::default_location()
+        .ok()
+        .map(|store| store.root().canonicalize().unwrap_or_else(|_| store.root().to_path_buf()));
 
     // Build the parallel walker
     // Use ignore crate ONLY for parallel walking performance (like ripgrep)
-    // Disable ALL gitignore filtering - we rely on validation functions instead:
-    // - is_rust_target_dir() checks for CACHEDIR.TAG/.rustc_info.json
-    // - is_node_modules_dir() checks for package.json + structure
-    // - is_python_venv_dir() checks for pyvenv.cfg + activation + lib
+    // Disable ALL gitignore filtering - we rely on each detector's validation function instead
     let walker = WalkBuilder::new(root)
+        .filter_entry(move |entry| {
+            let path = entry.path();
+            path.file_name().and_then(|n| n.to_str()) != Some(quarantine::AREA_DIR_NAME)
+                && quarantine_store.as_deref() != Some(path)
+        })
         .follow_links(false)
         .git_ignore(false)        // Don't filter based on .gitignore
         .git_global(false)         // Don't filter based on global gitignore
@@ -53,25 +233,21 @@ pub fn find_all_rust_artifacts(root: &Path) -> Result<DiscoveredPaths> {
 
     // Walk directories in parallel
     let projects_clone = Arc::clone(&projects);
-    let orphaned_clone = Arc::clone(&orphaned_targets);
-    let node_modules_clone = Arc::clone(&node_modules);
-    let python_venvs_clone = Arc::clone(&python_venvs);
-    let sccache_dirs_clone = Arc::clone(&sccache_dirs);
-    let stack_work_dirs_clone = Arc::clone(&stack_work_dirs);
-    let rustup_dirs_clone = Arc::clone(&rustup_dirs);
-    let next_dirs_clone = Arc::clone(&next_dirs);
-    let cargo_nix_dirs_clone = Arc::clone(&cargo_nix_dirs);
+    let artifacts_clone = Arc::clone(&artifacts);
+    let excluded_clone = Arc::clone(&excluded);
+    let toolchain_files_clone = Arc::clone(&toolchain_files);
+    let suspicious_clone = Arc::clone(&suspicious);
+    let rules_clone = Arc::clone(&rules);
+    let registry_clone = Arc::clone(&registry);
 
     walker.run(move || {
         let projects = Arc::clone(&projects_clone);
-        let orphaned_targets = Arc::clone(&orphaned_clone);
-        let node_modules = Arc::clone(&node_modules_clone);
-        let python_venvs = Arc::clone(&python_venvs_clone);
-        let sccache_dirs = Arc::clone(&sccache_dirs_clone);
-        let stack_work_dirs = Arc::clone(&stack_work_dirs_clone);
-        let rustup_dirs = Arc::clone(&rustup_dirs_clone);
-        let next_dirs = Arc::clone(&next_dirs_clone);
-        let cargo_nix_dirs = Arc::clone(&cargo_nix_dirs_clone);
+        let artifacts = Arc::clone(&artifacts_clone);
+        let excluded = Arc::clone(&excluded_clone);
+        let toolchain_files = Arc::clone(&toolchain_files_clone);
+        let suspicious = Arc::clone(&suspicious_clone);
+        let rules = Arc::clone(&rules_clone);
+        let registry = Arc::clone(&registry_clone);
 
         Box::new(move |result| {
             use ignore::WalkState;
@@ -83,95 +259,54 @@ pub fn find_all_rust_artifacts(root: &Path) -> Result<DiscoveredPaths> {
                 if path.is_file() && path.file_name().and_then(|n| n.to_str()) == Some("Cargo.toml") {
                     // Get the parent directory (the project root)
                     if let Some(project_dir) = path.parent() {
-                        if let Ok(mut projects) = projects.lock() {
-                            projects.push(project_dir.to_path_buf());
-                        }
+                        record(&projects, &excluded, &rules, project_dir, || project_dir.to_path_buf());
+                    }
+                }
+                // Toolchains pinned anywhere in the tree stay installed
+                else if path.is_file()
+                    && path.file_name().and_then(|n| n.to_str()).is_some_and(|name| rustup::TOOLCHAIN_FILES.contains(&name))
+                {
+                    if let Ok(mut toolchain_files) = toolchain_files.lock() {
+                        toolchain_files.push(path.to_path_buf());
                     }
                 }
                 // Check if this is a directory
                 else if path.is_dir() {
                     let dir_name = path.file_name().and_then(|n| n.to_str());
 
-                    // Check if this is a potentially orphaned target directory
-                    // Support "target" and "target-ra" (rust-analyzer cache)
-                    let is_target_dir = dir_name.map_or(false, |name| {
-                        name == "target" || name == "target-ra"
-                    });
-
-                    if is_target_dir {
-                        // Verify it's a Rust target by checking for markers
-                        let has_cachedir_tag = path.join("CACHEDIR.TAG").exists();
-                        let has_rustc_info = path.join(".rustc_info.json").exists();
-
-                        if has_cachedir_tag || has_rustc_info {
-                            // Check if parent has Cargo.toml - if not, it's orphaned
-                            if let Some(parent) = path.parent() {
-                                if !parent.join("Cargo.toml").exists() {
-                                    if let Ok(mut orphaned) = orphaned_targets.lock() {
-                                        orphaned.push(path.to_path_buf());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    // Check if this is a node_modules directory
-                    else if dir_name == Some("node_modules") {
-                        if is_node_modules_dir(path) {
-                            if let Ok(mut nm) = node_modules.lock() {
-                                nm.push(path.to_path_buf());
-                            }
-                        }
-                    }
-                    // Check if this is an sccache directory
-                    else if dir_name == Some(".sccache") {
-                        if is_sccache_dir(path) {
-                            if let Ok(mut sccache) = sccache_dirs.lock() {
-                                sccache.push(path.to_path_buf());
-                            }
-                        }
-                    }
-                    // Check if this is a Stack work directory
-                    else if dir_name == Some(".stack-work") {
-                        if is_stack_work_dir(path) {
-                            if let Ok(mut stack_work) = stack_work_dirs.lock() {
-                                stack_work.push(path.to_path_buf());
-                            }
-                        }
+                    // Git internals never contain anything we clean
+                    if dir_name == Some(".git") {
+                        return WalkState::Skip;
                     }
-                    // Check if this is a rustup directory
-                    else if dir_name == Some(".rustup") {
-                        if is_rustup_dir(path) {
-                            if let Ok(mut rustup) = rustup_dirs.lock() {
-                                rustup.push(path.to_path_buf());
-                            }
-                        }
-                    }
-                    // Check if this is a Next.js build directory
-                    else if dir_name == Some(".next") {
-                        if is_next_dir(path) {
-                            if let Ok(mut next) = next_dirs.lock() {
-                                next.push(path.to_path_buf());
-                            }
-                        }
+
+                    // Let the first detector that recognizes the directory claim it, and
+                    // don't descend: nothing inside an artifact is reported separately
+                    if let Some(detector) = registry.detect(path) {
+                        record(&artifacts, &excluded, &rules, path, || (detector.kind, path.to_path_buf()));
+                        return WalkState::Skip;
                     }
-                    // Check if this is a cargo-nix directory
-                    else if dir_name == Some(".cargo-nix") {
-                        if is_cargo_nix_dir(path) {
-                            if let Ok(mut cargo_nix) = cargo_nix_dirs.lock() {
-                                cargo_nix.push(path.to_path_buf());
-                            }
-                        }
+
+                    // One that looks the part but fails the stricter checks is reported instead
+                    let suspicion = dir_name.and_then(|name| {
+                        registry
+                            .detectors()
+                            .iter()
+                            .filter(|detector| detector.matches_name(name))
+                            .find_map(|detector| Some((detector.kind, detector.suspicion(path)?)))
+                    });
+                    if let Some((kind, reason)) = suspicion {
+                        record(&suspicious, &excluded, &rules, path, || SuspiciousPath {
+                            kind,
+                            path: path.to_path_buf(),
+                            reason,
+                        });
+                        return WalkState::Skip;
                     }
-                    // Check if this is a Python venv directory
-                    else if let Some(name) = dir_name {
-                        let venv_names = ["venv", ".venv", "env", "ENV", "virtualenv", ".virtualenv"];
-                        if venv_names.contains(&name) {
-                            if is_python_venv_dir(path) {
-                                if let Ok(mut venvs) = python_venvs.lock() {
-                                    venvs.push(path.to_path_buf());
-                                }
-                            }
-                        }
+
+                    // A project's own target is cleaned through the project, but is just
+                    // as pointless to walk
+                    if is_rust_target_dir(path) {
+                        return WalkState::Skip;
                     }
                 }
             }
@@ -181,60 +316,61 @@ pub fn find_all_rust_artifacts(root: &Path) -> Result<DiscoveredPaths> {
     });
 
     // Extract the results from the mutexes
-    let projects = Arc::try_unwrap(projects)
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
-        .into_inner()
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
-
-    let orphaned_targets = Arc::try_unwrap(orphaned_targets)
+    let mut projects = Arc::try_unwrap(projects)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner()
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
 
-    let node_modules = Arc::try_unwrap(node_modules)
+    let found: Vec<(ArtifactKind, PathBuf)> = Arc::try_unwrap(artifacts)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner()
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
 
-    let python_venvs = Arc::try_unwrap(python_venvs)
+    let mut excluded = Arc::try_unwrap(excluded)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner()
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
 
-    let sccache_dirs = Arc::try_unwrap(sccache_dirs)
+    let mut toolchain_files: Vec<PathBuf> = Arc::try_unwrap(toolchain_files)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner()
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
 
-    let stack_work_dirs = Arc::try_unwrap(stack_work_dirs)
+    let mut suspicious: Vec<SuspiciousPath> = Arc::try_unwrap(suspicious)
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
         .into_inner()
         .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
 
-    let rustup_dirs = Arc::try_unwrap(rustup_dirs)
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
-        .into_inner()
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
+    // Skipping detected directories already keeps nested artifacts out of a single walk;
+    // enforce it here too so overlap never depends on traversal details
+    let roots: HashSet<PathBuf> = found.iter().map(|(_, path)| path.clone()).collect();
+    projects.retain(|project| !is_nested_in(project, &roots));
 
-    let next_dirs = Arc::try_unwrap(next_dirs)
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
-        .into_inner()
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
-
-    let cargo_nix_dirs = Arc::try_unwrap(cargo_nix_dirs)
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Arc"))?
-        .into_inner()
-        .map_err(|_| anyhow::anyhow!("Failed to unwrap Mutex"))?;
+    // The parallel walk visits entries in no particular order; sort for stable output
+    let mut artifacts: BTreeMap<ArtifactKind, Vec<PathBuf>> = BTreeMap::new();
+    for (kind, path) in found {
+        if !is_nested_in(&path, &roots) {
+            artifacts.entry(kind).or_default().push(path);
+        }
+    }
+    projects.sort();
+    claim_custom_targets(root, registry.as_ref(), &mut projects, &mut artifacts, &mut excluded, &rules);
+    claim_sccache_dir(registry.as_ref(), &mut artifacts, &mut excluded, &rules);
+    for paths in artifacts.values_mut() {
+        paths.sort();
+    }
+    let workspaces = workspace::group_members(&mut projects);
+    excluded.sort_by(|a, b| a.path.cmp(&b.path));
+    toolchain_files.sort();
+    suspicious.retain(|entry| !is_nested_in(&entry.path, &roots));
+    suspicious.sort_by(|a, b| a.path.cmp(&b.path));
 
     Ok(DiscoveredPaths {
         projects,
-        orphaned_targets,
-        node_modules,
-        python_venvs,
-        sccache_dirs,
-        stack_work_dirs,
-        rustup_dirs,
-        next_dirs,
-        cargo_nix_dirs,
+        workspaces,
+        artifacts,
+        excluded,
+        toolchain_files,
+        suspicious,
     })
 }
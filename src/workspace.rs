@@ -0,0 +1,243 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The parts of a Cargo.toml that decide workspace membership
+#[derive(Debug, Default)]
+struct Manifest {
+    /// Present if the manifest has a `[workspace]` table
+    workspace: Option<Patterns>,
+    /// `package.workspace`: an explicit path to the workspace root
+    workspace_root: Option<PathBuf>,
+}
+
+/// `workspace.members` and `workspace.exclude`, relative to the workspace root
+#[derive(Debug)]
+struct Patterns {
+    members: GlobSet,
+    exclude: Vec<PathBuf>,
+}
+
+impl Manifest {
+    /// Reads a project's Cargo.toml; unreadable or invalid manifests count as plain packages
+    fn read(project_dir: &Path) -> Manifest {
+        let Some(table) = fs::read_to_string(project_dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|contents| contents.parse::<toml::Table>().ok())
+        else {
+            return Manifest::default();
+        };
+
+        let strings = |table: &toml::Table, key: &str| -> Vec<String> {
+            table
+                .get(key)
+                .and_then(|value| value.as_array())
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let workspace = table
+            .get("workspace")
+            .and_then(|w| w.as_table())
+            .map(|workspace| {
+                let mut members = GlobSetBuilder::new();
+                for pattern in strings(workspace, "members") {
+                    // `*` stays within one path component, as in Cargo
+                    let pattern = normalize(Path::new(&pattern));
+                    if let Ok(glob) = GlobBuilder::new(&pattern.to_string_lossy())
+                        .literal_separator(true)
+                        .build()
+                    {
+                        members.add(glob);
+                    }
+                }
+                Patterns {
+                    members: members.build().unwrap_or_else(|_| GlobSet::empty()),
+                    exclude: strings(workspace, "exclude")
+                        .iter()
+                        .map(|path| normalize(Path::new(path)))
+                        .collect(),
+                }
+            });
+
+        let workspace_root = table
+            .get("package")
+            .and_then(|package| package.get("workspace"))
+            .and_then(|root| root.as_str())
+            .map(|root| normalize(&project_dir.join(root)));
+
+        Manifest {
+            workspace,
+            workspace_root,
+        }
+    }
+}
+
+impl Patterns {
+    /// Returns true if the package at `relative` (to the root) is listed in `members`
+    fn has_member(&self, relative: &Path) -> bool {
+        self.members.is_match(relative)
+    }
+
+    /// Returns true if `relative` is, or is inside, an excluded path
+    fn excludes(&self, relative: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|excluded| relative.starts_with(excluded))
+    }
+}
+
+/// Folds workspace members into their workspace root
+///
+/// Members are removed from `projects` and returned keyed by the root they belong to,
+/// which stays in `projects` and is cleaned (and validated) once for the whole workspace.
+/// Only roots among `projects` are considered, so nothing outside the search is pulled in.
+/// Like Cargo, a package belongs to the nearest workspace above it, unless its manifest names a
+/// root with `package.workspace`. The search stops at the first workspace that excludes it, which
+/// leaves the package on its own even if a workspace further up lists it.
+pub fn group_members(projects: &mut Vec<PathBuf>) -> BTreeMap<PathBuf, Vec<PathBuf>> {
+    let manifests: HashMap<PathBuf, Manifest> = projects
+        .iter()
+        .map(|project| (project.clone(), Manifest::read(project)))
+        .collect();
+
+    let mut members: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for project in projects.iter() {
+        if let Some(root) = workspace_root(project, &manifests) {
+            members
+                .entry(root.to_path_buf())
+                .or_default()
+                .push(project.clone());
+        }
+    }
+
+    let grouped: HashSet<PathBuf> = members.values().flatten().cloned().collect();
+    projects.retain(|project| !grouped.contains(project));
+    members
+}
+
+/// Finds the discovered workspace root `project` is a member of, if any (never itself)
+fn workspace_root<'a>(
+    project: &Path,
+    manifests: &'a HashMap<PathBuf, Manifest>,
+) -> Option<&'a Path> {
+    let manifest = manifests.get(project)?;
+    if manifest.workspace.is_some() {
+        return None;
+    }
+
+    if let Some(explicit) = &manifest.workspace_root {
+        return manifests
+            .get_key_value(explicit)
+            .filter(|(_, root)| root.workspace.is_some())
+            .map(|(path, _)| path.as_path());
+    }
+
+    for ancestor in project.ancestors().skip(1) {
+        let Some((root, patterns)) = manifests
+            .get_key_value(ancestor)
+            .and_then(|(root, manifest)| Some((root, manifest.workspace.as_ref()?)))
+        else {
+            continue;
+        };
+
+        let relative = project.strip_prefix(root).ok()?;
+        if patterns.excludes(relative) {
+            return None;
+        }
+        // Cargo refuses to build a package under a workspace that doesn't list it;
+        // leave those alone so they are validated (and reported) on their own
+        return patterns.has_member(relative).then_some(root.as_path());
+    }
+    None
+}
+
+/// Resolves `.` and `..` without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_manifest(dir: &Path, contents: &str) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("Cargo.toml"), contents).unwrap();
+        dir.to_path_buf()
+    }
+
+    #[test]
+    fn test_group_members_under_workspace_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let workspace = write_manifest(
+            &root.join("ws"),
+            "[workspace]\nmembers = [\"crates/*\", \"tools/cli\"]\nexclude = [\"crates/scratch\"]\n",
+        );
+        let core = write_manifest(
+            &workspace.join("crates/core"),
+            "[package]\nname = \"core\"\n",
+        );
+        let macros = write_manifest(
+            &workspace.join("crates/macros"),
+            "[package]\nname = \"macros\"\n",
+        );
+        let cli = write_manifest(&workspace.join("tools/cli"), "[package]\nname = \"cli\"\n");
+        let scratch = write_manifest(
+            &workspace.join("crates/scratch"),
+            "[package]\nname = \"scratch\"\n",
+        );
+        let explicit = write_manifest(
+            &root.join("elsewhere/plugin"),
+            "[package]\nname = \"plugin\"\nworkspace = \"../../ws\"\n",
+        );
+        let standalone = write_manifest(&root.join("app"), "[package]\nname = \"app\"\n");
+
+        // A workspace nested in another owns its own members, and a package excluded by the
+        // nearest workspace isn't picked up by one further out
+        let outer = write_manifest(root, "[workspace]\nmembers = [\"ws/crates/scratch\"]\n");
+        let nested = write_manifest(
+            &workspace.join("crates/nested"),
+            "[workspace]\nmembers = [\"sub\"]\n",
+        );
+        let sub = write_manifest(&nested.join("sub"), "[package]\nname = \"sub\"\n");
+
+        let mut projects = vec![
+            outer.clone(),
+            workspace.clone(),
+            core.clone(),
+            macros.clone(),
+            nested.clone(),
+            sub.clone(),
+            cli.clone(),
+            scratch.clone(),
+            explicit.clone(),
+            standalone.clone(),
+        ];
+        let members = group_members(&mut projects);
+
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[&workspace], [core, macros, cli, explicit]);
+        assert_eq!(members[&nested], [sub]);
+        assert_eq!(projects, [outer, workspace, nested, scratch, standalone]);
+    }
+}
@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use wd_40::artifacts::ArtifactKind;
 use std::process::Command;
 use tempfile::TempDir;
 
@@ -116,26 +117,26 @@ fn test_walker_finds_all_artifacts() {
     // Verify counts
     // Note: rust-nix-project-1 has a Cargo.toml so it's counted as a Rust project too
     assert!(discovered.projects.len() >= 3, "Expected at least 3 Rust projects, found {}", discovered.projects.len());
-    assert_eq!(discovered.orphaned_targets.len(), 1, "Expected 1 orphaned target");
+    assert_eq!(discovered.get(ArtifactKind::ORPHANED_TARGET).len(), 1, "Expected 1 orphaned target");
     // Note: npm can create nested node_modules (e.g., send/node_modules), so we check >= 2
-    assert!(discovered.node_modules.len() >= 2, "Expected at least 2 node_modules directories, found {}", discovered.node_modules.len());
-    assert_eq!(discovered.python_venvs.len(), 2, "Expected 2 Python venvs");
-    assert_eq!(discovered.sccache_dirs.len(), 2, "Expected 2 sccache directories");
-    assert_eq!(discovered.stack_work_dirs.len(), 2, "Expected 2 Stack work directories");
-    assert_eq!(discovered.rustup_dirs.len(), 2, "Expected 2 rustup directories");
-    assert_eq!(discovered.next_dirs.len(), 2, "Expected 2 Next.js build directories");
-    assert_eq!(discovered.cargo_nix_dirs.len(), 2, "Expected 2 cargo-nix directories");
+    assert!(discovered.get(ArtifactKind::NODE_MODULES).len() >= 2, "Expected at least 2 node_modules directories, found {}", discovered.get(ArtifactKind::NODE_MODULES).len());
+    assert_eq!(discovered.get(ArtifactKind::PYTHON_VENV).len(), 2, "Expected 2 Python venvs");
+    assert_eq!(discovered.get(ArtifactKind::SCCACHE).len(), 2, "Expected 2 sccache directories");
+    assert_eq!(discovered.get(ArtifactKind::STACK_WORK).len(), 2, "Expected 2 Stack work directories");
+    assert_eq!(discovered.get(ArtifactKind::RUSTUP).len(), 2, "Expected 2 rustup directories");
+    assert_eq!(discovered.get(ArtifactKind::NEXT).len(), 2, "Expected 2 Next.js build directories");
+    assert_eq!(discovered.get(ArtifactKind::CARGO_NIX).len(), 2, "Expected 2 cargo-nix directories");
 
     println!("✓ Walker correctly discovered all artifacts:");
     println!("  - {} Rust projects", discovered.projects.len());
-    println!("  - {} orphaned targets", discovered.orphaned_targets.len());
-    println!("  - {} node_modules", discovered.node_modules.len());
-    println!("  - {} Python venvs", discovered.python_venvs.len());
-    println!("  - {} sccache dirs", discovered.sccache_dirs.len());
-    println!("  - {} Stack work dirs", discovered.stack_work_dirs.len());
-    println!("  - {} rustup dirs", discovered.rustup_dirs.len());
-    println!("  - {} Next.js builds", discovered.next_dirs.len());
-    println!("  - {} cargo-nix dirs", discovered.cargo_nix_dirs.len());
+    println!("  - {} orphaned targets", discovered.get(ArtifactKind::ORPHANED_TARGET).len());
+    println!("  - {} node_modules", discovered.get(ArtifactKind::NODE_MODULES).len());
+    println!("  - {} Python venvs", discovered.get(ArtifactKind::PYTHON_VENV).len());
+    println!("  - {} sccache dirs", discovered.get(ArtifactKind::SCCACHE).len());
+    println!("  - {} Stack work dirs", discovered.get(ArtifactKind::STACK_WORK).len());
+    println!("  - {} rustup dirs", discovered.get(ArtifactKind::RUSTUP).len());
+    println!("  - {} Next.js builds", discovered.get(ArtifactKind::NEXT).len());
+    println!("  - {} cargo-nix dirs", discovered.get(ArtifactKind::CARGO_NIX).len());
 }
 
 #[test]
@@ -164,7 +165,6 @@ fn test_clean_rust_projects() {
         let result = wd_40::cleaner::clean_project(
             project,
             false, // dry_run
-            false, // verbose
             false, // force
             false, // strict
         ).expect("Failed to clean project");
@@ -197,9 +197,9 @@ fn test_clean_orphaned_targets() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.orphaned_targets.len(), 1, "Expected 1 orphaned target");
+    assert_eq!(discovered.get(ArtifactKind::ORPHANED_TARGET).len(), 1, "Expected 1 orphaned target");
 
-    let orphaned = &discovered.orphaned_targets[0];
+    let orphaned = &discovered.get(ArtifactKind::ORPHANED_TARGET)[0];
     assert!(dir_exists(orphaned), "Orphaned target should exist before cleaning");
 
     // Clean the orphaned target using the specific orphaned function
@@ -225,16 +225,16 @@ fn test_clean_node_modules() {
         .expect("Failed to find artifacts");
 
     // Note: npm can create nested node_modules, so we check >= 2
-    assert!(discovered.node_modules.len() >= 2, "Expected at least 2 node_modules directories");
+    assert!(discovered.get(ArtifactKind::NODE_MODULES).len() >= 2, "Expected at least 2 node_modules directories");
 
     // Verify they exist before cleaning
-    for nm in &discovered.node_modules {
+    for nm in discovered.get(ArtifactKind::NODE_MODULES) {
         assert!(dir_exists(nm), "node_modules should exist before cleaning: {:?}", nm);
     }
 
     // Clean each node_modules
     let mut cleaned_count = 0;
-    for nm in &discovered.node_modules {
+    for nm in discovered.get(ArtifactKind::NODE_MODULES) {
         let result = wd_40::cleaner::delete_node_modules_dir(nm, false)
             .expect("Failed to delete node_modules");
 
@@ -244,7 +244,7 @@ fn test_clean_node_modules() {
     }
 
     // Verify they were removed
-    for nm in &discovered.node_modules {
+    for nm in discovered.get(ArtifactKind::NODE_MODULES) {
         assert!(!dir_exists(nm), "node_modules should not exist after cleaning: {:?}", nm);
     }
 
@@ -265,16 +265,16 @@ fn test_clean_python_venvs() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.python_venvs.len(), 2, "Expected 2 Python venvs");
+    assert_eq!(discovered.get(ArtifactKind::PYTHON_VENV).len(), 2, "Expected 2 Python venvs");
 
     // Verify they exist before cleaning
-    for venv in &discovered.python_venvs {
+    for venv in discovered.get(ArtifactKind::PYTHON_VENV) {
         assert!(dir_exists(venv), "Python venv should exist before cleaning: {:?}", venv);
     }
 
     // Clean each venv
     let mut cleaned_count = 0;
-    for venv in &discovered.python_venvs {
+    for venv in discovered.get(ArtifactKind::PYTHON_VENV) {
         let result = wd_40::cleaner::delete_venv_dir(venv, false)
             .expect("Failed to delete venv");
 
@@ -284,7 +284,7 @@ fn test_clean_python_venvs() {
     }
 
     // Verify they were removed
-    for venv in &discovered.python_venvs {
+    for venv in discovered.get(ArtifactKind::PYTHON_VENV) {
         assert!(!dir_exists(venv), "Python venv should not exist after cleaning: {:?}", venv);
     }
 
@@ -305,16 +305,16 @@ fn test_clean_sccache_dirs() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.sccache_dirs.len(), 2, "Expected 2 sccache directories");
+    assert_eq!(discovered.get(ArtifactKind::SCCACHE).len(), 2, "Expected 2 sccache directories");
 
     // Verify they exist before cleaning
-    for sccache in &discovered.sccache_dirs {
+    for sccache in discovered.get(ArtifactKind::SCCACHE) {
         assert!(dir_exists(sccache), "sccache directory should exist before cleaning: {:?}", sccache);
     }
 
     // Clean each sccache directory
     let mut cleaned_count = 0;
-    for sccache in &discovered.sccache_dirs {
+    for sccache in discovered.get(ArtifactKind::SCCACHE) {
         let result = wd_40::cleaner::delete_sccache_dir(sccache, false)
             .expect("Failed to delete sccache directory");
 
@@ -324,7 +324,7 @@ fn test_clean_sccache_dirs() {
     }
 
     // Verify they were removed
-    for sccache in &discovered.sccache_dirs {
+    for sccache in discovered.get(ArtifactKind::SCCACHE) {
         assert!(!dir_exists(sccache), "sccache directory should not exist after cleaning: {:?}", sccache);
     }
 
@@ -345,16 +345,16 @@ fn test_clean_stack_work_dirs() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.stack_work_dirs.len(), 2, "Expected 2 Stack work directories");
+    assert_eq!(discovered.get(ArtifactKind::STACK_WORK).len(), 2, "Expected 2 Stack work directories");
 
     // Verify they exist before cleaning
-    for stack_work in &discovered.stack_work_dirs {
+    for stack_work in discovered.get(ArtifactKind::STACK_WORK) {
         assert!(dir_exists(stack_work), "Stack work directory should exist before cleaning: {:?}", stack_work);
     }
 
     // Clean each Stack work directory
     let mut cleaned_count = 0;
-    for stack_work in &discovered.stack_work_dirs {
+    for stack_work in discovered.get(ArtifactKind::STACK_WORK) {
         let result = wd_40::cleaner::delete_stack_work_dir(stack_work, false)
             .expect("Failed to delete Stack work directory");
 
@@ -364,7 +364,7 @@ fn test_clean_stack_work_dirs() {
     }
 
     // Verify they were removed
-    for stack_work in &discovered.stack_work_dirs {
+    for stack_work in discovered.get(ArtifactKind::STACK_WORK) {
         assert!(!dir_exists(stack_work), "Stack work directory should not exist after cleaning: {:?}", stack_work);
     }
 
@@ -385,16 +385,16 @@ fn test_clean_rustup_dirs() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.rustup_dirs.len(), 2, "Expected 2 rustup directories");
+    assert_eq!(discovered.get(ArtifactKind::RUSTUP).len(), 2, "Expected 2 rustup directories");
 
     // Verify they exist before cleaning
-    for rustup in &discovered.rustup_dirs {
+    for rustup in discovered.get(ArtifactKind::RUSTUP) {
         assert!(dir_exists(rustup), "rustup directory should exist before cleaning: {:?}", rustup);
     }
 
     // Clean each rustup directory
     let mut cleaned_count = 0;
-    for rustup in &discovered.rustup_dirs {
+    for rustup in discovered.get(ArtifactKind::RUSTUP) {
         let result = wd_40::cleaner::delete_rustup_dir(rustup, false)
             .expect("Failed to delete rustup directory");
 
@@ -404,7 +404,7 @@ fn test_clean_rustup_dirs() {
     }
 
     // Verify they were removed
-    for rustup in &discovered.rustup_dirs {
+    for rustup in discovered.get(ArtifactKind::RUSTUP) {
         assert!(!dir_exists(rustup), "rustup directory should not exist after cleaning: {:?}", rustup);
     }
 
@@ -425,16 +425,16 @@ fn test_clean_next_dirs() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.next_dirs.len(), 2, "Expected 2 Next.js build directories");
+    assert_eq!(discovered.get(ArtifactKind::NEXT).len(), 2, "Expected 2 Next.js build directories");
 
     // Verify they exist before cleaning
-    for next in &discovered.next_dirs {
+    for next in discovered.get(ArtifactKind::NEXT) {
         assert!(dir_exists(next), "Next.js build directory should exist before cleaning: {:?}", next);
     }
 
     // Clean each Next.js directory
     let mut cleaned_count = 0;
-    for next in &discovered.next_dirs {
+    for next in discovered.get(ArtifactKind::NEXT) {
         let result = wd_40::cleaner::delete_next_dir(next, false)
             .expect("Failed to delete Next.js directory");
 
@@ -444,7 +444,7 @@ fn test_clean_next_dirs() {
     }
 
     // Verify they were removed
-    for next in &discovered.next_dirs {
+    for next in discovered.get(ArtifactKind::NEXT) {
         assert!(!dir_exists(next), "Next.js build directory should not exist after cleaning: {:?}", next);
     }
 
@@ -465,16 +465,16 @@ fn test_clean_cargo_nix_dirs() {
     let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
         .expect("Failed to find artifacts");
 
-    assert_eq!(discovered.cargo_nix_dirs.len(), 2, "Expected 2 cargo-nix directories");
+    assert_eq!(discovered.get(ArtifactKind::CARGO_NIX).len(), 2, "Expected 2 cargo-nix directories");
 
     // Verify they exist before cleaning
-    for cargo_nix in &discovered.cargo_nix_dirs {
+    for cargo_nix in discovered.get(ArtifactKind::CARGO_NIX) {
         assert!(dir_exists(cargo_nix), "cargo-nix directory should exist before cleaning: {:?}", cargo_nix);
     }
 
     // Clean each cargo-nix directory
     let mut cleaned_count = 0;
-    for cargo_nix in &discovered.cargo_nix_dirs {
+    for cargo_nix in discovered.get(ArtifactKind::CARGO_NIX) {
         let result = wd_40::cleaner::delete_cargo_nix_dir(cargo_nix, false)
             .expect("Failed to delete cargo-nix directory");
 
@@ -484,7 +484,7 @@ fn test_clean_cargo_nix_dirs() {
     }
 
     // Verify they were removed
-    for cargo_nix in &discovered.cargo_nix_dirs {
+    for cargo_nix in discovered.get(ArtifactKind::CARGO_NIX) {
         assert!(!dir_exists(cargo_nix), "cargo-nix directory should not exist after cleaning: {:?}", cargo_nix);
     }
 
@@ -507,16 +507,16 @@ fn test_full_cleanup_all_artifacts() {
 
     // Count total artifacts before cleanup
     let total_before = discovered.projects.len()
-        + discovered.orphaned_targets.len()
-        + discovered.node_modules.len()
-        + discovered.python_venvs.len();
+        + discovered.get(ArtifactKind::ORPHANED_TARGET).len()
+        + discovered.get(ArtifactKind::NODE_MODULES).len()
+        + discovered.get(ArtifactKind::PYTHON_VENV).len();
 
     println!("Found {} total artifacts before cleanup", total_before);
 
     // Clean Rust projects
     let mut rust_cleaned = 0;
     for project in &discovered.projects {
-        let result = wd_40::cleaner::clean_project(project, false, false, false, false)
+        let result = wd_40::cleaner::clean_project(project, false, false, false)
             .expect("Failed to clean project");
         if result.is_success() {
             rust_cleaned += 1;
@@ -525,7 +525,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean orphaned targets
     let mut orphaned_cleaned = 0;
-    for orphaned in &discovered.orphaned_targets {
+    for orphaned in discovered.get(ArtifactKind::ORPHANED_TARGET) {
         if wd_40::cleaner::delete_orphaned_target_dir(orphaned, false).ok().flatten().is_some() {
             orphaned_cleaned += 1;
         }
@@ -533,7 +533,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean node_modules
     let mut node_cleaned = 0;
-    for nm in &discovered.node_modules {
+    for nm in discovered.get(ArtifactKind::NODE_MODULES) {
         if wd_40::cleaner::delete_node_modules_dir(nm, false).ok().flatten().is_some() {
             node_cleaned += 1;
         }
@@ -541,7 +541,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean Python venvs
     let mut venv_cleaned = 0;
-    for venv in &discovered.python_venvs {
+    for venv in discovered.get(ArtifactKind::PYTHON_VENV) {
         if wd_40::cleaner::delete_venv_dir(venv, false).ok().flatten().is_some() {
             venv_cleaned += 1;
         }
@@ -549,7 +549,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean sccache directories
     let mut sccache_cleaned = 0;
-    for sccache in &discovered.sccache_dirs {
+    for sccache in discovered.get(ArtifactKind::SCCACHE) {
         if wd_40::cleaner::delete_sccache_dir(sccache, false).ok().flatten().is_some() {
             sccache_cleaned += 1;
         }
@@ -557,7 +557,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean Stack work directories
     let mut stack_work_cleaned = 0;
-    for stack_work in &discovered.stack_work_dirs {
+    for stack_work in discovered.get(ArtifactKind::STACK_WORK) {
         if wd_40::cleaner::delete_stack_work_dir(stack_work, false).ok().flatten().is_some() {
             stack_work_cleaned += 1;
         }
@@ -565,7 +565,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean rustup directories
     let mut rustup_cleaned = 0;
-    for rustup in &discovered.rustup_dirs {
+    for rustup in discovered.get(ArtifactKind::RUSTUP) {
         if wd_40::cleaner::delete_rustup_dir(rustup, false).ok().flatten().is_some() {
             rustup_cleaned += 1;
         }
@@ -573,7 +573,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean Next.js build directories
     let mut next_cleaned = 0;
-    for next in &discovered.next_dirs {
+    for next in discovered.get(ArtifactKind::NEXT) {
         if wd_40::cleaner::delete_next_dir(next, false).ok().flatten().is_some() {
             next_cleaned += 1;
         }
@@ -581,7 +581,7 @@ fn test_full_cleanup_all_artifacts() {
 
     // Clean cargo-nix directories
     let mut cargo_nix_cleaned = 0;
-    for cargo_nix in &discovered.cargo_nix_dirs {
+    for cargo_nix in discovered.get(ArtifactKind::CARGO_NIX) {
         if wd_40::cleaner::delete_cargo_nix_dir(cargo_nix, false).ok().flatten().is_some() {
             cargo_nix_cleaned += 1;
         }
@@ -775,3 +775,1188 @@ fn test_validation_prevents_false_positives() {
 
     println!("✓ Validation correctly rejects false positives");
 }
+
+#[test]
+fn test_wd40ignore_excludes_artifacts() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path();
+
+    // Three orphaned targets: one unprotected, one protected, one re-included by a nested file
+    for dir in ["scratch/target", "vendor/lib/target", "vendor/keep/target"] {
+        let target = test_path.join(dir);
+        std::fs::create_dir_all(target.join("debug")).expect("Failed to create target");
+        std::fs::write(target.join("CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55")
+            .expect("Failed to write CACHEDIR.TAG");
+    }
+    std::fs::write(test_path.join(".wd40ignore"), "# protected trees\nvendor/\n")
+        .expect("Failed to write .wd40ignore");
+    std::fs::write(test_path.join("vendor/.wd40ignore"), "!keep/\n")
+        .expect("Failed to write nested .wd40ignore");
+
+    let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
+        .expect("Failed to find artifacts");
+
+    let mut found = discovered.get(ArtifactKind::ORPHANED_TARGET).to_vec();
+    found.sort();
+    assert_eq!(found, vec![test_path.join("scratch/target"), test_path.join("vendor/keep/target")]);
+
+    assert_eq!(discovered.excluded.len(), 1, "Expected 1 excluded candidate");
+    assert_eq!(discovered.excluded[0].path, test_path.join("vendor/lib/target"));
+    assert_eq!(discovered.excluded[0].ignore_file, test_path.join(".wd40ignore"));
+
+    // The cleaner re-checks ignore files at delete time, even when handed the path directly
+    let protected = test_path.join("vendor/lib/target");
+    let result = wd_40::cleaner::delete_orphaned_target_dir(&protected, false)
+        .expect("Delete should not error");
+    assert!(result.is_none(), "Protected target should not be deleted");
+    assert!(dir_exists(&protected), "Protected target should still exist");
+
+    println!("✓ .wd40ignore files protect matching artifacts");
+}
+
+/// Validation for the in-house detector used in test_custom_detector_registry
+fn is_test_cache_dir(path: &Path) -> bool {
+    path.join("CACHE_MARKER").exists()
+}
+
+#[test]
+fn test_custom_detector_registry() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path();
+
+    let cache = test_path.join("tool/.tool-cache");
+    std::fs::create_dir_all(&cache).expect("Failed to create cache");
+    std::fs::write(cache.join("CACHE_MARKER"), "").expect("Failed to write marker");
+    std::fs::write(cache.join("blob"), "0123456789").expect("Failed to write blob");
+
+    // Same name, but fails validation
+    std::fs::create_dir_all(test_path.join("other/.tool-cache")).expect("Failed to create decoy");
+
+    let mut registry = wd_40::artifacts::Registry::builtin();
+    registry.register(wd_40::artifacts::Detector {
+        kind: ArtifactKind("tool-cache"),
+        dir_names: &[".tool-cache"],
+        validate: is_test_cache_dir,
+        icon: "T",
+        log_tag: "TOOL_CACHE",
+        markers: &["CACHE_MARKER"],
+        noun: ("tool cache", "tool caches"),
+    });
+
+    let discovered = wd_40::walker::find_artifacts(test_path, &registry)
+        .expect("Failed to find artifacts");
+    assert_eq!(discovered.get(ArtifactKind("tool-cache")), std::slice::from_ref(&cache));
+
+    let detector = registry.find_by_id("tool-cache").expect("Detector should be registered");
+    let freed = wd_40::cleaner::delete_artifact(detector, &cache, false)
+        .expect("Failed to delete tool cache");
+    assert!(freed.is_some_and(|bytes| bytes >= 10), "Expected size of deleted cache, got {:?}", freed);
+    assert!(!dir_exists(&cache), "Tool cache should be removed");
+
+    println!("✓ Custom detectors plug into discovery and cleaning");
+}
+
+#[test]
+fn test_walker_prunes_nested_artifacts() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path();
+
+    // node_modules containing a package that has its own package.json and node_modules
+    let project = test_path.join("web");
+    let outer = project.join("node_modules");
+    let inner = outer.join("send/node_modules");
+    std::fs::create_dir_all(inner.join("ms")).expect("Failed to create nested node_modules");
+    std::fs::write(project.join("package.json"), "{}").expect("Failed to write package.json");
+    std::fs::write(outer.join("send/package.json"), "{}").expect("Failed to write package.json");
+    std::fs::write(outer.join(".package-lock.json"), "{}").expect("Failed to write lockfile");
+    assert!(wd_40::cleaner::is_node_modules_dir(&inner), "Nested node_modules validates on its own");
+
+    // A crate vendored inside node_modules must not show up as a project
+    std::fs::write(outer.join("send/Cargo.toml"), "[package]").expect("Failed to write Cargo.toml");
+
+    // Something that looks like an orphaned target inside .git is never reported
+    let git_target = test_path.join("repo/.git/target");
+    std::fs::create_dir_all(&git_target).expect("Failed to create .git/target");
+    std::fs::write(git_target.join("CACHEDIR.TAG"), "").expect("Failed to write CACHEDIR.TAG");
+
+    let discovered = wd_40::walker::find_all_rust_artifacts(test_path)
+        .expect("Failed to find artifacts");
+
+    assert_eq!(discovered.get(ArtifactKind::NODE_MODULES), std::slice::from_ref(&outer));
+    assert!(discovered.get(ArtifactKind::ORPHANED_TARGET).is_empty(), "Nothing under .git should be found");
+    assert!(discovered.projects.is_empty(), "Crates inside node_modules are not projects");
+
+    println!("✓ Walker reports only outermost artifacts and skips .git");
+}
+
+#[test]
+fn test_dry_run_reports_real_sizes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path();
+
+    let project = test_path.join("web");
+    let node_modules = project.join("node_modules");
+    std::fs::create_dir_all(node_modules.join("left-pad")).expect("Failed to create node_modules");
+    std::fs::write(project.join("package.json"), "{}").expect("Failed to write package.json");
+    std::fs::write(node_modules.join(".package-lock.json"), "{}").expect("Failed to write lockfile");
+    std::fs::write(node_modules.join("left-pad/index.js"), vec![b'x'; 4096]).expect("Failed to write module");
+
+    let registry = wd_40::artifacts::Registry::builtin();
+    let detector = registry.get(ArtifactKind::NODE_MODULES).expect("Built-in detector");
+
+    let estimate = wd_40::cleaner::delete_artifact(detector, &node_modules, true)
+        .expect("Dry run should not fail");
+    assert!(estimate.is_some_and(|bytes| bytes >= 4096), "Expected a real size estimate, got {:?}", estimate);
+    assert!(dir_exists(&node_modules), "Dry run must not delete anything");
+
+    println!("✓ Dry run estimates the space each artifact would free");
+}
+
+/// Sets the modification time of `root` and everything below it to `days` ago
+fn age_tree(root: &Path, days: u64) {
+    let when = std::time::SystemTime::now() - std::time::Duration::from_secs(days * 24 * 60 * 60);
+    let when = filetime::FileTime::from_system_time(when);
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(path) = stack.pop() {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(&path).expect("Failed to read dir") {
+                stack.push(entry.expect("Failed to read entry").path());
+            }
+        }
+        filetime::set_file_mtime(&path, when).expect("Failed to set mtime");
+    }
+}
+
+#[test]
+fn test_older_than_keeps_recently_used_artifacts() {
+    use wd_40::age::{AgeFilter, AgeSource};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path();
+
+    for name in ["stale", "active"] {
+        let node_modules = test_path.join(name).join("node_modules");
+        std::fs::create_dir_all(node_modules.join("left-pad")).expect("Failed to create node_modules");
+        std::fs::write(test_path.join(name).join("package.json"), "{}").expect("Failed to write package.json");
+    }
+    age_tree(&test_path.join("stale"), 60);
+    age_tree(&test_path.join("active"), 60);
+
+    // Editing a source file makes the whole project count as in use
+    std::fs::write(test_path.join("active/index.js"), "").expect("Failed to write source");
+
+    let registry = wd_40::artifacts::Registry::builtin();
+    let mut discovered = wd_40::walker::find_artifacts(test_path, &registry)
+        .expect("Failed to find artifacts");
+    assert_eq!(discovered.get(ArtifactKind::NODE_MODULES).len(), 2);
+
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+
+    // Judged only by its contents, the active project's node_modules is stale too
+    let by_artifact = AgeFilter::new(30 * day, AgeSource::Artifact, true, &registry);
+    assert!(by_artifact.decide(ArtifactKind::NODE_MODULES, &test_path.join("active/node_modules"), &[], std::time::SystemTime::now()).selected);
+
+    let filter = AgeFilter::new(30 * day, AgeSource::Both, false, &registry);
+    let decisions = filter.apply(&mut discovered);
+    assert_eq!(decisions.len(), 2);
+    assert!(decisions.iter().all(|d| d.age.is_some()), "Every item should get an age");
+    assert_eq!(
+        discovered.get(ArtifactKind::NODE_MODULES),
+        [test_path.join("stale/node_modules")],
+        "Only the untouched project's node_modules should remain selected"
+    );
+
+    println!("✓ --older-than keeps recently used artifacts");
+}
+
+#[test]
+fn test_workspace_age_covers_member_targets() {
+    use wd_40::age::{AgeFilter, AgeSource};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let workspace = temp_dir.path().join("ws");
+    let member = workspace.join("crates/core");
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    write(workspace.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n");
+    write(workspace.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(member.join("Cargo.toml"), "[package]\nname = \"core\"\n");
+    write(member.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    age_tree(&workspace, 60);
+
+    // A build of the member alone, into its own target directory, counts as using the workspace
+    write(member.join("target/debug/core"), "fresh binary");
+
+    let registry = wd_40::artifacts::Registry::builtin();
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+    let filter = AgeFilter::new(30 * day, AgeSource::Artifact, false, &registry);
+    let now = std::time::SystemTime::now();
+    assert!(filter.decide(ArtifactKind::RUST_PROJECT, &workspace, &[], now).selected);
+    assert!(!filter.decide(ArtifactKind::RUST_PROJECT, &workspace, &[member], now).selected);
+
+    println!("✓ A workspace's age covers its members' target directories");
+}
+
+#[test]
+fn test_quarantine_restore_and_purge() {
+    use wd_40::cleaner::{remove_artifact, Removal};
+    use wd_40::quarantine::QuarantineStore;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path();
+    let store = QuarantineStore::new(test_path.join("quarantine"));
+
+    let project = test_path.join("web");
+    let node_modules = project.join("node_modules");
+    std::fs::create_dir_all(node_modules.join("patched-dep")).expect("Failed to create node_modules");
+    std::fs::write(project.join("package.json"), "{}").expect("Failed to write package.json");
+    std::fs::write(node_modules.join("patched-dep/index.js"), "// hand-patched").expect("Failed to write module");
+
+    let registry = wd_40::artifacts::Registry::builtin();
+    let detector = registry.get(ArtifactKind::NODE_MODULES).expect("Built-in detector");
+
+    // Quarantining moves the directory out of the way and records it
+    let quarantine = store.begin("run-1").expect("Failed to start quarantine");
+    let moved = remove_artifact(detector, &node_modules, Removal::Quarantine(&quarantine))
+        .expect("Failed to quarantine");
+    assert!(moved.is_some_and(|bytes| bytes > 0));
+    assert!(!dir_exists(&node_modules), "node_modules should be moved away");
+    let manifests = store.manifests().expect("Failed to read manifests");
+    assert_eq!(manifests.len(), 1);
+    assert_eq!(manifests[0].entries[0].original, node_modules);
+
+    // Restoring by run id puts it back untouched
+    let restored = store.restore("run-1").expect("Failed to restore");
+    assert_eq!(restored.entries.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(node_modules.join("patched-dep/index.js")).expect("Restored file should exist"),
+        "// hand-patched"
+    );
+    assert!(store.manifests().expect("Failed to read manifests").is_empty());
+
+    // Purging deletes quarantined artifacts for good
+    let quarantine = store.begin("run-2").expect("Failed to start quarantine");
+    let quarantined = quarantine.quarantine(&node_modules).expect("Failed to quarantine");
+    let young = store
+        .purge(Some(std::time::Duration::from_secs(7 * 24 * 60 * 60)), false)
+        .expect("Failed to purge");
+    assert!(young.is_empty(), "A fresh run is not older than 7 days");
+    let purged = store.purge(None, false).expect("Failed to purge");
+    assert_eq!(purged.len(), 1);
+    assert!(!quarantined.exists(), "Purged artifacts should be gone");
+    assert!(store.restore("run-2").is_err(), "Nothing is left to restore");
+
+    println!("✓ Quarantine moves, restores and purges artifacts");
+}
+
+#[test]
+fn test_workspace_is_cleaned_as_one_unit() {
+    use wd_40::cleaner::{clean_workspace_with, Removal};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let workspace = temp_dir.path().join("ws");
+
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    write(workspace.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\nresolver = \"2\"\n");
+    for name in ["alpha", "beta"] {
+        let member = workspace.join("crates").join(name);
+        write(
+            member.join("Cargo.toml"),
+            &format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n", name),
+        );
+        write(member.join("src/lib.rs"), "");
+    }
+    // The shared target at the root, and a stale one from before alpha joined the workspace
+    write(workspace.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(workspace.join("target/debug/libalpha.rlib"), "shared build output");
+    write(workspace.join("crates/alpha/target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+
+    let discovered = wd_40::walker::find_all_rust_artifacts(temp_dir.path()).expect("Failed to find artifacts");
+    assert_eq!(discovered.projects.len(), 1, "Members are folded into the workspace root");
+    assert_eq!(discovered.projects[0], workspace);
+    assert_eq!(
+        discovered.members(&workspace),
+        [workspace.join("crates/alpha"), workspace.join("crates/beta")]
+    );
+
+    let result = clean_workspace_with(&workspace, discovered.members(&workspace), Removal::Delete, false, false)
+        .expect("Failed to clean workspace");
+    assert!(result.is_success(), "Workspace should validate once at its root: {:?}", result.status);
+    assert_eq!(result.members.len(), 2);
+    assert!(!dir_exists(&workspace.join("target")), "Shared target should be deleted");
+    assert!(!dir_exists(&workspace.join("crates/alpha/target")), "Stale member target should be deleted");
+    assert!(workspace.join("crates/alpha/src/lib.rs").exists(), "Sources are untouched");
+
+    println!("✓ Workspaces are validated and cleaned as one unit");
+}
+
+#[test]
+fn test_custom_target_dirs_are_honored() {
+    use wd_40::cleaner::{clean_project_with, remove_artifact, Removal};
+
+    // CARGO_TARGET_DIR overrides every config file below
+    if std::env::var_os("CARGO_TARGET_DIR").is_some() || std::env::var_os("CARGO_BUILD_TARGET_DIR").is_some() {
+        return;
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path();
+
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    let package = |dir: &Path, name: &str| {
+        write(
+            dir.join("Cargo.toml"),
+            &format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n", name),
+        );
+        write(dir.join("src/lib.rs"), "");
+    };
+
+    // Everything under the root builds into one shared target, named like an orphan
+    write(root.join(".cargo/config.toml"), "[build]\ntarget-dir = \"cache/target\"\n");
+    write(root.join("cache/target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(root.join("cache/target/package/alpha-0.1.0/Cargo.toml"), "[package]\nname = \"alpha\"\n");
+    package(&root.join("alpha"), "alpha");
+    package(&root.join("beta"), "beta");
+
+    // ...except gamma, which keeps its builds in its own "out" directory
+    package(&root.join("gamma"), "gamma");
+    write(root.join("gamma/.cargo/config.toml"), "[build]\ntarget-dir = \"out\"\n");
+    write(root.join("gamma/out/.rustc_info.json"), "{}");
+    write(root.join("gamma/out/debug/libgamma.rlib"), "build output");
+
+    let discovered = wd_40::walker::find_all_rust_artifacts(root).expect("Failed to find artifacts");
+    assert_eq!(
+        discovered.projects,
+        [root.join("alpha"), root.join("beta"), root.join("gamma")],
+        "Packages inside a target directory aren't projects"
+    );
+    assert_eq!(discovered.get(ArtifactKind::SHARED_TARGET), [root.join("cache/target")]);
+    assert!(discovered.get(ArtifactKind::ORPHANED_TARGET).is_empty(), "A shared target isn't an orphan");
+
+    let result = clean_project_with(&root.join("gamma"), Removal::Delete, false, false).expect("Failed to clean project");
+    assert!(result.is_success(), "Project should be cleaned: {:?}", result.status);
+    assert!(result.space_freed().unwrap_or(0) > 0);
+    assert!(!dir_exists(&root.join("gamma/out")), "Configured target should be deleted");
+
+    let detector = wd_40::artifacts::Registry::builtin();
+    let detector = detector.get(ArtifactKind::SHARED_TARGET).expect("Shared target detector");
+    let freed = remove_artifact(detector, &root.join("cache/target"), Removal::Delete).expect("Failed to clean shared target");
+    assert!(freed.is_some());
+    assert!(!dir_exists(&root.join("cache/target")), "Shared target should be deleted");
+    assert!(root.join("alpha/src/lib.rs").exists(), "Sources are untouched");
+
+    println!("✓ build.target-dir is honored for project and shared targets");
+}
+
+#[test]
+fn test_workspace_root_target_dir_is_claimed_once() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let workspace = temp_dir.path().join("ws");
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    write(workspace.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n");
+    write(workspace.join(".cargo/config.toml"), "[build]\ntarget-dir = \"out\"\n");
+    write(workspace.join("out/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(workspace.join("out/debug/libcore.rlib"), "build output");
+    write(workspace.join("crates/core/Cargo.toml"), "[package]\nname = \"core\"\n");
+    write(workspace.join("crates/core/src/lib.rs"), "");
+
+    // Seen from the members, the root's config is one above them; its target is still the root's
+    let output = Command::new(env!("CARGO_BIN_EXE_wd-40"))
+        .args(["--dry-run", "--format", "json", "--no-dry-run-log", "--force", "--in-use-window", "0s"])
+        .arg(temp_dir.path())
+        .env_remove("CARGO_TARGET_DIR")
+        .env_remove("CARGO_BUILD_TARGET_DIR")
+        .env("CARGO_HOME", temp_dir.path().join("cargo-home"))
+        .output()
+        .expect("Failed to run wd-40");
+    assert!(output.status.success(), "wd-40 failed: {}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("Invalid JSON report");
+    let items: Vec<(&str, &str)> = report["artifacts"]
+        .as_array()
+        .expect("artifacts array")
+        .iter()
+        .filter_map(|artifact| Some((artifact["kind"].as_str()?, artifact["path"].as_str()?)))
+        .collect();
+    assert_eq!(items, [("rust-project", workspace.to_str().unwrap())]);
+
+    println!("✓ A workspace root's own target directory is cleaned once, with the root");
+}
+
+#[test]
+fn test_clean_only_parts_of_target() {
+    use std::collections::BTreeSet;
+    use wd_40::cleaner::{clean_workspace_parts, Removal};
+    use wd_40::target_parts::{TargetPart, TargetParts};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let project = temp_dir.path().join("app");
+    let target = project.join("target");
+
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    write(project.join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n");
+    write(project.join("src/main.rs"), "fn main() {}");
+    write(target.join("CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(target.join("debug/incremental/app-1/query-cache.bin"), "incremental cache");
+    write(target.join("debug/deps/app-1"), "debug binary");
+    write(target.join("release/incremental/app-2/query-cache.bin"), "incremental cache");
+    write(target.join("release/app"), "release binary");
+    write(target.join("doc/app/index.html"), "<html>");
+    write(target.join("criterion/parse/base/estimates.json"), "{}");
+
+    let parts = TargetParts {
+        parts: BTreeSet::from([TargetPart::Incremental, TargetPart::Doc]),
+        ..TargetParts::default()
+    };
+    let result = clean_workspace_parts(&project, &[], &parts, Removal::Delete, true, false)
+        .expect("Failed to clean project");
+
+    assert!(result.is_success(), "Project should be cleaned: {:?}", result.status);
+    let cleaned: Vec<&Path> = result.parts.iter().map(|(path, _)| path.as_path()).collect();
+    assert_eq!(
+        cleaned,
+        [target.join("debug/incremental"), target.join("doc"), target.join("release/incremental")]
+    );
+    assert_eq!(result.space_freed(), Some(result.parts.iter().map(|(_, size)| size).sum()));
+    assert!(target.join("release/app").exists(), "Release binaries are kept");
+    assert!(target.join("debug/deps/app-1").exists(), "Other debug output is kept");
+    assert!(target.join("criterion/parse/base/estimates.json").exists(), "Benchmark baselines are kept");
+    assert!(target.join("CACHEDIR.TAG").exists(), "The target stays recognizable");
+
+    println!("✓ Only the selected parts of a target directory are cleaned");
+}
+
+#[test]
+fn test_sweep_prunes_stale_units_only() {
+    use std::time::{Duration, SystemTime};
+    use wd_40::cleaner::{clean_workspace_parts, Removal};
+    use wd_40::sweep::Sweep;
+    use wd_40::target_parts::TargetParts;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let project = temp_dir.path().join("app");
+    let debug = project.join("target/debug");
+
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    write(project.join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2021\"\n");
+    write(project.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+
+    // serde 1.0.100 was last built two months ago; 1.0.200 is part of the current build
+    let two_months_ago = filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(60 * 24 * 60 * 60));
+    for (hash, stale) in [("0123456789abcdef", true), ("fedcba9876543210", false)] {
+        let fingerprint = debug.join(format!(".fingerprint/serde-{}", hash));
+        let files = [
+            fingerprint.join("lib-serde.json"),
+            fingerprint.join("invoked.timestamp"),
+            debug.join(format!("deps/libserde-{}.rlib", hash)),
+            debug.join(format!("deps/serde-{}.d", hash)),
+            debug.join(format!("build/serde-{}/output", hash)),
+        ];
+        for file in &files {
+            write(file.clone(), "{\"rustc\":1}");
+        }
+        if stale {
+            for path in files.iter().map(PathBuf::as_path).chain([fingerprint.as_path()]) {
+                filetime::set_file_mtime(path, two_months_ago).expect("Failed to set mtime");
+            }
+        }
+    }
+
+    let parts = TargetParts {
+        sweep: Some(Sweep {
+            older_than: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            installed: None,
+        }),
+        ..TargetParts::default()
+    };
+    let result = clean_workspace_parts(&project, &[], &parts, Removal::Delete, true, false)
+        .expect("Failed to sweep project");
+
+    assert!(result.is_success(), "Project should be swept: {:?}", result.status);
+    assert_eq!(result.parts.len(), 1);
+    assert_eq!(result.parts[0].0, debug);
+    assert!(!dir_exists(&debug.join(".fingerprint/serde-0123456789abcdef")));
+    assert!(!debug.join("deps/libserde-0123456789abcdef.rlib").exists());
+    assert!(!dir_exists(&debug.join("build/serde-0123456789abcdef")));
+    assert!(debug.join("deps/libserde-fedcba9876543210.rlib").exists(), "The current build stays warm");
+    assert!(dir_exists(&debug.join(".fingerprint/serde-fedcba9876543210")));
+
+    println!("✓ Sweeping removes only stale units");
+}
+
+#[test]
+fn test_keep_executables_while_cleaning_target() {
+    use wd_40::cleaner::{clean_workspace_parts, Removal};
+    use wd_40::target_parts::TargetParts;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let project = temp_dir.path().join("tool");
+    let target = project.join("target");
+
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    write(
+        project.join("Cargo.toml"),
+        "[package]\nname = \"my-tool\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\"]\n",
+    );
+    write(project.join("src/main.rs"), "fn main() {}");
+    write(project.join("src/lib.rs"), "");
+    write(target.join("CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(target.join("release/my-tool"), "release binary");
+    write(target.join("release/libmy_tool.so"), "shared library");
+    write(target.join("release/deps/my_tool-0123456789abcdef"), "intermediate binary");
+    write(target.join("release/my-tool.d"), "dep info");
+    write(target.join("debug/incremental/my_tool-1/query-cache.bin"), "incremental cache");
+
+    let parts = TargetParts {
+        keep_final_artifacts: true,
+        ..TargetParts::default()
+    };
+    let result = clean_workspace_parts(&project, &[], &parts, Removal::Delete, false, false)
+        .expect("Failed to clean project");
+
+    assert!(result.is_success(), "Project should be cleaned: {:?}", result.status);
+    let preserved: Vec<&Path> = result.preserved.iter().map(|(path, _)| path.as_path()).collect();
+    assert_eq!(preserved, [target.join("release/libmy_tool.so"), target.join("release/my-tool")]);
+    assert_eq!(result.preserved[1].1, "release binary".len() as u64);
+    assert!(target.join("release/my-tool").exists(), "The binary is kept");
+    assert!(target.join("release/libmy_tool.so").exists(), "The cdylib is kept");
+    assert!(!dir_exists(&target.join("release/deps")), "Intermediate output is deleted");
+    assert!(!target.join("release/my-tool.d").exists());
+    assert!(!dir_exists(&target.join("debug")));
+
+    println!("✓ Built executables survive cleaning their target directory");
+}
+
+#[test]
+fn test_validators_and_timeout() {
+    use std::time::Duration;
+    use wd_40::cleaner::{clean_validated, Removal};
+    use wd_40::target_parts::TargetParts;
+    use wd_40::validate::{Validation, Validator};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+
+    // A toolchain file naming a toolchain nobody has doesn't matter to the manifest check
+    let app = temp_dir.path().join("app");
+    write(app.join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\n");
+    write(app.join("src/main.rs"), "fn main() {}");
+    write(app.join("rust-toolchain.toml"), "[toolchain]\nchannel = \"1.0.0-does-not-exist\"\n");
+    let manifest = Validation::default();
+    assert!(manifest.validate(&app, &[]).is_ok());
+
+    // A manifest Cargo would reject still gets its target directory cleaned, but nothing more
+    let broken = temp_dir.path().join("broken");
+    write(broken.join("Cargo.toml"), "[package]\nversion = \"0.1.0\"\n");
+    write(broken.join("src/lib.rs"), "");
+    write(broken.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    let validated = manifest.validate(&broken, &[]);
+    let result = clean_validated(&broken, &[], Some(validated), &TargetParts::default(), Removal::Delete, false, false)
+        .expect("Failed to clean project");
+    assert!(result.is_target_only(), "Only the target should be cleaned: {:?}", result.status);
+    assert!(!dir_exists(&broken.join("target")));
+
+    // cargo is given up on (and killed) once the timeout passes
+    let impatient = Validation {
+        validator: Validator::Cargo,
+        timeout: Duration::ZERO,
+    };
+    assert_eq!(
+        impatient.validate(&app, &[]),
+        Err("cargo metadata timed out after 0s".to_string())
+    );
+
+    println!("✓ Projects are validated in-process, and validators time out");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_artifacts_in_use_are_detected() {
+    use std::time::Duration;
+    use wd_40::in_use::{InUse, Usage};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let venv = temp_dir.path().join("notebook/.venv");
+    std::fs::create_dir_all(venv.join("lib")).expect("Failed to create venv");
+
+    assert_eq!(InUse::scan(Duration::ZERO).check(&venv), None);
+
+    // A kernel running from inside the venv
+    let mut kernel = Command::new("sleep")
+        .arg("30")
+        .current_dir(venv.join("lib"))
+        .spawn()
+        .expect("Failed to start process");
+    let usage = InUse::scan(Duration::ZERO).check(&venv);
+    let _ = kernel.kill();
+    let _ = kernel.wait();
+
+    assert_eq!(
+        usage,
+        Some(Usage::Process {
+            pid: kernel.id(),
+            name: "sleep".to_string()
+        })
+    );
+    assert!(usage.unwrap().reason().starts_with("in use: used by sleep"));
+    assert_eq!(InUse::scan(Duration::ZERO).check(&venv), None, "Free again once the process exits");
+
+    println!("✓ Artifacts in use are detected");
+}
+
+#[test]
+fn test_git_tracked_artifacts_are_refused() {
+    use wd_40::artifacts::Registry;
+    use wd_40::cleaner::{clean_project, remove_artifact, remove_artifact_parts, CleanStatus, Removal};
+    use wd_40::target_parts::TargetParts;
+    use wd_40::tracked::TrackedFiles;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let repo = temp_dir.path();
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    let git = |args: &[&str]| {
+        let status = Command::new("git").arg("-C").arg(repo).args(args).output().expect("Failed to run git").status;
+        assert!(status.success(), "git {:?} failed", args);
+    };
+
+    // A web project that commits one patched dependency, and a Rust project that commits a binary
+    let web = repo.join("web");
+    write(web.join("package.json"), "{}");
+    write(web.join("node_modules/left-pad/index.js"), "patched");
+    write(web.join("node_modules/lodash/index.js"), "installed");
+    let app = repo.join("app");
+    write(app.join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\n");
+    write(app.join("src/main.rs"), "fn main() {}");
+    write(app.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55");
+    write(app.join("target/release/app"), "binary");
+    git(&["init", "-q"]);
+    git(&["add", "web/node_modules/left-pad/index.js", "app/target/release/app"]);
+
+    let registry = Registry::builtin();
+    let detector = registry.get(ArtifactKind::NODE_MODULES).expect("node_modules detector");
+    let node_modules = web.join("node_modules");
+    let error = remove_artifact(detector, &node_modules, Removal::Delete).expect_err("Tracked files must be refused");
+    let tracked = error.downcast_ref::<TrackedFiles>().expect("The refusal names the tracked files");
+    assert_eq!(tracked.files, [node_modules.join("left-pad/index.js")]);
+    assert!(dir_exists(&node_modules.join("lodash")), "Nothing is deleted when refused");
+
+    let result = clean_project(&app, false, false, false).expect("Failed to clean project");
+    assert!(
+        matches!(&result.status, CleanStatus::Refused(reason) if reason.contains("release/app")),
+        "The project should be refused: {:?}",
+        result.status
+    );
+    assert!(app.join("target/release/app").exists());
+
+    // --force removes them anyway
+    let freed = remove_artifact_parts(detector, &node_modules, &TargetParts::default(), Removal::Delete, true)
+        .expect("Failed to force removal");
+    assert!(freed.is_some());
+    assert!(!dir_exists(&node_modules));
+    let result = clean_project(&app, false, true, false).expect("Failed to clean project");
+    assert!(result.is_success());
+    assert!(!dir_exists(&app.join("target")));
+
+    println!("✓ Artifacts holding git-tracked files are refused without --force");
+}
+
+#[test]
+fn test_prune_rustup_keeps_used_toolchains() {
+    use wd_40::cleaner::{prune_rustup_dir, Removal};
+
+    // The environment would pin one more toolchain
+    if std::env::var_os("RUSTUP_TOOLCHAIN").is_some() {
+        return;
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let home = temp_dir.path();
+    let rustup = home.join(".rustup");
+    let host = "x86_64-unknown-linux-gnu";
+    for toolchain in ["stable", "beta", "nightly-2024-01-01", "1.70.0"] {
+        let dir = rustup.join(format!("toolchains/{}-{}/bin", toolchain, host));
+        std::fs::create_dir_all(&dir).expect("Failed to create toolchain");
+        std::fs::write(dir.join("rustc"), "rustc").expect("Failed to write rustc");
+        std::fs::create_dir_all(rustup.join("update-hashes")).expect("Failed to create update-hashes");
+        std::fs::write(rustup.join(format!("update-hashes/{}-{}", toolchain, host)), "hash")
+            .expect("Failed to write update hash");
+    }
+    std::fs::create_dir_all(rustup.join("downloads")).expect("Failed to create downloads");
+    std::fs::write(rustup.join("downloads/abc123.partial"), "partial").expect("Failed to write download");
+    std::fs::create_dir_all(rustup.join("tmp/rustup-tmp")).expect("Failed to create tmp");
+    std::fs::write(rustup.join("settings.toml"), format!("default_toolchain = \"stable-{}\"\n", host))
+        .expect("Failed to write settings");
+
+    // A project pins a dated nightly
+    let project = home.join("src/app");
+    std::fs::create_dir_all(&project).expect("Failed to create project");
+    std::fs::write(project.join("rust-toolchain.toml"), "[toolchain]\nchannel = \"nightly-2024-01-01\"\n")
+        .expect("Failed to write rust-toolchain.toml");
+    let discovered = wd_40::walker::find_artifacts(home, &wd_40::artifacts::Registry::builtin())
+        .expect("Failed to find artifacts");
+    assert_eq!(discovered.toolchain_files, [project.join("rust-toolchain.toml")]);
+
+    let dry_run = prune_rustup_dir(&rustup, &discovered.toolchain_files, Removal::DryRun, false)
+        .expect("Failed to estimate pruning")
+        .expect("The rustup directory should validate");
+    assert_eq!(dry_run.len(), 4, "Two toolchains and two scratch entries: {:?}", dry_run);
+    assert!(dir_exists(&rustup.join(format!("toolchains/beta-{}", host))));
+
+    let removed = prune_rustup_dir(&rustup, &discovered.toolchain_files, Removal::Delete, false)
+        .expect("Failed to prune")
+        .expect("The rustup directory should validate");
+    assert_eq!(removed, dry_run);
+    for kept in ["stable", "nightly-2024-01-01"] {
+        assert!(dir_exists(&rustup.join(format!("toolchains/{}-{}", kept, host))), "{} should stay", kept);
+        assert!(rustup.join(format!("update-hashes/{}-{}", kept, host)).exists());
+    }
+    for pruned in ["beta", "1.70.0"] {
+        assert!(!dir_exists(&rustup.join(format!("toolchains/{}-{}", pruned, host))), "{} should go", pruned);
+        assert!(!rustup.join(format!("update-hashes/{}-{}", pruned, host)).exists());
+    }
+    assert!(rustup.join("settings.toml").exists());
+    assert!(dir_exists(&rustup.join("downloads")) && !rustup.join("downloads/abc123.partial").exists());
+    assert!(dir_exists(&rustup.join("tmp")) && !dir_exists(&rustup.join("tmp/rustup-tmp")));
+
+    println!("✓ Pruning rustup keeps the default and pinned toolchains");
+}
+
+#[test]
+fn test_sccache_trim_keeps_recent_entries() {
+    use filetime::FileTime;
+    use wd_40::cleaner::{trim_sccache_dir, Removal};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path();
+    let write_entry = |cache: &Path, name: &str, size: usize, used: i64| {
+        let path = cache.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create cache directory");
+        std::fs::write(&path, vec![0u8; size]).expect("Failed to write cache entry");
+        let time = FileTime::from_unix_time(used, 0);
+        filetime::set_file_times(&path, time, time).expect("Failed to set times");
+    };
+
+    // A cache named .sccache is trimmed in place, oldest entries first
+    let cache = root.join("project/.sccache");
+    write_entry(&cache, "0/1/old", 4096, 1_000);
+    write_entry(&cache, "2/3/recent", 4096, 3_000);
+    write_entry(&cache, "4/5/middle", 4096, 2_000);
+    let freed = trim_sccache_dir(&cache, 10_000, Removal::DryRun, false).expect("Failed to estimate trim");
+    assert_eq!(freed, Some(4096));
+    assert!(cache.join("0/1/old").exists());
+    let freed = trim_sccache_dir(&cache, 5_000, Removal::Delete, false).expect("Failed to trim");
+    assert_eq!(freed, Some(8192));
+    assert!(!cache.join("0/1/old").exists() && !cache.join("4/5/middle").exists());
+    assert!(cache.join("2/3/recent").exists());
+    assert_eq!(trim_sccache_dir(&cache, 5_000, Removal::Delete, false).unwrap(), Some(0));
+
+    // The cache sccache's config file points at is found whatever it's called
+    let configured = root.join("caches/compiler");
+    write_entry(&configured, "a/b/old", 4096, 1_000);
+    write_entry(&configured, "c/d/recent", 4096, 3_000);
+    let config = root.join("sccache.toml");
+    std::fs::write(&config, format!("[cache.disk]\ndir = {:?}\n", configured.to_str().unwrap()))
+        .expect("Failed to write sccache config");
+    let output = Command::new(env!("CARGO_BIN_EXE_wd-40"))
+        .args(["--only", "sccache", "--sccache-max-size", "5K", "--in-use-window", "0s", "-y", "--log-file"])
+        .arg(root.join("clean.log"))
+        .arg(root)
+        .env_remove("SCCACHE_DIR")
+        .env("SCCACHE_CONF", &config)
+        .env("XDG_CACHE_HOME", root.join("xdg"))
+        .output()
+        .expect("Failed to run wd-40");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "wd-40 failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Found 2 sccache directories"), "Both caches should be found: {}", stdout);
+    assert!(!configured.join("a/b/old").exists());
+    assert!(configured.join("c/d/recent").exists());
+    assert!(cache.join("2/3/recent").exists());
+
+    println!("✓ sccache caches are trimmed to size, least recently used first");
+}
+
+#[test]
+fn test_configured_sccache_dir_outside_root_is_only_trimmed() {
+    use filetime::FileTime;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("src");
+    std::fs::create_dir_all(&root).expect("Failed to create search root");
+    let configured = temp_dir.path().join("caches/compiler");
+    for (name, used) in [("a/b/old", 1_000), ("c/d/recent", 3_000)] {
+        let path = configured.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create cache directory");
+        std::fs::write(&path, vec![0u8; 4096]).expect("Failed to write cache entry");
+        let time = FileTime::from_unix_time(used, 0);
+        filetime::set_file_times(&path, time, time).expect("Failed to set times");
+    }
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_wd-40"))
+            .args(["--only", "sccache", "--in-use-window", "0s", "--format", "json", "--log-file"])
+            .arg(temp_dir.path().join("clean.log"))
+            .args(args)
+            .arg(&root)
+            .env("SCCACHE_DIR", &configured)
+            .env("XDG_CACHE_HOME", temp_dir.path().join("xdg"))
+            .output()
+            .expect("Failed to run wd-40");
+        assert!(output.status.success(), "wd-40 failed: {}", String::from_utf8_lossy(&output.stderr));
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("Invalid JSON report");
+        report["artifacts"].as_array().expect("artifacts array").clone()
+    };
+
+    // Found even though it's outside the searched directory, but never deleted whole
+    let artifacts = run(&["-y"]);
+    assert_eq!(artifacts.len(), 1, "The configured cache should be reported: {:?}", artifacts);
+    assert_eq!(artifacts[0]["path"], configured.to_str().unwrap());
+    assert_eq!(artifacts[0]["status"], "skipped");
+    assert!(configured.join("a/b/old").exists() && configured.join("c/d/recent").exists());
+
+    // Trimmed to size, least recently used first
+    let artifacts = run(&["-y", "--sccache-max-size", "5K"]);
+    assert_eq!(artifacts.len(), 1);
+    assert_eq!(artifacts[0]["status"], "success");
+    assert_eq!(artifacts[0]["size_bytes"], 4096);
+    assert!(!configured.join("a/b/old").exists());
+    assert!(configured.join("c/d/recent").exists());
+
+    println!("✓ A configured sccache cache outside the search root is trimmed, never deleted");
+}
+
+#[test]
+fn test_suspicious_cargo_nix_dirs_are_reported() {
+    use wd_40::cleaner::{delete_cargo_nix_dir, is_cargo_nix_dir, suspicious_cargo_nix_dir};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path();
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+
+    // Real ones: a crate with a cargo-nix target, and a flake with a build result in the store
+    write(root.join("crate/Cargo.toml"), "[package]\nname = \"crate\"\nversion = \"0.1.0\"\n");
+    write(root.join("crate/.cargo-nix/target/artifact.o"), "object");
+    write(root.join("flake/flake.nix"), "{ outputs = { self }: { }; }");
+    std::fs::create_dir_all(root.join("flake/.cargo-nix")).expect("Failed to create .cargo-nix");
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("/nix/store/0c5n0v3b7yq8vrmbnzw2ijs1ykl5hc2x-flake", root.join("flake/.cargo-nix/result"))
+        .expect("Failed to create result link");
+    #[cfg(not(unix))]
+    write(root.join("flake/.cargo-nix/result"), "/nix/store/0c5n0v3b7yq8vrmbnzw2ijs1ykl5hc2x-flake");
+
+    // Lookalikes: no project around it, and a project around something cargo-nix never writes
+    write(root.join("downloads/.cargo-nix/cache.bin"), "data");
+    write(root.join("notes/Cargo.toml"), "[package]\nname = \"notes\"\nversion = \"0.1.0\"\n");
+    write(root.join("notes/.cargo-nix/todo.txt"), "remember the milk");
+
+    let discovered = wd_40::walker::find_artifacts(root, &wd_40::artifacts::Registry::builtin())
+        .expect("Failed to find artifacts");
+    assert_eq!(
+        discovered.get(ArtifactKind::CARGO_NIX),
+        [root.join("crate/.cargo-nix"), root.join("flake/.cargo-nix")]
+    );
+    let suspicious: Vec<(&Path, &str)> = discovered
+        .suspicious
+        .iter()
+        .map(|entry| (entry.path.as_path(), entry.reason.as_str()))
+        .collect();
+    assert_eq!(suspicious.len(), 2, "{:?}", suspicious);
+    assert_eq!(suspicious[0].0, root.join("downloads/.cargo-nix"));
+    assert!(suspicious[0].1.starts_with("suspicious: none of Cargo.toml"), "{}", suspicious[0].1);
+    assert_eq!(suspicious[1].0, root.join("notes/.cargo-nix"));
+    assert!(suspicious[1].1.contains("no Nix store references"), "{}", suspicious[1].1);
+
+    // Suspicious directories are never deleted
+    let lookalike = root.join("downloads/.cargo-nix");
+    assert!(!is_cargo_nix_dir(&lookalike));
+    assert_eq!(delete_cargo_nix_dir(&lookalike, false).expect("Deletion should be declined"), None);
+    assert!(dir_exists(&lookalike));
+    assert_eq!(suspicious_cargo_nix_dir(&root.join("crate/.cargo-nix")), None);
+
+    println!("✓ Suspicious .cargo-nix directories are reported, not deleted");
+}
+
+#[test]
+fn test_next_cache_only_keeps_build_output() {
+    use wd_40::cleaner::{clean_next_cache, next_build_output, Removal};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let web = temp_dir.path().join("web");
+    let next = web.join(".next");
+    let write = |path: PathBuf, size: usize| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, vec![b'x'; size]).expect("Failed to write file");
+    };
+    write(web.join("package.json"), 2);
+    write(next.join("cache/webpack/client-production/0.pack"), 4000);
+    write(next.join("cache/images/abc/photo.webp"), 3000);
+    write(next.join("server/pages/index.js"), 1000);
+    write(next.join("static/chunks/main.js"), 500);
+    write(next.join("BUILD_ID"), 21);
+
+    let estimate = clean_next_cache(&next, Removal::DryRun, false)
+        .expect("Failed to estimate")
+        .expect("The .next directory should validate");
+    assert_eq!(
+        estimate,
+        [(next.join("cache/images"), 3000), (next.join("cache/webpack"), 4000)]
+    );
+    assert_eq!(
+        next_build_output(&next),
+        [(next.join("BUILD_ID"), 21), (next.join("server"), 1000), (next.join("static"), 500)]
+    );
+
+    let removed = clean_next_cache(&next, Removal::Delete, false)
+        .expect("Failed to clean cache")
+        .expect("The .next directory should validate");
+    assert_eq!(removed, estimate);
+    assert!(dir_exists(&next.join("cache")) && !dir_exists(&next.join("cache/webpack")));
+    assert!(next.join("server/pages/index.js").exists());
+    assert!(next.join("static/chunks/main.js").exists());
+    assert!(next.join("BUILD_ID").exists());
+
+    println!("✓ Cache-only cleaning keeps the Next.js build output");
+}
+
+#[test]
+fn test_orphaned_node_modules_are_found_by_structure() {
+    use wd_40::cleaner::{delete_orphaned_node_modules_dir, is_node_modules_dir, is_orphaned_node_modules_dir};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path();
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+
+    // npm and pnpm installs whose package.json and lockfile were deleted or moved
+    write(root.join("old-npm/node_modules/.package-lock.json"), "{}");
+    write(root.join("old-npm/node_modules/lodash/package.json"), "{\"name\":\"lodash\"}");
+    write(root.join("old-pnpm/node_modules/.modules.yaml"), "layoutVersion: 5");
+    write(root.join("old-pnpm/node_modules/@types/node/package.json"), "{\"name\":\"@types/node\"}");
+
+    // A live project, a directory that merely has the name, and a global install prefix
+    write(root.join("web/package.json"), "{}");
+    write(root.join("web/node_modules/.package-lock.json"), "{}");
+    write(root.join("web/node_modules/react/package.json"), "{\"name\":\"react\"}");
+    write(root.join("notes/node_modules/todo.txt"), "not a package");
+    write(root.join("prefix/bin/npm"), "#!/bin/sh");
+    write(root.join("prefix/lib/node_modules/.package-lock.json"), "{}");
+    write(root.join("prefix/lib/node_modules/npm/package.json"), "{\"name\":\"npm\"}");
+
+    let discovered = wd_40::walker::find_artifacts(root, &wd_40::artifacts::Registry::builtin())
+        .expect("Failed to find artifacts");
+    let orphaned = [root.join("old-npm/node_modules"), root.join("old-pnpm/node_modules")];
+    assert_eq!(discovered.get(ArtifactKind::ORPHANED_NODE_MODULES), orphaned);
+    assert_eq!(discovered.get(ArtifactKind::NODE_MODULES), [root.join("web/node_modules")]);
+    assert!(!is_orphaned_node_modules_dir(&root.join("web/node_modules")));
+    assert!(!is_orphaned_node_modules_dir(&root.join("notes/node_modules")));
+    assert!(!is_orphaned_node_modules_dir(&root.join("prefix/lib/node_modules")));
+    assert!(!is_node_modules_dir(&orphaned[0]));
+
+    let freed = delete_orphaned_node_modules_dir(&orphaned[0], false).expect("Failed to delete orphan");
+    assert!(freed.is_some_and(|bytes| bytes > 0));
+    assert!(!dir_exists(&orphaned[0]));
+
+    // Once its project comes back it is an ordinary node_modules again
+    write(root.join("old-pnpm/pnpm-lock.yaml"), "lockfileVersion: 6");
+    assert_eq!(delete_orphaned_node_modules_dir(&orphaned[1], false).expect("Deletion should be declined"), None);
+    assert!(is_node_modules_dir(&orphaned[1]));
+
+    println!("✓ Orphaned node_modules are found by their structure");
+}
+
+#[test]
+fn test_quarantine_is_never_scanned() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path();
+    let write = |path: PathBuf, contents: &str| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, contents).expect("Failed to write file");
+    };
+    let node_project = |dir: PathBuf| {
+        write(dir.join("package.json"), "{}");
+        write(dir.join("node_modules/.package-lock.json"), "{}");
+        write(dir.join("node_modules/left-pad/package.json"), "{\"name\":\"left-pad\"}");
+    };
+
+    // A live project, one quarantined into the store (under the scanned home directory), and
+    // one quarantined into an area on another filesystem
+    node_project(root.join("web"));
+    let cache = root.join("home/.cache");
+    node_project(cache.join("wd-40/quarantine/20260101-000000-1/1-node_modules/foo"));
+    node_project(root.join("data/.wd40-quarantine/20260101-000000-1/2-node_modules/bar"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_wd-40"))
+        .args(["--dry-run", "--format", "json", "--no-dry-run-log", "--in-use-window", "0s"])
+        .arg(root)
+        .env("HOME", root.join("home"))
+        .env("XDG_CACHE_HOME", &cache)
+        .output()
+        .expect("Failed to run wd-40");
+    assert!(output.status.success(), "wd-40 failed: {}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("Invalid JSON report");
+    let paths: Vec<&str> = report["artifacts"]
+        .as_array()
+        .expect("artifacts array")
+        .iter()
+        .filter_map(|artifact| artifact["path"].as_str())
+        .collect();
+    assert_eq!(paths, [root.join("web/node_modules").to_str().unwrap()]);
+
+    println!("✓ Quarantined artifacts are never scanned");
+}
+
+#[test]
+fn test_failed_target_removal_fails_the_project() {
+    use wd_40::cleaner::{clean_validated, CleanStatus, Removal};
+    use wd_40::quarantine::QuarantineStore;
+    use wd_40::target_parts::TargetParts;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let project = temp_dir.path().join("app");
+    std::fs::create_dir_all(project.join("src")).expect("Failed to create project");
+    std::fs::write(project.join("Cargo.toml"), "[package]\nname = \"app\"\nversion = \"0.1.0\"\n")
+        .expect("Failed to write Cargo.toml");
+    std::fs::write(project.join("src/main.rs"), "fn main() {}").expect("Failed to write main.rs");
+    std::fs::create_dir_all(project.join("target/debug")).expect("Failed to create target");
+    std::fs::write(project.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55")
+        .expect("Failed to write CACHEDIR.TAG");
+
+    // The store disappears (replaced by a file) after the run began, so nothing can move into it
+    let store_root = temp_dir.path().join("store");
+    let quarantine = QuarantineStore::new(store_root.clone()).begin("run").expect("Failed to begin quarantine");
+    std::fs::remove_dir_all(&store_root).expect("Failed to remove store");
+    std::fs::write(&store_root, "not a directory").expect("Failed to write file");
+
+    let result = clean_validated(&project, &[], None, &TargetParts::default(), Removal::Quarantine(&quarantine), false, false)
+        .expect("Failed to clean project");
+    assert!(
+        matches!(&result.status, CleanStatus::Failed(error) if error.contains("quarantine")),
+        "A failed move must fail the project: {:?}",
+        result.status
+    );
+    assert_eq!(result.space_freed(), None);
+    assert!(dir_exists(&project.join("target")));
+
+    // The same goes when an invalid workspace only has its targets cleaned, members' ones included
+    let member = project.join("crates/core");
+    std::fs::create_dir_all(&member).expect("Failed to create member");
+    std::fs::write(member.join("Cargo.toml"), "[package]\nname = \"core\"\n").expect("Failed to write Cargo.toml");
+    std::fs::create_dir_all(member.join("target/debug")).expect("Failed to create member target");
+    std::fs::write(member.join("target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55")
+        .expect("Failed to write CACHEDIR.TAG");
+    let members = [member.clone()];
+    let invalid = || Some(Err("invalid manifest".to_string()));
+    let parts = TargetParts::default();
+
+    let estimate = clean_validated(&project, &members, invalid(), &parts, Removal::DryRun, false, false)
+        .expect("Failed to estimate");
+    assert!(estimate.is_target_only(), "{:?}", estimate.status);
+    assert_eq!(estimate.space_freed(), Some(2 * 43), "Both the root's and the member's target count");
+
+    let result = clean_validated(&project, &members, invalid(), &parts, Removal::Quarantine(&quarantine), false, false)
+        .expect("Failed to clean project");
+    assert!(
+        matches!(&result.status, CleanStatus::Failed(error) if error.contains("quarantine")),
+        "A failed move must fail a target-only clean too: {:?}",
+        result.status
+    );
+    assert!(dir_exists(&member.join("target")));
+
+    println!("✓ A target that can't be removed fails its project");
+}
+
+#[test]
+fn test_space_goal_sizes_what_would_be_removed() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("src");
+    let write = |path: PathBuf, size: usize| {
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        std::fs::write(path, vec![b'x'; size]).expect("Failed to write file");
+    };
+    write(root.join("web/package.json"), 2);
+    write(root.join("web/.next/cache/webpack/0.pack"), 4000);
+    write(root.join("web/.next/server/pages/index.js"), 1000);
+    write(root.join("web/.next/BUILD_ID"), 21);
+
+    let log = temp_dir.path().join("clean.jsonl");
+    let output = Command::new(env!("CARGO_BIN_EXE_wd-40"))
+        .args(["--dry-run", "--next-cache-only", "--free-at-least", "1", "--in-use-window", "0s"])
+        .args(["--log-format", "jsonl", "--log-file"])
+        .arg(&log)
+        .arg(&root)
+        .env("HOME", temp_dir.path())
+        .env("XDG_CACHE_HOME", temp_dir.path().join(".cache"))
+        .output()
+        .expect("Failed to run wd-40");
+    assert!(output.status.success(), "wd-40 failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(&log)
+        .expect("Failed to read log")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Invalid log line"))
+        .collect();
+    let candidate = events
+        .iter()
+        .find(|event| event["event"] == "budget_candidate")
+        .expect("The .next directory should be a budget candidate");
+    assert_eq!(candidate["path"], root.join("web/.next").to_str().unwrap());
+    assert_eq!(candidate["size_bytes"], 4000, "Only the cache is planned for, not the build output");
+
+    println!("✓ Space goals size items by what would really be removed");
+}
+
+#[test]
+fn test_runs_that_find_nothing_are_recorded_in_history() {
+    use wd_40::history::History;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("empty");
+    std::fs::create_dir_all(&root).expect("Failed to create search root");
+    let cache = temp_dir.path().join("cache");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_wd-40"))
+        .args(["--dry-run", "--in-use-window", "0s", "--log-file"])
+        .arg(temp_dir.path().join("clean.log"))
+        .arg(&root)
+        .env("XDG_CACHE_HOME", &cache)
+        .output()
+        .expect("Failed to run wd-40");
+    assert!(output.status.success(), "wd-40 failed: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No artifacts found."));
+
+    let runs = History::new(cache.join("wd-40/history.jsonl")).load().expect("Failed to load history");
+    assert_eq!(runs.len(), 1, "The run should be recorded");
+    assert_eq!(runs[0].root, root);
+    assert!(runs[0].cleaned.is_empty());
+
+    println!("✓ Runs that find nothing are recorded in the history");
+}
@@ -364,10 +364,11 @@ mkdir -p rust-nix-project-1/.cargo-nix/target
 echo "cached-artifact" > rust-nix-project-1/.cargo-nix/target/artifact.o
 echo "cached-deps" > rust-nix-project-1/.cargo-nix/deps.lock
 
-# Create cargo-nix directory 2 - minimal structure
+# Create cargo-nix directory 2 - minimal structure in a flake, pointing into the Nix store
 mkdir -p rust-nix-project-2/.cargo-nix
+echo '{ outputs = { self }: { }; }' > rust-nix-project-2/flake.nix
 echo "cache-data" > rust-nix-project-2/.cargo-nix/cache.bin
-echo "nix-store-path" > rust-nix-project-2/.cargo-nix/store-path
+echo "/nix/store/0c5n0v3b7yq8vrmbnzw2ijs1ykl5hc2x-rust-nix-project-2-0.1.0" > rust-nix-project-2/.cargo-nix/store-path
 
 echo "Test artifacts setup complete!"
 echo ""